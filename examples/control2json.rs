@@ -13,11 +13,7 @@ fn paragraph_to_json(paragraph: Paragraph) -> JsonValue {
 }
 
 fn main() -> anyhow::Result<()> {
-    let filename: PathBuf = args_os()
-        .skip(1)
-        .next()
-        .context("specify a file name")?
-        .into();
+    let filename: PathBuf = args_os().nth(1).context("specify a file name")?.into();
 
     let input = read_to_string(filename)?;
     let json: JsonValue = parse_str(&input)
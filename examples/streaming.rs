@@ -31,7 +31,7 @@ fn get_input(arg: Option<OsString>) -> anyhow::Result<Box<dyn Read>> {
 }
 
 fn main() -> anyhow::Result<()> {
-    let arg = args_os().skip(1).next();
+    let arg = args_os().nth(1);
     let mut parse = BufParse::new(get_input(arg)?, 4096);
 
     while let Some(result) = parse.try_next().map_err(stringify)? {
@@ -0,0 +1,246 @@
+//! Parsing of RFC 822 style addresses in fields such as `Maintainer` and `Uploaders`.
+
+use crate::{Field, ListSeparator, OwnedField};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// An address that couldn't be parsed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AddressError {
+    /// The offending address.
+    pub address: String,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid address '{}'", self.address)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AddressError {}
+
+/// A name and email address, as in `Jane Doe <jane@example.org>`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Address<'a> {
+    /// The display name without surrounding quotes, if there is one.
+    pub name: Option<&'a str>,
+    pub email: &'a str,
+}
+
+impl<'a> Address<'a> {
+    /// Return the email address in lowercase, for grouping addresses that only differ in case.
+    pub fn email_key(&self) -> String {
+        self.email.to_ascii_lowercase()
+    }
+}
+
+impl fmt::Display for Address<'_> {
+    /// Write the address as `Name <email>`, quoting the name if it contains a comma.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) if name.contains(',') => write!(f, "\"{}\" <{}>", name, self.email),
+            Some(name) => write!(f, "{} <{}>", name, self.email),
+            None => f.write_str(self.email),
+        }
+    }
+}
+
+/// Strip surrounding double quotes from a name.
+fn unquote(name: &str) -> Option<&str> {
+    match name.strip_prefix('"') {
+        Some(rest) => rest.strip_suffix('"'),
+        None if name.contains('"') => None,
+        None => Some(name),
+    }
+}
+
+/// Return true if this looks like an email address.
+fn is_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty() && !domain.is_empty() && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Parse a single address.
+///
+/// Addresses are usually written as `Name <email>`, where the name may be quoted. The older
+/// `email (Name)` form and bare email addresses are accepted as well.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::parse_address;
+/// let address = parse_address("\"Doe, Jane\" <jane@example.org>").unwrap();
+/// assert_eq!(address.name, Some("Doe, Jane"));
+/// assert_eq!(address.email, "jane@example.org");
+/// ```
+pub fn parse_address(value: &str) -> Result<Address<'_>, AddressError> {
+    let invalid = || AddressError {
+        address: String::from(value),
+    };
+    let value = value.trim();
+    let (name, email) = if let Some((name, rest)) = value.split_once('<') {
+        let email = rest.strip_suffix('>').ok_or_else(invalid)?;
+        (Some(name.trim()), email.trim())
+    } else if let Some((email, rest)) = value.split_once('(') {
+        let name = rest.strip_suffix(')').ok_or_else(invalid)?;
+        (Some(name.trim()), email.trim())
+    } else {
+        (None, value)
+    };
+    let name = match name {
+        Some(name) => Some(unquote(name).ok_or_else(invalid)?).filter(|name| !name.is_empty()),
+        None => None,
+    };
+    if !is_email(email) {
+        return Err(invalid());
+    }
+    Ok(Address { name, email })
+}
+
+/// Parse a comma-separated list of addresses, as in `Uploaders`.
+///
+/// The list may span continuation lines and end with a trailing comma. Names that contain commas
+/// must be quoted.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::parse_addresses;
+/// let addresses = parse_addresses("\
+/// a <a@example.org>,
+/// \"Roe, Richard\" <r@example.org>,").unwrap();
+/// assert_eq!(addresses.len(), 2);
+/// assert_eq!(addresses[1].name, Some("Roe, Richard"));
+/// ```
+pub fn parse_addresses(value: &str) -> Result<Vec<Address<'_>>, AddressError> {
+    ListSeparator::Comma
+        .split(value)
+        .into_iter()
+        .map(parse_address)
+        .collect()
+}
+
+macro_rules! address_methods {
+    ($type: ty) => {
+        impl $type {
+            /// Parse the value as a single address, as in `Maintainer` or `Changed-By`.
+            pub fn address(&self) -> Result<Address<'_>, AddressError> {
+                parse_address(&self.value)
+            }
+
+            /// Parse the value as a list of addresses, as in `Uploaders`.
+            pub fn addresses(&self) -> Result<Vec<Address<'_>>, AddressError> {
+                parse_addresses(&self.value)
+            }
+        }
+    };
+}
+
+address_methods!(Field<'_>);
+address_methods!(OwnedField);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::{string::ToString, vec};
+    use indoc::indoc;
+
+    fn address<'a>(name: Option<&'a str>, email: &'a str) -> Address<'a> {
+        Address { name, email }
+    }
+
+    #[test]
+    fn should_parse_name_and_email() {
+        assert_eq!(
+            parse_address("Jane Doe <jane@example.org>"),
+            Ok(address(Some("Jane Doe"), "jane@example.org"))
+        );
+        assert_eq!(
+            parse_address("  Debian QA Group<packages@qa.debian.org> "),
+            Ok(address(Some("Debian QA Group"), "packages@qa.debian.org"))
+        );
+    }
+
+    #[test]
+    fn should_parse_alternative_forms() {
+        assert_eq!(
+            parse_address("jane@example.org (Jane Doe)"),
+            Ok(address(Some("Jane Doe"), "jane@example.org"))
+        );
+        assert_eq!(
+            parse_address("jane@example.org"),
+            Ok(address(None, "jane@example.org"))
+        );
+        assert_eq!(
+            parse_address("<jane@example.org>"),
+            Ok(address(None, "jane@example.org"))
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_addresses() {
+        for value in [
+            "Jane Doe",
+            "Jane <jane@example.org",
+            "Jane <>",
+            "\"Jane <jane@example.org>",
+            "Jane <jane at example.org>",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                parse_address(value),
+                Err(AddressError {
+                    address: value.to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn should_parse_multiline_uploaders() {
+        let paragraphs = parse_str(indoc!(
+            "
+            Maintainer: Utopia Maintenance Team <pkg-utopia@lists.example.org>
+            Uploaders:
+             \"McVittie, Simon\" <smcv@debian.org>,
+             Sjoerd Simons <sjoerd@debian.org>,
+            "
+        ))
+        .unwrap();
+        let paragraph = &paragraphs[0];
+        assert_eq!(
+            paragraph.field("Maintainer").unwrap().address(),
+            Ok(address(
+                Some("Utopia Maintenance Team"),
+                "pkg-utopia@lists.example.org"
+            ))
+        );
+        assert_eq!(
+            paragraph.field("Uploaders").unwrap().addresses(),
+            Ok(vec![
+                address(Some("McVittie, Simon"), "smcv@debian.org"),
+                address(Some("Sjoerd Simons"), "sjoerd@debian.org"),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_write_addresses() {
+        let quoted = address(Some("Roe, Richard"), "r@example.org");
+        assert_eq!(quoted.to_string(), "\"Roe, Richard\" <r@example.org>");
+        assert_eq!(parse_address(&quoted.to_string()), Ok(quoted));
+        assert_eq!(address(None, "a@b").to_string(), "a@b");
+    }
+
+    #[test]
+    fn should_group_by_lowercase_email() {
+        let a = parse_address("A <Jane@Example.org>").unwrap();
+        let b = parse_address("B <jane@example.org>").unwrap();
+        assert_eq!(a.email_key(), b.email_key());
+    }
+}
@@ -0,0 +1,379 @@
+//! Debian architecture names and wildcards.
+//!
+//! Every Debian architecture corresponds to a tuple of ABI, libc, operating system and CPU, as
+//! defined by dpkg's `tupletable` and `cputable`. Architecture wildcards such as `linux-any` or
+//! `any-amd64` match all architectures whose tuples agree in the parts that are not `any`. This
+//! module reproduces dpkg's tables, extended with the architectures used by AOSC OS, and its
+//! matching rules, so [`matches`](fn.matches.html) behaves like `dpkg-architecture -a <arch> -i
+//! <wildcard>`.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::arch::{matches, matches_list, tuple};
+//! assert!(matches("linux-any", "loongarch64"));
+//! assert!(matches("any-arm", "armhf"));
+//! assert!(!matches("any-mips64el", "loongson3"));
+//! assert_eq!(tuple("armhf").unwrap().to_string(), "eabihf-gnu-linux-arm");
+//!
+//! assert_eq!(matches_list("!amd64 !i386", "arm64"), Ok(true));
+//! assert_eq!(matches_list("!amd64 !i386", "amd64"), Ok(false));
+//! ```
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// The `any` wildcard, which matches every architecture and tuple part.
+const ANY: &str = "any";
+
+/// The placeholder for a CPU name in [`TUPLE_TABLE`](constant.TUPLE_TABLE.html).
+const CPU_PLACEHOLDER: &str = "<cpu>";
+
+/// Known CPU names, as in dpkg's `cputable`, followed by the CPUs only used by AOSC OS.
+pub const CPUS: &[&str] = &[
+    "i386",
+    "ia64",
+    "alpha",
+    "amd64",
+    "armeb",
+    "arm",
+    "arm64",
+    "arc",
+    "avr32",
+    "hppa",
+    "loong64",
+    "m32r",
+    "m68k",
+    "mips",
+    "mipsel",
+    "mipsr6",
+    "mipsr6el",
+    "mips64",
+    "mips64el",
+    "mips64r6",
+    "mips64r6el",
+    "nios2",
+    "or1k",
+    "powerpc",
+    "powerpcel",
+    "ppc64",
+    "ppc64el",
+    "riscv64",
+    "s390",
+    "s390x",
+    "sh3",
+    "sh3eb",
+    "sh4",
+    "sh4eb",
+    "sparc",
+    "sparc64",
+    "loongarch64",
+    "loongson3",
+];
+
+/// Mapping of tuples to architecture names, as in dpkg's `tupletable`.
+///
+/// `<cpu>` stands for every CPU in [`CPUS`](constant.CPUS.html). When several entries map to the
+/// same architecture name or tuple, the first one wins.
+pub const TUPLE_TABLE: &[(&str, &str)] = &[
+    ("eabihf-musl-linux-arm", "musl-linux-armhf"),
+    ("base-musl-linux-<cpu>", "musl-linux-<cpu>"),
+    ("ilp32-gnu-linux-arm64", "arm64ilp32"),
+    ("eabihf-gnu-linux-arm", "armhf"),
+    ("eabi-gnu-linux-arm", "armel"),
+    ("abin32-gnu-linux-mips64r6el", "mipsn32r6el"),
+    ("abin32-gnu-linux-mips64r6", "mipsn32r6"),
+    ("abin32-gnu-linux-mips64el", "mipsn32el"),
+    ("abin32-gnu-linux-mips64", "mipsn32"),
+    ("abi64-gnu-linux-mips64r6el", "mips64r6el"),
+    ("abi64-gnu-linux-mips64r6", "mips64r6"),
+    ("abi64-gnu-linux-mips64el", "mips64el"),
+    ("abi64-gnu-linux-mips64", "mips64"),
+    ("abi64-gnu-linux-loongson3", "loongson3"),
+    ("spe-gnu-linux-powerpc", "powerpcspe"),
+    ("x32-gnu-linux-amd64", "x32"),
+    ("base-gnu-linux-<cpu>", "<cpu>"),
+    ("eabihf-gnu-kfreebsd-arm", "kfreebsd-armhf"),
+    ("base-gnu-kfreebsd-<cpu>", "kfreebsd-<cpu>"),
+    ("base-gnu-knetbsd-<cpu>", "knetbsd-<cpu>"),
+    ("base-gnu-kopensolaris-<cpu>", "kopensolaris-<cpu>"),
+    ("base-gnu-hurd-<cpu>", "hurd-<cpu>"),
+    ("base-bsd-dragonflybsd-<cpu>", "dragonflybsd-<cpu>"),
+    ("base-bsd-freebsd-<cpu>", "freebsd-<cpu>"),
+    ("base-bsd-openbsd-<cpu>", "openbsd-<cpu>"),
+    ("base-bsd-netbsd-<cpu>", "netbsd-<cpu>"),
+    ("base-bsd-darwin-<cpu>", "darwin-<cpu>"),
+    ("base-sysv-aix-<cpu>", "aix-<cpu>"),
+    ("base-sysv-solaris-<cpu>", "solaris-<cpu>"),
+    ("eabi-uclibc-linux-arm", "uclibc-linux-armel"),
+    ("base-uclibc-linux-<cpu>", "uclibc-linux-<cpu>"),
+];
+
+/// An error in a list of architecture wildcards.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ArchError {
+    /// The list mixes negated and plain entries, as in `amd64 !i386`.
+    MixedNegation(String),
+    /// The list is empty.
+    Empty,
+}
+
+impl fmt::Display for ArchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchError::MixedNegation(list) => write!(
+                f,
+                "architecture list '{}' mixes negated and plain entries",
+                list
+            ),
+            ArchError::Empty => write!(f, "architecture list is empty"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ArchError {}
+
+/// The tuple an architecture or wildcard stands for.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Tuple<'a> {
+    pub abi: &'a str,
+    pub libc: &'a str,
+    pub os: &'a str,
+    pub cpu: &'a str,
+}
+
+impl<'a> Tuple<'a> {
+    /// Split a tuple such as `base-gnu-linux-amd64` into its parts.
+    fn split(tuple: &'a str) -> Option<Tuple<'a>> {
+        let mut parts = tuple.splitn(4, '-');
+        Some(Tuple {
+            abi: parts.next()?,
+            libc: parts.next()?,
+            os: parts.next()?,
+            cpu: parts.next()?,
+        })
+    }
+
+    /// Return true if this tuple, which may contain `any`, matches the other tuple.
+    fn matches(&self, other: &Tuple<'_>) -> bool {
+        let part = |wildcard: &str, part: &str| wildcard == ANY || wildcard == part;
+        part(self.abi, other.abi)
+            && part(self.libc, other.libc)
+            && part(self.os, other.os)
+            && part(self.cpu, other.cpu)
+    }
+}
+
+impl fmt::Display for Tuple<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}-{}", self.abi, self.libc, self.os, self.cpu)
+    }
+}
+
+/// Return the known CPU with the given name.
+fn known_cpu(cpu: &str) -> Option<&'static str> {
+    CPUS.iter().copied().find(|known| *known == cpu)
+}
+
+/// Match a name against a table pattern that may contain `<cpu>`, returning the CPU.
+fn match_pattern(pattern: &str, name: &str) -> Option<Option<&'static str>> {
+    match pattern.split_once(CPU_PLACEHOLDER) {
+        Some((prefix, suffix)) => {
+            let cpu = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            known_cpu(cpu).map(Some)
+        }
+        None if pattern == name => Some(None),
+        None => None,
+    }
+}
+
+/// Return the tuple of a concrete architecture, or `None` if the architecture is unknown.
+pub fn tuple(arch: &str) -> Option<Tuple<'static>> {
+    TUPLE_TABLE.iter().find_map(|(tuple, pattern)| {
+        let cpu = match_pattern(pattern, arch)?;
+        let tuple = Tuple::split(tuple)?;
+        Some(match cpu {
+            Some(cpu) => Tuple { cpu, ..tuple },
+            None => tuple,
+        })
+    })
+}
+
+/// Return the architecture name for a tuple, or `None` if no architecture has this tuple.
+pub fn from_tuple(tuple: &Tuple<'_>) -> Option<String> {
+    TUPLE_TABLE.iter().find_map(|(entry, pattern)| {
+        let entry = Tuple::split(entry)?;
+        if (entry.abi, entry.libc, entry.os) != (tuple.abi, tuple.libc, tuple.os) {
+            return None;
+        }
+        if entry.cpu == CPU_PLACEHOLDER {
+            known_cpu(tuple.cpu)?;
+            Some(pattern.replace(CPU_PLACEHOLDER, tuple.cpu))
+        } else if entry.cpu == tuple.cpu {
+            Some(String::from(*pattern))
+        } else {
+            None
+        }
+    })
+}
+
+/// Return the tuple of a wildcard.
+///
+/// Wildcards with fewer than four parts are filled up with `any` from the left, so `linux-any`
+/// stands for `any-any-linux-any`. Names without `any` are looked up as concrete architectures.
+fn wildcard_tuple(wildcard: &str) -> Option<Tuple<'_>> {
+    if !wildcard.split('-').any(|part| part == ANY) {
+        return tuple(wildcard);
+    }
+    let mut parts = [ANY; 4];
+    let given = wildcard.splitn(4, '-').collect::<Vec<_>>();
+    parts[4 - given.len()..].copy_from_slice(&given);
+    Some(Tuple {
+        abi: parts[0],
+        libc: parts[1],
+        os: parts[2],
+        cpu: parts[3],
+    })
+}
+
+/// Return true if the name is an architecture wildcard such as `any` or `linux-any`.
+pub fn is_wildcard(arch: &str) -> bool {
+    arch.split('-').any(|part| part == ANY)
+}
+
+/// Return true if the architecture matches the wildcard.
+///
+/// The wildcard may also be a concrete architecture, which only matches itself. `any` matches
+/// every architecture, including ones unknown to this module and `all`; other wildcards only
+/// match known architectures.
+pub fn matches(wildcard: &str, arch: &str) -> bool {
+    if wildcard == arch || wildcard == ANY {
+        return true;
+    }
+    match (wildcard_tuple(wildcard), tuple(arch)) {
+        (Some(wildcard), Some(arch)) => wildcard.matches(&arch),
+        _ => false,
+    }
+}
+
+/// Return true if the architecture matches a whitespace-separated list of wildcards, as in
+/// `Architecture` fields and the `[...]` qualifiers of relationships.
+///
+/// A plain list matches if any of its entries matches. A list of negated entries such as
+/// `!amd64 !i386` matches if none of its entries matches. Lists that mix both are invalid.
+pub fn matches_list(list: &str, arch: &str) -> Result<bool, ArchError> {
+    let mut entries = list.split_whitespace().peekable();
+    let negated = entries.peek().ok_or(ArchError::Empty)?.starts_with('!');
+    let mut matched = false;
+    for entry in entries {
+        let wildcard = match (negated, entry.strip_prefix('!')) {
+            (true, Some(wildcard)) => wildcard,
+            (false, None) => entry,
+            _ => return Err(ArchError::MixedNegation(String::from(list))),
+        };
+        matched |= matches(wildcard, arch);
+    }
+    Ok(matched != negated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn should_look_up_tuples() {
+        let cases = [
+            ("amd64", "base-gnu-linux-amd64"),
+            ("i386", "base-gnu-linux-i386"),
+            ("armhf", "eabihf-gnu-linux-arm"),
+            ("armel", "eabi-gnu-linux-arm"),
+            ("arm64ilp32", "ilp32-gnu-linux-arm64"),
+            ("mips64el", "abi64-gnu-linux-mips64el"),
+            ("mips64r6el", "abi64-gnu-linux-mips64r6el"),
+            ("mipsn32r6el", "abin32-gnu-linux-mips64r6el"),
+            ("loongarch64", "base-gnu-linux-loongarch64"),
+            ("loongson3", "abi64-gnu-linux-loongson3"),
+            ("x32", "x32-gnu-linux-amd64"),
+            ("musl-linux-armhf", "eabihf-musl-linux-arm"),
+            ("musl-linux-riscv64", "base-musl-linux-riscv64"),
+            ("kfreebsd-amd64", "base-gnu-kfreebsd-amd64"),
+            ("hurd-i386", "base-gnu-hurd-i386"),
+            ("darwin-arm64", "base-bsd-darwin-arm64"),
+        ];
+        for (arch, expected) in cases.iter() {
+            assert_eq!(tuple(arch).unwrap().to_string(), *expected, "{}", arch);
+            let parsed = Tuple::split(expected).unwrap();
+            assert_eq!(from_tuple(&parsed).as_deref(), Some(*arch), "{}", expected);
+        }
+    }
+
+    #[test]
+    fn should_not_know_invalid_architectures() {
+        for arch in ["all", "any", "foo", "linux-foo", "musl-linux-", ""].iter() {
+            assert_eq!(tuple(arch), None, "{}", arch);
+        }
+    }
+
+    #[test]
+    fn should_match_like_dpkg_architecture() {
+        let cases = [
+            ("any", "amd64", true),
+            ("any", "all", true),
+            ("any", "foo", true),
+            ("all", "all", true),
+            ("all", "amd64", false),
+            ("amd64", "amd64", true),
+            ("amd64", "i386", false),
+            ("linux-any", "amd64", true),
+            ("linux-any", "loongarch64", true),
+            ("linux-any", "hurd-i386", false),
+            ("linux-any", "all", false),
+            ("any-amd64", "amd64", true),
+            ("any-amd64", "x32", true),
+            ("any-amd64", "kfreebsd-amd64", true),
+            ("any-amd64", "i386", false),
+            ("any-arm", "armel", true),
+            ("any-arm", "armhf", true),
+            ("any-arm", "arm64", false),
+            ("any-mips64el", "mips64el", true),
+            ("any-mips64el", "mipsn32el", true),
+            ("any-mips64el", "loongson3", false),
+            ("any-loongson3", "loongson3", true),
+            ("any-mips64r6el", "mips64r6el", true),
+            ("gnu-any-any", "amd64", true),
+            ("gnu-any-any", "musl-linux-amd64", false),
+            ("musl-any-any", "musl-linux-amd64", true),
+            ("eabihf-any-any-arm", "armhf", true),
+            ("eabihf-any-any-arm", "armel", false),
+            ("any-any-any-any", "amd64", true),
+            ("linux-any", "foo", false),
+            ("foo", "foo", true),
+        ];
+        for (wildcard, arch, expected) in cases.iter() {
+            assert_eq!(matches(wildcard, arch), *expected, "{} {}", wildcard, arch);
+        }
+    }
+
+    #[test]
+    fn should_detect_wildcards() {
+        assert!(is_wildcard("any"));
+        assert!(is_wildcard("linux-any"));
+        assert!(is_wildcard("any-amd64"));
+        assert!(!is_wildcard("amd64"));
+        assert!(!is_wildcard("all"));
+    }
+
+    #[test]
+    fn should_match_lists() {
+        assert_eq!(matches_list("amd64 linux-any", "riscv64"), Ok(true));
+        assert_eq!(matches_list("amd64 i386", "riscv64"), Ok(false));
+        assert_eq!(matches_list("!linux-any", "hurd-i386"), Ok(true));
+        assert_eq!(matches_list("!linux-any", "amd64"), Ok(false));
+        assert_eq!(matches_list("  ", "amd64"), Err(ArchError::Empty));
+        assert_eq!(
+            matches_list("amd64 !i386", "amd64"),
+            Err(ArchError::MixedNegation(String::from("amd64 !i386")))
+        );
+    }
+}
@@ -1,5 +1,8 @@
-use crate::{parse_finish, parse_streaming, Paragraph, Streaming, SyntaxError};
-use alloc::vec::Vec;
+use crate::{parse_finish, parse_streaming, OwnedParagraph, Paragraph, Streaming, SyntaxError};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::{
     fmt,
     str::{from_utf8, Utf8Error},
@@ -70,6 +73,50 @@ impl std::error::Error for BufParseError<'_> {
     }
 }
 
+/// An error type returned by [`BufParse::next_owned`](struct.BufParse.html#method.next_owned).
+///
+/// Unlike [`BufParseError`](enum.BufParseError.html), this error doesn't borrow from the parser's
+/// buffer, so it can be returned from functions that consume the whole input.
+#[derive(Debug)]
+pub enum ReadError<E> {
+    /// Reading from the input source failed.
+    Read(E),
+    /// The input stream was not valid UTF-8.
+    InvalidUtf8(Utf8Error),
+    /// There was a syntax error in the input stream. This holds the formatted syntax error.
+    InvalidSyntax(String),
+}
+
+impl<E: fmt::Display> fmt::Display for ReadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Read(err) => write!(f, "failed to read input: {}", err),
+            ReadError::InvalidUtf8(err) => write!(f, "invalid utf-8 in input: {}", err),
+            ReadError::InvalidSyntax(err) => write!(f, "invalid syntax: {}", err),
+        }
+    }
+}
+
+impl<E> From<BufParseError<'_>> for ReadError<E> {
+    fn from(err: BufParseError<'_>) -> Self {
+        match err {
+            BufParseError::InvalidUtf8(err) => ReadError::InvalidUtf8(err),
+            BufParseError::InvalidSyntax(err) => ReadError::InvalidSyntax(err.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for ReadError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Read(err) => Some(err),
+            ReadError::InvalidUtf8(err) => Some(err),
+            ReadError::InvalidSyntax(_) => None,
+        }
+    }
+}
+
 /// A streaming control file parser that buffers input internally.
 ///
 /// This type handles incrementally reading and buffering input from a source implementing the
@@ -78,7 +125,7 @@ impl std::error::Error for BufParseError<'_> {
 /// # Example
 /// ```
 /// # #[cfg(feature = "std")] {
-/// use debcontrol::{BufParse, Streaming};
+/// use oma_debcontrol::{BufParse, Streaming};
 /// use std::fs::File;
 ///
 /// # let file_name = format!("{}/tests/control", env!("CARGO_MANIFEST_DIR"));
@@ -150,7 +197,7 @@ impl<R: BufParseInput> BufParse<R> {
     ///   [`buffer`](struct.BufParse.html#method.buffer) to read more input.
     /// * If it's [`Streaming::Item`](enum.Streaming.html#variant.Item), a paragraph was parsed.
     ///   Call `try_next` again after processing it.
-    pub fn try_next(&mut self) -> Result<Option<Streaming<Paragraph>>, BufParseError> {
+    pub fn try_next(&mut self) -> Result<Option<Streaming<Paragraph<'_>>>, BufParseError<'_>> {
         let input = self.as_longest_utf8(&self.buf)?;
 
         match parse_streaming(input)? {
@@ -172,6 +219,21 @@ impl<R: BufParseInput> BufParse<R> {
         }
     }
 
+    /// Parse the next paragraph from the input into an owned value.
+    ///
+    /// This is a convenience wrapper around [`try_next`](struct.BufParse.html#method.try_next) that
+    /// reads more input whenever needed. It returns `None` once all input has been parsed. Since
+    /// the returned paragraph doesn't borrow from the parser, it can be collected or stored.
+    pub fn next_owned(&mut self) -> Result<Option<OwnedParagraph>, ReadError<R::Error>> {
+        loop {
+            match self.try_next()? {
+                Some(Streaming::Item(paragraph)) => return Ok(Some(paragraph.into())),
+                Some(Streaming::Incomplete) => self.buffer().map_err(ReadError::Read)?,
+                None => return Ok(None),
+            }
+        }
+    }
+
     /// Consume this `BufParse` and return the wrapped input source.
     ///
     /// Any input that was already buffered will be lost.
@@ -341,6 +403,28 @@ mod tests {
         assert_matches!(parse.try_next(), Err(BufParseError::InvalidUtf8(_)));
     }
 
+    #[test]
+    fn should_read_owned_paragraphs() {
+        let mut parse = BufParse::new(Bytes::new(b"a: 1\n\nb: 2\n 3\n\nc: 4"), 3);
+        let mut paragraphs = vec![];
+        while let Some(paragraph) = parse.next_owned().unwrap() {
+            paragraphs.push(paragraph);
+        }
+        let values = paragraphs
+            .iter()
+            .flat_map(|p| p.fields.iter().map(|f| (f.name.as_str(), f.value.as_str())))
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![("a", "1"), ("b", "2\n3"), ("c", "4")]);
+        assert_matches!(parse.next_owned(), Ok(None));
+    }
+
+    #[test]
+    fn should_return_owned_error_on_invalid_syntax() {
+        let mut parse = BufParse::new(Bytes::new(b"a: 1\n\n-b: 2\n"), 100);
+        assert_matches!(parse.next_owned(), Ok(Some(_)));
+        assert_matches!(parse.next_owned(), Err(ReadError::InvalidSyntax(_)));
+    }
+
     #[test]
     fn should_return_inner() {
         let input = Bytes::new(b"abcd");
@@ -0,0 +1,337 @@
+//! Semantic comparison of control files.
+
+use crate::{FieldRegistry, Paragraph};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+/// A field that differs between two matching paragraphs.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct FieldChange<'p> {
+    pub name: &'p str,
+    /// The old value, or `None` if the field was added.
+    pub old: Option<&'p str>,
+    /// The new value, or `None` if the field was removed.
+    pub new: Option<&'p str>,
+}
+
+impl fmt::Display for FieldChange<'_> {
+    /// Write the field name and the kind of change, such as `Depends changed`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = match (self.old, self.new) {
+            (None, _) => "added",
+            (_, None) => "removed",
+            _ => "changed",
+        };
+        write!(f, "{} {}", self.name, change)
+    }
+}
+
+/// A paragraph that exists in both files, but with different fields.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ParagraphChange<'p, 'a> {
+    /// The values of the key fields.
+    pub key: Vec<&'p str>,
+    pub old: &'p Paragraph<'a>,
+    pub new: &'p Paragraph<'a>,
+    /// The changed fields, in the order of the old paragraph followed by added fields.
+    pub fields: Vec<FieldChange<'p>>,
+}
+
+impl ParagraphChange<'_, '_> {
+    /// Return the change of the field with the given name, if it changed.
+    pub fn field(&self, name: &str) -> Option<&FieldChange<'_>> {
+        self.fields
+            .iter()
+            .find(|change| change.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for ParagraphChange<'_, '_> {
+    /// Write a summary such as `foo 1.2 -> 1.3, Depends changed`.
+    ///
+    /// The key values are joined with colons, as in `foo:amd64`. A changed `Version` is shown
+    /// with both values, all other fields only by name.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key.join(":"))?;
+        let mut separator = " ";
+        if let Some(FieldChange {
+            old: Some(old),
+            new: Some(new),
+            ..
+        }) = self.field("Version")
+        {
+            write!(f, "{}{} -> {}", separator, old, new)?;
+            separator = ", ";
+        }
+        for change in &self.fields {
+            if change.name.eq_ignore_ascii_case("Version")
+                && change.old.is_some() == change.new.is_some()
+            {
+                continue;
+            }
+            write!(f, "{}{}", separator, change)?;
+            separator = ", ";
+        }
+        Ok(())
+    }
+}
+
+/// The differences between two control files, as computed by [`diff`](fn.diff.html).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct ControlDiff<'p, 'a> {
+    /// Paragraphs only in the new file, in their order there.
+    pub added: Vec<&'p Paragraph<'a>>,
+    /// Paragraphs only in the old file, in their order there.
+    pub removed: Vec<&'p Paragraph<'a>>,
+    /// Paragraphs in both files with different fields, in the order of the old file.
+    pub changed: Vec<ParagraphChange<'p, 'a>>,
+}
+
+impl ControlDiff<'_, '_> {
+    /// Return true if both files contain the same paragraphs.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the fields of two paragraphs.
+///
+/// Field names are compared case-insensitively and independent of their order. Folded fields such
+/// as `Depends` are compared after folding, so rewrapping them is not a change.
+fn diff_fields<'p>(old: &'p Paragraph<'_>, new: &'p Paragraph<'_>) -> Vec<FieldChange<'p>> {
+    let registry = FieldRegistry::new();
+    let mut changes = Vec::new();
+    for (index, field) in old.fields.iter().enumerate() {
+        let duplicate = old.fields[..index]
+            .iter()
+            .any(|earlier| earlier.name.eq_ignore_ascii_case(field.name));
+        if duplicate {
+            continue;
+        }
+        match new.field(field.name) {
+            Some(other) if other.value_in(&registry) == field.value_in(&registry) => {}
+            other => changes.push(FieldChange {
+                name: field.name,
+                old: Some(&field.value),
+                new: other.map(|other| other.value.as_str()),
+            }),
+        }
+    }
+    for (index, field) in new.fields.iter().enumerate() {
+        let duplicate = new.fields[..index]
+            .iter()
+            .any(|earlier| earlier.name.eq_ignore_ascii_case(field.name));
+        if !duplicate && old.field(field.name).is_none() {
+            changes.push(FieldChange {
+                name: field.name,
+                old: None,
+                new: Some(&field.value),
+            });
+        }
+    }
+    changes
+}
+
+/// Compare two control files paragraph by paragraph.
+///
+/// Paragraphs are matched by the values of the key fields, which are given as field names
+/// separated by whitespace, such as `"Package Architecture"`. Missing key fields have an empty
+/// value. If several paragraphs in a file have the same key, such as several versions of a package,
+/// they are matched in order.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{diff, parse_str};
+/// let old = parse_str("\
+/// Package: foo
+/// Version: 1.2
+/// Depends: libc6
+///
+/// Package: bar
+/// Version: 1.0
+/// ").unwrap();
+/// let new = parse_str("\
+/// Package: foo
+/// Depends: libc6, zlib1g
+/// Version: 1.3
+///
+/// Package: baz
+/// Version: 0.1
+/// ").unwrap();
+///
+/// let diff = diff(&old, &new, "Package");
+/// assert_eq!(diff.added[0].get("Package"), Some("baz"));
+/// assert_eq!(diff.removed[0].get("Package"), Some("bar"));
+/// assert_eq!(diff.changed[0].to_string(), "foo 1.2 -> 1.3, Depends changed");
+/// ```
+pub fn diff<'p, 'a>(
+    old: &'p [Paragraph<'a>],
+    new: &'p [Paragraph<'a>],
+    key: &str,
+) -> ControlDiff<'p, 'a> {
+    let key_fields = key.split_whitespace().collect::<Vec<_>>();
+    let key_of = |paragraph: &'p Paragraph<'a>| {
+        key_fields
+            .iter()
+            .map(|name| paragraph.get(name).unwrap_or(""))
+            .collect::<Vec<_>>()
+    };
+
+    let mut unmatched = BTreeMap::<_, VecDeque<_>>::new();
+    for (index, paragraph) in new.iter().enumerate() {
+        unmatched
+            .entry(key_of(paragraph))
+            .or_default()
+            .push_back(index);
+    }
+
+    let mut result = ControlDiff::default();
+    let mut matched = vec![false; new.len()];
+    for paragraph in old {
+        let key = key_of(paragraph);
+        let index = match unmatched.get_mut(&key).and_then(VecDeque::pop_front) {
+            Some(index) => index,
+            None => {
+                result.removed.push(paragraph);
+                continue;
+            }
+        };
+        matched[index] = true;
+        let fields = diff_fields(paragraph, &new[index]);
+        if !fields.is_empty() {
+            result.changed.push(ParagraphChange {
+                key,
+                old: paragraph,
+                new: &new[index],
+                fields,
+            });
+        }
+    }
+    result.added = new
+        .iter()
+        .zip(matched)
+        .filter(|(_, matched)| !matched)
+        .map(|(paragraph, _)| paragraph)
+        .collect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::string::ToString;
+    use indoc::indoc;
+
+    const OLD: &str = indoc!(
+        "
+        Package: foo
+        Architecture: amd64
+        Version: 1.2
+        Depends: libc6 (>= 2.34),
+         zlib1g
+        Homepage: https://example.org
+
+        Package: foo
+        Architecture: arm64
+        Version: 1.2
+
+        Package: bar
+        Architecture: all
+        Version: 1.0
+        "
+    );
+
+    const NEW: &str = indoc!(
+        "
+        Package: baz
+        Architecture: all
+        Version: 0.1
+
+        Package: foo
+        Architecture: arm64
+        Version: 1.2
+
+        Package: foo
+        architecture: amd64
+        depends: libc6 (>= 2.34), zlib1g
+        Version: 1.3
+        Section: utils
+        "
+    );
+
+    #[test]
+    fn should_match_paragraphs_by_key() {
+        let old = parse_str(OLD).unwrap();
+        let new = parse_str(NEW).unwrap();
+        let diff = diff(&old, &new, "Package Architecture");
+        assert_eq!(diff.added, vec![&new[0]]);
+        assert_eq!(diff.removed, vec![&old[2]]);
+        assert_eq!(diff.changed.len(), 1);
+
+        let change = &diff.changed[0];
+        assert_eq!(change.key, vec!["foo", "amd64"]);
+        assert_eq!(
+            change.fields,
+            vec![
+                FieldChange {
+                    name: "Version",
+                    old: Some("1.2"),
+                    new: Some("1.3"),
+                },
+                FieldChange {
+                    name: "Homepage",
+                    old: Some("https://example.org"),
+                    new: None,
+                },
+                FieldChange {
+                    name: "Section",
+                    old: None,
+                    new: Some("utils"),
+                },
+            ]
+        );
+        assert_eq!(
+            change.to_string(),
+            "foo:amd64 1.2 -> 1.3, Homepage removed, Section added"
+        );
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn should_match_duplicate_keys_in_order() {
+        let old = parse_str(OLD).unwrap();
+        let new = parse_str(NEW).unwrap();
+        let diff = diff(&old, &new, "Package");
+        assert_eq!(diff.added, vec![&new[0]]);
+        assert_eq!(diff.removed, vec![&old[2]]);
+        let summaries = diff
+            .changed
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summaries,
+            vec![
+                "foo Architecture changed, Depends removed, Homepage removed",
+                "foo 1.2 -> 1.3, Architecture changed, depends added, Section added",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_no_differences_for_reordered_fields() {
+        let old = parse_str("A: 1\nB: 2\n\nA: 2\n").unwrap();
+        let new = parse_str("A: 2\n\nb: 2\nA: 1\n").unwrap();
+        assert!(diff(&old, &new, "A").is_empty());
+        assert!(diff(&old, &old, "").is_empty());
+
+        let new = parse_str("A: 1\nVersion: 1\n\nA: 2\n").unwrap();
+        let diff = diff(&old, &new, "A");
+        assert_eq!(diff.changed[0].to_string(), "1 B removed, Version added");
+    }
+}
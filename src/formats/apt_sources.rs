@@ -0,0 +1,722 @@
+//! APT data sources in deb822 style (`.sources` files).
+//!
+//! Every paragraph in a `.sources` file describes a set of repositories: the combination of all
+//! listed `Types`, `URIs`, `Suites` and `Components`. Options that apply to all of them, such as
+//! `Architectures` or `Signed-By`, are given as further fields. One-line style `sources.list`
+//! entries can be converted into this form with [`parse_sources_list`](fn.parse_sources_list.html).
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::formats::apt_sources::{parse_sources, parse_sources_list};
+//! let sources = parse_sources("\
+//! Types: deb deb-src
+//! URIs: https://deb.debian.org/debian
+//! Suites: bookworm bookworm-updates
+//! Components: main contrib
+//! ").unwrap();
+//! let entries = sources[0].entries();
+//! assert_eq!(entries.len(), 8);
+//! assert_eq!(entries[0].to_string(), "deb https://deb.debian.org/debian bookworm main");
+//!
+//! let converted = parse_sources_list("deb [arch=amd64] https://repo.aosc.io/debs stable main\n")
+//!     .unwrap();
+//! assert_eq!(converted[0].to_string(), "\
+//! Types: deb
+//! URIs: https://repo.aosc.io/debs
+//! Suites: stable
+//! Components: main
+//! Architectures: amd64
+//! ");
+//! ```
+
+use crate::{parse_str, write_paragraphs, OwnedField, OwnedParagraph, Paragraph};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+/// Mapping of one-line style option names to the corresponding deb822 field names.
+const OPTION_FIELDS: &[(&str, &str)] = &[
+    ("arch", "Architectures"),
+    ("lang", "Languages"),
+    ("target", "Targets"),
+    ("pdiffs", "PDiffs"),
+    ("by-hash", "By-Hash"),
+    ("allow-insecure", "Allow-Insecure"),
+    ("allow-weak", "Allow-Weak"),
+    ("allow-downgrade-to-insecure", "Allow-Downgrade-To-Insecure"),
+    ("trusted", "Trusted"),
+    ("signed-by", "Signed-By"),
+    ("check-valid-until", "Check-Valid-Until"),
+    ("valid-until-min", "Valid-Until-Min"),
+    ("valid-until-max", "Valid-Until-Max"),
+    ("check-date", "Check-Date"),
+    ("date-max-future", "Date-Max-Future"),
+    ("inrelease-path", "InRelease-Path"),
+    ("snapshot", "Snapshot"),
+];
+
+/// The fields that are interpreted by [`Source`](struct.Source.html) itself rather than kept as
+/// options.
+const SOURCE_FIELDS: &[&str] = &[
+    "Types",
+    "URIs",
+    "Suites",
+    "Components",
+    "Architectures",
+    "Signed-By",
+    "Enabled",
+];
+
+/// An error when reading APT data sources.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AptSourcesError {
+    /// The input is not a valid control file.
+    InvalidSyntax(String),
+    /// A required field is missing from the paragraph with the given index.
+    MissingField { index: usize, field: &'static str },
+    /// A source type other than `deb` or `deb-src`.
+    InvalidType(String),
+    /// An `Enabled` value other than `yes` or `no`.
+    InvalidEnabled(String),
+    /// A suite that is not an exact path (ending in `/`) in a paragraph without components, or
+    /// an exact path in a paragraph with components.
+    InvalidComponents { index: usize, suite: String },
+    /// A one-line style entry that couldn't be parsed.
+    InvalidLine(String),
+}
+
+impl fmt::Display for AptSourcesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AptSourcesError::InvalidSyntax(err) => write!(f, "invalid syntax: {}", err),
+            AptSourcesError::MissingField { index, field } => {
+                write!(f, "paragraph {} is missing required field {}", index, field)
+            }
+            AptSourcesError::InvalidType(value) => write!(f, "invalid source type '{}'", value),
+            AptSourcesError::InvalidEnabled(value) => {
+                write!(f, "invalid Enabled value '{}'", value)
+            }
+            AptSourcesError::InvalidComponents { index, suite } if suite.ends_with('/') => write!(
+                f,
+                "paragraph {} lists components for exact path {}",
+                index, suite
+            ),
+            AptSourcesError::InvalidComponents { index, suite } => write!(
+                f,
+                "paragraph {} lists no components for suite {}",
+                index, suite
+            ),
+            AptSourcesError::InvalidLine(line) => write!(f, "invalid sources.list line '{}'", line),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AptSourcesError {}
+
+/// The type of archive a source refers to.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum SourceType {
+    /// Binary packages.
+    Deb,
+    /// Source packages.
+    DebSrc,
+}
+
+impl SourceType {
+    fn from_word(word: &str) -> Result<Self, AptSourcesError> {
+        match word {
+            "deb" => Ok(SourceType::Deb),
+            "deb-src" => Ok(SourceType::DebSrc),
+            word => Err(AptSourcesError::InvalidType(String::from(word))),
+        }
+    }
+
+    /// Return the type as used in `Types` fields and one-line entries.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceType::Deb => "deb",
+            SourceType::DebSrc => "deb-src",
+        }
+    }
+}
+
+impl fmt::Display for SourceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The keys a source is signed with.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SignedBy {
+    /// Paths to keyring files or key fingerprints.
+    Keyrings(Vec<String>),
+    /// An inline ASCII-armored key block. Blank lines in the key are empty lines here.
+    Key(String),
+}
+
+/// The armor header line that starts an inline key block.
+const KEY_BLOCK_HEADER: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+impl SignedBy {
+    /// Parse a `Signed-By` value. Like APT, this treats the value as an inline key if it contains
+    /// an armor header, and as a list of keyrings or fingerprints otherwise, even if that list
+    /// spans several lines.
+    fn parse(value: &str) -> Self {
+        if value.contains(KEY_BLOCK_HEADER) {
+            let key = value
+                .trim_start_matches('\n')
+                .split('\n')
+                .map(|line| if line == "." { "" } else { line })
+                .collect::<Vec<_>>()
+                .join("\n");
+            SignedBy::Key(key)
+        } else {
+            SignedBy::Keyrings(split_words(value))
+        }
+    }
+
+    fn to_value(&self) -> String {
+        match self {
+            SignedBy::Keyrings(keyrings) => keyrings.join(" "),
+            SignedBy::Key(key) => {
+                let mut value = String::from("\n");
+                value.push_str(key.trim_end_matches('\n'));
+                value
+            }
+        }
+    }
+}
+
+/// Split a whitespace-separated field value into owned words.
+fn split_words(value: &str) -> Vec<String> {
+    value.split_whitespace().map(String::from).collect()
+}
+
+/// A single repository, as it would be written in a one-line style `sources.list`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Entry<'a> {
+    pub source_type: SourceType,
+    pub uri: &'a str,
+    pub suite: &'a str,
+    /// The component, or `None` if the suite is an exact path.
+    pub component: Option<&'a str>,
+}
+
+impl fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.source_type, self.uri, self.suite)?;
+        if let Some(component) = self.component {
+            write!(f, " {}", component)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single paragraph in a `.sources` file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Source {
+    pub types: Vec<SourceType>,
+    pub uris: Vec<String>,
+    pub suites: Vec<String>,
+    /// The components. This is empty if all suites are exact paths.
+    pub components: Vec<String>,
+    /// The architectures to download indices for. If empty, APT uses its configured default.
+    pub architectures: Vec<String>,
+    pub signed_by: Option<SignedBy>,
+    pub enabled: bool,
+    /// All other fields, such as `Languages` or `Trusted`, in their original order.
+    pub options: Vec<OwnedField>,
+}
+
+impl Source {
+    /// Interpret a single `.sources` paragraph. `index` is only used for error reporting.
+    pub fn from_paragraph(
+        paragraph: &Paragraph<'_>,
+        index: usize,
+    ) -> Result<Self, AptSourcesError> {
+        let required = |field| {
+            paragraph
+                .get(field)
+                .map(split_words)
+                .filter(|words| !words.is_empty())
+                .ok_or(AptSourcesError::MissingField { index, field })
+        };
+        let source = Source {
+            types: paragraph
+                .get("Types")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(SourceType::from_word)
+                .collect::<Result<Vec<_>, _>>()?,
+            uris: required("URIs")?,
+            suites: required("Suites")?,
+            components: split_words(paragraph.get("Components").unwrap_or_default()),
+            architectures: split_words(paragraph.get("Architectures").unwrap_or_default()),
+            signed_by: paragraph.get("Signed-By").map(SignedBy::parse),
+            enabled: match paragraph.get("Enabled").map(str::trim) {
+                None | Some("yes") => true,
+                Some("no") => false,
+                Some(value) => return Err(AptSourcesError::InvalidEnabled(String::from(value))),
+            },
+            options: paragraph
+                .fields
+                .iter()
+                .filter(|field| {
+                    !SOURCE_FIELDS
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(field.name))
+                })
+                .map(|field| OwnedField::from(field.clone()))
+                .collect(),
+        };
+        if source.types.is_empty() {
+            return Err(AptSourcesError::MissingField {
+                index,
+                field: "Types",
+            });
+        }
+        let has_components = !source.components.is_empty();
+        if let Some(suite) = source
+            .suites
+            .iter()
+            .find(|suite| suite.ends_with('/') == has_components)
+        {
+            return Err(AptSourcesError::InvalidComponents {
+                index,
+                suite: suite.clone(),
+            });
+        }
+        Ok(source)
+    }
+
+    /// Convert a single one-line style `sources.list` entry.
+    ///
+    /// Comments and blank lines produce `None`. Options in square brackets are converted into the
+    /// corresponding deb822 fields, so `arch=amd64,i386` becomes `Architectures: amd64 i386` and
+    /// `lang+=de` becomes `Languages-Add: de`.
+    pub fn from_one_line(line: &str) -> Result<Option<Self>, AptSourcesError> {
+        let invalid = || AptSourcesError::InvalidLine(String::from(line));
+        let content = line.split('#').next().unwrap_or_default().trim();
+        if content.is_empty() {
+            return Ok(None);
+        }
+
+        let (source_type, rest) = content
+            .split_once(char::is_whitespace)
+            .ok_or_else(invalid)?;
+        let mut source = Source {
+            types: vec![SourceType::from_word(source_type)?],
+            uris: Vec::new(),
+            suites: Vec::new(),
+            components: Vec::new(),
+            architectures: Vec::new(),
+            signed_by: None,
+            enabled: true,
+            options: Vec::new(),
+        };
+
+        let mut rest = rest.trim_start();
+        if let Some(options) = rest.strip_prefix('[') {
+            let (options, after) = options.split_once(']').ok_or_else(invalid)?;
+            for option in options.split_whitespace() {
+                source.add_one_line_option(option).ok_or_else(invalid)?;
+            }
+            rest = after;
+        }
+
+        let mut words = rest.split_whitespace().map(String::from);
+        source.uris.push(words.next().ok_or_else(invalid)?);
+        let suite = words.next().ok_or_else(invalid)?;
+        source.components = words.collect();
+        if suite.ends_with('/') != source.components.is_empty() {
+            return Err(invalid());
+        }
+        source.suites.push(suite);
+        Ok(Some(source))
+    }
+
+    /// Add a single `key=value` option from a one-line style entry.
+    fn add_one_line_option(&mut self, option: &str) -> Option<()> {
+        let (key, value) = option.split_once('=')?;
+        let (key, suffix) = if let Some(key) = key.strip_suffix('+') {
+            (key, "-Add")
+        } else if let Some(key) = key.strip_suffix('-') {
+            (key, "-Remove")
+        } else {
+            (key, "")
+        };
+        let values = value
+            .split(',')
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        match (key, suffix) {
+            ("arch", "") => self.architectures = values,
+            ("signed-by", "") => self.signed_by = Some(SignedBy::Keyrings(values)),
+            _ => {
+                let mut name = OPTION_FIELDS
+                    .iter()
+                    .find(|(option, _)| *option == key)
+                    .map(|(_, field)| String::from(*field))
+                    .unwrap_or_else(|| title_case(key));
+                name.push_str(suffix);
+                self.options.push(OwnedField {
+                    name,
+                    value: values.join(" "),
+                });
+            }
+        }
+        Some(())
+    }
+
+    /// Return every repository described by this source.
+    ///
+    /// The entries are ordered by type, then URI, then suite, then component.
+    pub fn entries(&self) -> Vec<Entry<'_>> {
+        let mut entries = Vec::new();
+        for source_type in &self.types {
+            for uri in &self.uris {
+                for suite in &self.suites {
+                    if suite.ends_with('/') {
+                        entries.push(Entry {
+                            source_type: *source_type,
+                            uri,
+                            suite,
+                            component: None,
+                        });
+                    }
+                    for component in self.components.iter().filter(|_| !suite.ends_with('/')) {
+                        entries.push(Entry {
+                            source_type: *source_type,
+                            uri,
+                            suite,
+                            component: Some(component),
+                        });
+                    }
+                }
+            }
+        }
+        entries
+    }
+
+    /// Return the value of an additional option field, such as `Languages`.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+            .map(|field| field.value.as_str())
+    }
+
+    /// Convert the source into a paragraph.
+    ///
+    /// `Enabled` is only written if the source is disabled, and `Signed-By` is written after all
+    /// other fields so an inline key doesn't separate them.
+    pub fn to_paragraph(&self) -> OwnedParagraph {
+        let mut paragraph = OwnedParagraph::default();
+        if !self.enabled {
+            paragraph.set("Enabled", "no");
+        }
+        let types = self
+            .types
+            .iter()
+            .map(SourceType::as_str)
+            .collect::<Vec<_>>();
+        paragraph.set("Types", types.join(" "));
+        paragraph.set("URIs", self.uris.join(" "));
+        paragraph.set("Suites", self.suites.join(" "));
+        if !self.components.is_empty() {
+            paragraph.set("Components", self.components.join(" "));
+        }
+        if !self.architectures.is_empty() {
+            paragraph.set("Architectures", self.architectures.join(" "));
+        }
+        paragraph.fields.extend(self.options.iter().cloned());
+        if let Some(signed_by) = &self.signed_by {
+            paragraph.set("Signed-By", signed_by.to_value());
+        }
+        paragraph
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_paragraph())
+    }
+}
+
+/// Turn a one-line option name such as `foo-bar` into a field name such as `Foo-Bar`.
+fn title_case(key: &str) -> String {
+    key.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Parse the contents of a deb822 style `.sources` file.
+pub fn parse_sources(input: &str) -> Result<Vec<Source>, AptSourcesError> {
+    let paragraphs =
+        parse_str(input).map_err(|err| AptSourcesError::InvalidSyntax(err.to_string()))?;
+    paragraphs
+        .iter()
+        .enumerate()
+        .map(|(index, paragraph)| Source::from_paragraph(paragraph, index))
+        .collect()
+}
+
+/// Convert the contents of a one-line style `sources.list` file.
+///
+/// Every entry becomes a separate source; comments and blank lines are dropped.
+pub fn parse_sources_list(input: &str) -> Result<Vec<Source>, AptSourcesError> {
+    input
+        .lines()
+        .filter_map(|line| Source::from_one_line(line).transpose())
+        .collect()
+}
+
+/// Write the given sources as a `.sources` file.
+pub fn write_sources(f: &mut impl fmt::Write, sources: &[Source]) -> fmt::Result {
+    let paragraphs = sources.iter().map(Source::to_paragraph).collect::<Vec<_>>();
+    write_paragraphs(f, &paragraphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const SOURCES: &str = indoc!(
+        "
+        Types: deb
+        URIs: https://repo.aosc.io/debs
+        Suites: stable
+        Components: main
+        Architectures: amd64 arm64
+        Languages: en zh_CN
+        Signed-By:
+         -----BEGIN PGP PUBLIC KEY BLOCK-----
+         .
+         mQINBF4xQ3EBEAC
+         -----END PGP PUBLIC KEY BLOCK-----
+
+        Enabled: no
+        Types: deb-src
+        URIs: file:/srv/local
+        Suites: ./
+        Signed-By: /usr/share/keyrings/local.gpg
+        "
+    );
+
+    #[test]
+    fn should_parse_sources() {
+        let sources = parse_sources(SOURCES).unwrap();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].types, vec![SourceType::Deb]);
+        assert_eq!(sources[0].architectures, vec!["amd64", "arm64"]);
+        assert!(sources[0].enabled);
+        assert_eq!(sources[0].option("languages"), Some("en zh_CN"));
+        assert_eq!(
+            sources[0].signed_by,
+            Some(SignedBy::Key(String::from(
+                "-----BEGIN PGP PUBLIC KEY BLOCK-----\n\nmQINBF4xQ3EBEAC\n-----END PGP PUBLIC KEY BLOCK-----"
+            )))
+        );
+        assert!(!sources[1].enabled);
+        assert_eq!(
+            sources[1].signed_by,
+            Some(SignedBy::Keyrings(vec![String::from(
+                "/usr/share/keyrings/local.gpg"
+            )]))
+        );
+    }
+
+    #[test]
+    fn should_parse_folded_keyring_list() {
+        let sources = parse_sources(indoc!(
+            "
+            Types: deb
+            URIs: http://a
+            Suites: s
+            Components: c
+            Signed-By:
+             /usr/share/keyrings/a.gpg
+             0123456789ABCDEF0123456789ABCDEF01234567
+            "
+        ))
+        .unwrap();
+        assert_eq!(
+            sources[0].signed_by,
+            Some(SignedBy::Keyrings(vec![
+                String::from("/usr/share/keyrings/a.gpg"),
+                String::from("0123456789ABCDEF0123456789ABCDEF01234567"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn should_expand_entries() {
+        let sources = parse_sources(indoc!(
+            "
+            Types: deb deb-src
+            URIs: http://a http://b
+            Suites: s1 s2/
+            Components: c1 c2
+            "
+        ));
+        assert_eq!(
+            sources,
+            Err(AptSourcesError::InvalidComponents {
+                index: 0,
+                suite: String::from("s2/")
+            })
+        );
+
+        let sources =
+            parse_sources("Types: deb deb-src\nURIs: http://a\nSuites: s1 s2\nComponents: c1 c2\n")
+                .unwrap();
+        let entries = sources[0]
+            .entries()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                "deb http://a s1 c1",
+                "deb http://a s1 c2",
+                "deb http://a s2 c1",
+                "deb http://a s2 c2",
+                "deb-src http://a s1 c1",
+                "deb-src http://a s1 c2",
+                "deb-src http://a s2 c1",
+                "deb-src http://a s2 c2",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_expand_exact_path_without_component() {
+        let sources = parse_sources(SOURCES).unwrap();
+        assert_eq!(
+            sources[1].entries(),
+            vec![Entry {
+                source_type: SourceType::DebSrc,
+                uri: "file:/srv/local",
+                suite: "./",
+                component: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_sources() {
+        assert_eq!(
+            parse_sources("Types: deb\nSuites: stable\nComponents: main\n"),
+            Err(AptSourcesError::MissingField {
+                index: 0,
+                field: "URIs"
+            })
+        );
+        assert_eq!(
+            parse_sources("Types: rpm\nURIs: http://a\nSuites: s\nComponents: c\n"),
+            Err(AptSourcesError::InvalidType(String::from("rpm")))
+        );
+        assert_eq!(
+            parse_sources("Types: deb\nURIs: http://a\nSuites: s\nComponents: c\nEnabled: maybe\n"),
+            Err(AptSourcesError::InvalidEnabled(String::from("maybe")))
+        );
+    }
+
+    #[test]
+    fn should_round_trip_sources() {
+        let sources = parse_sources(SOURCES).unwrap();
+        let mut output = String::new();
+        write_sources(&mut output, &sources).unwrap();
+        assert_eq!(
+            output,
+            indoc!(
+                "
+                Types: deb
+                URIs: https://repo.aosc.io/debs
+                Suites: stable
+                Components: main
+                Architectures: amd64 arm64
+                Languages: en zh_CN
+                Signed-By:
+                 -----BEGIN PGP PUBLIC KEY BLOCK-----
+                 .
+                 mQINBF4xQ3EBEAC
+                 -----END PGP PUBLIC KEY BLOCK-----
+
+                Enabled: no
+                Types: deb-src
+                URIs: file:/srv/local
+                Suites: ./
+                Signed-By: /usr/share/keyrings/local.gpg
+                "
+            )
+        );
+        assert_eq!(parse_sources(&output).unwrap(), sources);
+    }
+
+    #[test]
+    fn should_convert_one_line_entries() {
+        let sources = parse_sources_list(indoc!(
+            "
+            # The main repository
+            deb [arch=amd64,i386 signed-by=/etc/apt/k.gpg lang+=de pdiffs=no] http://a stable main contrib # trailing
+
+            deb-src http://b ./
+            "
+        ))
+        .unwrap();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(
+            sources[0].to_string(),
+            indoc!(
+                "
+                Types: deb
+                URIs: http://a
+                Suites: stable
+                Components: main contrib
+                Architectures: amd64 i386
+                Languages-Add: de
+                PDiffs: no
+                Signed-By: /etc/apt/k.gpg
+                "
+            )
+        );
+        assert_eq!(sources[1].entries()[0].to_string(), "deb-src http://b ./");
+    }
+
+    #[test]
+    fn should_reject_invalid_one_line_entries() {
+        for line in [
+            "deb",
+            "deb http://a",
+            "deb [arch=amd64 http://a s c",
+            "deb http://a s",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                Source::from_one_line(line),
+                Err(AptSourcesError::InvalidLine(String::from(*line)))
+            );
+        }
+        assert_eq!(
+            Source::from_one_line("rpm http://a s c"),
+            Err(AptSourcesError::InvalidType(String::from("rpm")))
+        );
+    }
+}
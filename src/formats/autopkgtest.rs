@@ -0,0 +1,454 @@
+//! autopkgtest control files (`debian/tests/control`).
+//!
+//! Every paragraph in `debian/tests/control` declares one or more tests, either as a list of test
+//! scripts in `Tests` or as an inline shell command in `Test-Command`, along with their
+//! dependencies and restrictions. See the [autopkgtest specification] for details.
+//!
+//! [autopkgtest specification]: https://salsa.debian.org/ci-team/autopkgtest/-/blob/master/doc/README.package-tests.rst
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{formats::{autopkgtest::{Restriction, TestControl}, debian_control::ControlFile}, parse_str};
+//! let control = ControlFile::parse("\
+//! Source: foo
+//! Build-Depends: debhelper-compat (= 13)
+//!
+//! Package: foo
+//! Architecture: any
+//! ").unwrap();
+//! let paragraphs = parse_str("\
+//! Tests: smoke
+//! Depends: @, python3
+//! Restrictions: needs-root, allow-stderr
+//! ").unwrap();
+//!
+//! let tests = TestControl::from_paragraphs(&paragraphs).unwrap();
+//! let test = &tests.tests[0];
+//! assert_eq!(test.tests, vec!["smoke"]);
+//! assert_eq!(test.restrictions, vec![Restriction::NeedsRoot, Restriction::AllowStderr]);
+//! assert_eq!(test.depends(&control), vec!["foo", "python3"]);
+//! ```
+
+use crate::{formats::debian_control::ControlFile, Paragraph};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+/// The default value of `Depends` when the field is missing.
+const DEFAULT_DEPENDS: &str = "@";
+
+/// The default value of `Tests-Directory` when the field is missing.
+const DEFAULT_TESTS_DIRECTORY: &str = "debian/tests";
+
+/// An error when interpreting a `debian/tests/control` file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AutopkgtestError {
+    /// A paragraph has neither `Tests` nor `Test-Command`.
+    MissingTests(usize),
+    /// A paragraph has both `Tests` and `Test-Command`.
+    TestsAndTestCommand(usize),
+}
+
+impl fmt::Display for AutopkgtestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutopkgtestError::MissingTests(index) => {
+                write!(f, "paragraph {} has neither Tests nor Test-Command", index)
+            }
+            AutopkgtestError::TestsAndTestCommand(index) => {
+                write!(f, "paragraph {} has both Tests and Test-Command", index)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AutopkgtestError {}
+
+/// Generate the `Restriction` enum from the list of known restriction names.
+macro_rules! restrictions {
+    ($($(#[$meta: meta])* $variant: ident => $name: expr,)+) => {
+        /// A test restriction from the `Restrictions` field.
+        #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+        pub enum Restriction<'a> {
+            $($(#[$meta])* $variant,)+
+            /// A restriction not known to this crate.
+            ///
+            /// autopkgtest skips tests with restrictions it doesn't know, so these are reported by
+            /// [`Test::unknown_restrictions`](struct.Test.html#method.unknown_restrictions).
+            Unknown(&'a str),
+        }
+
+        impl<'a> Restriction<'a> {
+            fn parse(name: &'a str) -> Self {
+                match name {
+                    $($name => Restriction::$variant,)+
+                    name => Restriction::Unknown(name),
+                }
+            }
+
+            /// Return the restriction name as used in the control file.
+            pub fn as_str(&self) -> &'a str {
+                match self {
+                    $(Restriction::$variant => $name,)+
+                    Restriction::Unknown(name) => name,
+                }
+            }
+        }
+    };
+}
+
+restrictions! {
+    /// The test may write to stderr without failing.
+    AllowStderr => "allow-stderr",
+    /// The test may leave the testbed unusable for further tests.
+    BreaksTestbed => "breaks-testbed",
+    /// The source package must be built before running the test.
+    BuildNeeded => "build-needed",
+    /// The test is expected to fail intermittently.
+    Flaky => "flaky",
+    /// The test has no real dependencies; the field only lists packages that trigger it.
+    HintTestsuiteTriggers => "hint-testsuite-triggers",
+    /// The test needs to run in its own container.
+    IsolationContainer => "isolation-container",
+    /// The test needs to run in its own machine.
+    IsolationMachine => "isolation-machine",
+    /// The test needs unrestricted internet access.
+    NeedsInternet => "needs-internet",
+    /// The test may reboot the testbed.
+    NeedsReboot => "needs-reboot",
+    /// Recommends of the dependencies should be installed (deprecated).
+    NeedsRecommends => "needs-recommends",
+    /// The test needs to run as root.
+    NeedsRoot => "needs-root",
+    /// The test needs to run as a normal user with sudo access.
+    NeedsSudo => "needs-sudo",
+    /// The test needs a writable copy of the source tree.
+    RwBuildTree => "rw-build-tree",
+    /// The test is skipped when testing a foreign architecture.
+    SkipForeignArchitecture => "skip-foreign-architecture",
+    /// The test is skipped if its dependencies can't be installed.
+    SkipNotInstallable => "skip-not-installable",
+    /// The test may exit with status 77 to indicate that it was skipped.
+    Skippable => "skippable",
+    /// The test only checks superficial aspects of the package.
+    Superficial => "superficial",
+}
+
+impl fmt::Display for Restriction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Split a field value that is separated by commas, whitespace, or both.
+fn split_list(value: &str) -> Vec<&str> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Split a relationship field into its comma-separated items.
+fn split_relations(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// A single paragraph in `debian/tests/control`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Test<'a> {
+    /// The names of the test scripts, relative to `tests_directory`.
+    pub tests: Vec<&'a str>,
+    /// The inline test command, if the test is declared with `Test-Command`.
+    pub test_command: Option<&'a str>,
+    pub tests_directory: &'a str,
+    /// The raw `Depends` field, defaulting to `@`.
+    pub raw_depends: &'a str,
+    pub restrictions: Vec<Restriction<'a>>,
+    pub features: Vec<&'a str>,
+    pub architecture: Vec<&'a str>,
+    pub classes: Vec<&'a str>,
+}
+
+impl<'a> Test<'a> {
+    /// Interpret a single test paragraph. `index` is only used for error reporting.
+    pub fn from_paragraph(
+        paragraph: &'a Paragraph<'_>,
+        index: usize,
+    ) -> Result<Self, AutopkgtestError> {
+        let list = |name| split_list(paragraph.get(name).unwrap_or_default());
+        let tests = paragraph.get("Tests");
+        let test_command = paragraph.get("Test-Command");
+        match (tests, test_command) {
+            (None, None) => return Err(AutopkgtestError::MissingTests(index)),
+            (Some(_), Some(_)) => return Err(AutopkgtestError::TestsAndTestCommand(index)),
+            _ => {}
+        }
+
+        Ok(Test {
+            tests: split_list(tests.unwrap_or_default()),
+            test_command,
+            tests_directory: paragraph
+                .get("Tests-Directory")
+                .unwrap_or(DEFAULT_TESTS_DIRECTORY),
+            raw_depends: paragraph.get("Depends").unwrap_or(DEFAULT_DEPENDS),
+            restrictions: list("Restrictions")
+                .into_iter()
+                .map(Restriction::parse)
+                .collect(),
+            features: list("Features"),
+            architecture: list("Architecture"),
+            classes: list("Classes"),
+        })
+    }
+
+    /// Return true if the test has the given restriction.
+    pub fn has_restriction(&self, restriction: Restriction<'_>) -> bool {
+        self.restrictions
+            .iter()
+            .any(|r| r.as_str() == restriction.as_str())
+    }
+
+    /// Return the restrictions that are not known to this crate.
+    pub fn unknown_restrictions(&self) -> Vec<&'a str> {
+        self.restrictions
+            .iter()
+            .filter_map(|restriction| match restriction {
+                Restriction::Unknown(name) => Some(*name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Return the test dependencies with all substitutions expanded.
+    ///
+    /// The substitutions are taken from the source package's `debian/control`:
+    ///
+    /// * `@` expands to every binary package built by the source package,
+    /// * `@builddeps@` expands to the source package's build dependencies and `build-essential`,
+    /// * `@recommends@` expands to the `Recommends` of every binary package.
+    ///
+    /// Every returned item is a single (possibly alternative) relationship.
+    pub fn depends(&self, control: &ControlFile) -> Vec<String> {
+        let mut depends = Vec::new();
+        for item in split_relations(self.raw_depends) {
+            match item {
+                "@" => depends.extend(
+                    control
+                        .binaries()
+                        .filter_map(|binary| binary.package())
+                        .map(String::from),
+                ),
+                "@builddeps@" => {
+                    if let Some(source) = control.source() {
+                        let fields = [
+                            source.build_depends(),
+                            source.build_depends_indep(),
+                            source.build_depends_arch(),
+                        ];
+                        for field in fields.iter().flatten() {
+                            depends.extend(split_relations(field).map(String::from));
+                        }
+                    }
+                    depends.push("build-essential".to_string());
+                }
+                "@recommends@" => {
+                    for binary in control.binaries() {
+                        let recommends = binary.paragraph.get("Recommends").unwrap_or_default();
+                        depends.extend(split_relations(recommends).map(String::from));
+                    }
+                }
+                item => depends.push(String::from(item)),
+            }
+        }
+        depends
+    }
+}
+
+/// The interpreted contents of a `debian/tests/control` file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TestControl<'a> {
+    pub tests: Vec<Test<'a>>,
+}
+
+impl<'a> TestControl<'a> {
+    /// Interpret all paragraphs of a `debian/tests/control` file.
+    pub fn from_paragraphs(paragraphs: &'a [Paragraph<'_>]) -> Result<Self, AutopkgtestError> {
+        let tests = paragraphs
+            .iter()
+            .enumerate()
+            .map(|(index, paragraph)| Test::from_paragraph(paragraph, index))
+            .collect::<Result<_, _>>()?;
+        Ok(TestControl { tests })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::vec;
+    use indoc::indoc;
+
+    const CONTROL: &str = indoc!(
+        "
+        Source: foo
+        Maintainer: Jane Doe <jane@example.org>
+        Build-Depends: debhelper-compat (= 13), python3 <!nocheck>
+        Build-Depends-Indep: python3-sphinx
+
+        Package: foo
+        Architecture: any
+        Recommends: foo-data, curl | wget
+
+        Package: foo-doc
+        Architecture: all
+        Recommends: www-browser
+        "
+    );
+
+    const TESTS: &str = indoc!(
+        "
+        Tests: unit integration,
+         smoke
+        Depends: @, @builddeps@
+        Restrictions: allow-stderr, needs-root rw-build-tree
+        Features: test-name=unit
+        Classes: desktop
+
+        Test-Command: foo --version
+        Depends: @recommends@, shellcheck (>= 0.9)
+        Restrictions: superficial, needs-magic
+        Architecture: amd64 arm64
+        "
+    );
+
+    #[test]
+    fn should_parse_tests_list() {
+        let paragraphs = parse_str(TESTS).unwrap();
+        let tests = TestControl::from_paragraphs(&paragraphs).unwrap();
+        let test = &tests.tests[0];
+        assert_eq!(test.tests, vec!["unit", "integration", "smoke"]);
+        assert_eq!(test.test_command, None);
+        assert_eq!(test.tests_directory, "debian/tests");
+        assert_eq!(test.features, vec!["test-name=unit"]);
+        assert_eq!(test.classes, vec!["desktop"]);
+    }
+
+    #[test]
+    fn should_parse_test_command() {
+        let paragraphs = parse_str(TESTS).unwrap();
+        let tests = TestControl::from_paragraphs(&paragraphs).unwrap();
+        let test = &tests.tests[1];
+        assert!(test.tests.is_empty());
+        assert_eq!(test.test_command, Some("foo --version"));
+        assert_eq!(test.architecture, vec!["amd64", "arm64"]);
+    }
+
+    #[test]
+    fn should_parse_all_known_restrictions() {
+        let names = [
+            "allow-stderr",
+            "breaks-testbed",
+            "build-needed",
+            "flaky",
+            "hint-testsuite-triggers",
+            "isolation-container",
+            "isolation-machine",
+            "needs-internet",
+            "needs-reboot",
+            "needs-recommends",
+            "needs-root",
+            "needs-sudo",
+            "rw-build-tree",
+            "skip-foreign-architecture",
+            "skip-not-installable",
+            "skippable",
+            "superficial",
+        ];
+        for name in names.iter() {
+            let restriction = Restriction::parse(name);
+            assert_ne!(restriction, Restriction::Unknown(name));
+            assert_eq!(restriction.as_str(), *name);
+        }
+    }
+
+    #[test]
+    fn should_report_unknown_restrictions() {
+        let paragraphs = parse_str(TESTS).unwrap();
+        let tests = TestControl::from_paragraphs(&paragraphs).unwrap();
+        assert_eq!(
+            tests.tests[0].restrictions,
+            vec![
+                Restriction::AllowStderr,
+                Restriction::NeedsRoot,
+                Restriction::RwBuildTree
+            ]
+        );
+        assert!(tests.tests[0].unknown_restrictions().is_empty());
+        assert_eq!(tests.tests[1].unknown_restrictions(), vec!["needs-magic"]);
+        assert!(tests.tests[1].has_restriction(Restriction::Superficial));
+        assert!(!tests.tests[1].has_restriction(Restriction::NeedsRoot));
+    }
+
+    #[test]
+    fn should_expand_binaries_and_build_dependencies() {
+        let paragraphs = parse_str(TESTS).unwrap();
+        let tests = TestControl::from_paragraphs(&paragraphs).unwrap();
+        let control = ControlFile::parse(CONTROL).unwrap();
+        assert_eq!(
+            tests.tests[0].depends(&control),
+            vec![
+                "foo",
+                "foo-doc",
+                "debhelper-compat (= 13)",
+                "python3 <!nocheck>",
+                "python3-sphinx",
+                "build-essential",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_expand_recommends() {
+        let paragraphs = parse_str(TESTS).unwrap();
+        let tests = TestControl::from_paragraphs(&paragraphs).unwrap();
+        let control = ControlFile::parse(CONTROL).unwrap();
+        assert_eq!(
+            tests.tests[1].depends(&control),
+            vec![
+                "foo-data",
+                "curl | wget",
+                "www-browser",
+                "shellcheck (>= 0.9)"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_default_to_depending_on_all_binaries() {
+        let paragraphs = parse_str("Tests: t\n").unwrap();
+        let tests = TestControl::from_paragraphs(&paragraphs).unwrap();
+        let control = ControlFile::parse(CONTROL).unwrap();
+        assert_eq!(tests.tests[0].depends(&control), vec!["foo", "foo-doc"]);
+    }
+
+    #[test]
+    fn should_require_exactly_one_of_tests_and_test_command() {
+        let paragraphs = parse_str("Tests: t\n\nDepends: foo\n").unwrap();
+        assert_eq!(
+            TestControl::from_paragraphs(&paragraphs),
+            Err(AutopkgtestError::MissingTests(1))
+        );
+        let paragraphs = parse_str("Tests: t\nTest-Command: true\n").unwrap();
+        assert_eq!(
+            TestControl::from_paragraphs(&paragraphs),
+            Err(AutopkgtestError::TestsAndTestCommand(0))
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Build information files (`.buildinfo`).
+//!
+//! `.buildinfo` files record the environment a package was built in, most importantly the exact
+//! versions of all packages installed at build time, so the build can be reproduced.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{formats::buildinfo::Buildinfo, parse_str};
+//! let old = parse_str("\
+//! Source: foo
+//! Version: 1.0-1
+//! Installed-Build-Depends:
+//!  gcc-13 (= 13.2.0-1),
+//!  libc6-dev (= 2.37-7)
+//! ").unwrap();
+//! let new = parse_str("\
+//! Source: foo
+//! Version: 1.0-1
+//! Installed-Build-Depends:
+//!  gcc-13 (= 13.2.0-2),
+//!  libc6-dev (= 2.37-7)
+//! ").unwrap();
+//!
+//! let old = Buildinfo::from_paragraph(&old[0]).unwrap();
+//! let new = Buildinfo::from_paragraph(&new[0]).unwrap();
+//! let diff = old.diff(&new);
+//! assert_eq!(diff.changed.len(), 1);
+//! assert_eq!(diff.changed[0].to_string(), "gcc-13: 13.2.0-1 -> 13.2.0-2");
+//! ```
+
+use crate::{
+    formats::checksums::{parse_checksums, Checksum, ChecksumError},
+    Paragraph,
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// An error when interpreting a `.buildinfo` paragraph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BuildinfoError {
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// An entry in `Installed-Build-Depends` isn't of the form `name (= version)`.
+    InvalidInstalledPackage(String),
+    /// A line in `Environment` isn't of the form `KEY="value"`.
+    InvalidEnvironment(String),
+    /// A line in one of the `Checksums-*` fields is invalid.
+    InvalidChecksum(ChecksumError),
+}
+
+impl fmt::Display for BuildinfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildinfoError::MissingField(field) => write!(f, "missing required field {}", field),
+            BuildinfoError::InvalidInstalledPackage(value) => {
+                write!(f, "invalid installed build dependency '{}'", value)
+            }
+            BuildinfoError::InvalidEnvironment(line) => {
+                write!(f, "invalid environment line '{}'", line)
+            }
+            BuildinfoError::InvalidChecksum(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildinfoError {}
+
+impl From<ChecksumError> for BuildinfoError {
+    fn from(err: ChecksumError) -> Self {
+        BuildinfoError::InvalidChecksum(err)
+    }
+}
+
+/// A package that was installed at build time, from `Installed-Build-Depends`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct InstalledPackage<'a> {
+    pub name: &'a str,
+    /// The architecture qualifier, for packages of a foreign architecture.
+    pub architecture: Option<&'a str>,
+    pub version: &'a str,
+}
+
+impl<'a> InstalledPackage<'a> {
+    fn parse(entry: &'a str) -> Result<Self, BuildinfoError> {
+        let invalid = || BuildinfoError::InvalidInstalledPackage(String::from(entry));
+        let (name, version) = entry.split_once('(').ok_or_else(invalid)?;
+        let version = version
+            .trim()
+            .strip_suffix(')')
+            .and_then(|version| version.trim_start().strip_prefix('='))
+            .map(str::trim)
+            .filter(|version| !version.is_empty())
+            .ok_or_else(invalid)?;
+        let (name, architecture) = match name.trim().split_once(':') {
+            Some((name, arch)) => (name, Some(arch)),
+            None => (name.trim(), None),
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+        Ok(InstalledPackage {
+            name,
+            architecture,
+            version,
+        })
+    }
+
+    fn same_package(&self, other: &InstalledPackage<'_>) -> bool {
+        self.name == other.name && self.architecture == other.architecture
+    }
+}
+
+impl fmt::Display for InstalledPackage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        if let Some(arch) = self.architecture {
+            write!(f, ":{}", arch)?;
+        }
+        write!(f, " (= {})", self.version)
+    }
+}
+
+/// Parse a single `KEY="value"` line from the `Environment` field.
+///
+/// dpkg escapes double quotes in values with a backslash, but leaves backslashes alone, so only
+/// `\"` is unescaped and every other backslash is kept as is.
+fn parse_environment_line(line: &str) -> Result<(&str, String), BuildinfoError> {
+    let invalid = || BuildinfoError::InvalidEnvironment(String::from(line));
+    let (key, quoted) = line.split_once('=').ok_or_else(invalid)?;
+    let quoted = quoted
+        .strip_prefix('"')
+        .and_then(|quoted| quoted.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let mut value = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'"') => value.push(chars.next().unwrap()),
+            '"' => return Err(invalid()),
+            c => value.push(c),
+        }
+    }
+    if key.is_empty() {
+        return Err(invalid());
+    }
+    Ok((key, value))
+}
+
+/// The interpreted contents of a `.buildinfo` file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Buildinfo<'a> {
+    pub format: Option<&'a str>,
+    pub source: &'a str,
+    pub binary: Vec<&'a str>,
+    pub architecture: Vec<&'a str>,
+    pub version: &'a str,
+    pub build_origin: Option<&'a str>,
+    pub build_architecture: Option<&'a str>,
+    pub build_date: Option<&'a str>,
+    pub build_path: Option<&'a str>,
+    pub checksums_md5: Vec<Checksum<'a>>,
+    pub checksums_sha1: Vec<Checksum<'a>>,
+    pub checksums_sha256: Vec<Checksum<'a>>,
+    pub installed_build_depends: Vec<InstalledPackage<'a>>,
+    /// The environment variables, in file order, with quoting removed.
+    pub environment: Vec<(&'a str, String)>,
+}
+
+impl<'a> Buildinfo<'a> {
+    /// Interpret a `.buildinfo` paragraph.
+    ///
+    /// Signed files need to be unwrapped with
+    /// [`strip_signature`](../fn.strip_signature.html) before parsing.
+    pub fn from_paragraph(paragraph: &'a Paragraph<'_>) -> Result<Self, BuildinfoError> {
+        let required = |name| {
+            paragraph
+                .get(name)
+                .ok_or(BuildinfoError::MissingField(name))
+        };
+        let words = |name| {
+            paragraph
+                .get(name)
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+        };
+        let checksums = |name| {
+            paragraph
+                .get(name)
+                .map_or_else(|| Ok(Vec::new()), parse_checksums)
+        };
+
+        Ok(Buildinfo {
+            format: paragraph.get("Format"),
+            source: required("Source")?,
+            binary: words("Binary"),
+            architecture: words("Architecture"),
+            version: required("Version")?,
+            build_origin: paragraph.get("Build-Origin"),
+            build_architecture: paragraph.get("Build-Architecture"),
+            build_date: paragraph.get("Build-Date"),
+            build_path: paragraph.get("Build-Path"),
+            checksums_md5: checksums("Checksums-Md5")?,
+            checksums_sha1: checksums("Checksums-Sha1")?,
+            checksums_sha256: checksums("Checksums-Sha256")?,
+            installed_build_depends: paragraph
+                .get("Installed-Build-Depends")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(InstalledPackage::parse)
+                .collect::<Result<_, _>>()?,
+            environment: paragraph
+                .get("Environment")
+                .unwrap_or_default()
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(parse_environment_line)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Return the value of an environment variable.
+    pub fn env(&self, key: &str) -> Option<&str> {
+        self.environment
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Compare the installed build dependencies of this build with another one.
+    ///
+    /// Packages are matched by name and architecture qualifier. `self` is treated as the old build
+    /// and `other` as the new one.
+    pub fn diff<'b>(&'b self, other: &'b Buildinfo<'_>) -> BuildDependsDiff<'b> {
+        let mut diff = BuildDependsDiff::default();
+        for old in &self.installed_build_depends {
+            match other
+                .installed_build_depends
+                .iter()
+                .find(|new| new.same_package(old))
+            {
+                None => diff.removed.push(old.clone()),
+                Some(new) if new.version != old.version => diff.changed.push(VersionChange {
+                    name: old.name,
+                    architecture: old.architecture,
+                    old_version: old.version,
+                    new_version: new.version,
+                }),
+                Some(_) => {}
+            }
+        }
+        for new in &other.installed_build_depends {
+            if !self
+                .installed_build_depends
+                .iter()
+                .any(|old| old.same_package(new))
+            {
+                diff.added.push(new.clone());
+            }
+        }
+        diff
+    }
+}
+
+/// A build dependency that was installed in both builds, but in different versions.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct VersionChange<'a> {
+    pub name: &'a str,
+    pub architecture: Option<&'a str>,
+    pub old_version: &'a str,
+    pub new_version: &'a str,
+}
+
+impl fmt::Display for VersionChange<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        if let Some(arch) = self.architecture {
+            write!(f, ":{}", arch)?;
+        }
+        write!(f, ": {} -> {}", self.old_version, self.new_version)
+    }
+}
+
+/// The differences between the installed build dependencies of two builds.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BuildDependsDiff<'a> {
+    /// Packages only installed in the new build.
+    pub added: Vec<InstalledPackage<'a>>,
+    /// Packages only installed in the old build.
+    pub removed: Vec<InstalledPackage<'a>>,
+    /// Packages installed in both builds with different versions.
+    pub changed: Vec<VersionChange<'a>>,
+}
+
+impl BuildDependsDiff<'_> {
+    /// Return true if both builds used exactly the same build dependencies.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::{string::ToString, vec};
+    use indoc::indoc;
+
+    const BUILDINFO: &str = indoc!(
+        r#"
+        Format: 1.0
+        Source: hello
+        Binary: hello
+        Architecture: amd64
+        Version: 2.10-3
+        Checksums-Md5:
+         0f9e8d7c6b5a49382716a5b4c3d2e1f0 53180 hello_2.10-3_amd64.deb
+        Checksums-Sha256:
+         f1e2d3c4b5a6978877665544332211ffeeddccbbaa9988776655443322110000 53180 hello_2.10-3_amd64.deb
+        Build-Origin: Debian
+        Build-Architecture: amd64
+        Build-Date: Sat, 02 Sep 2023 12:00:00 +0000
+        Build-Path: /build/reproducible-path/hello-2.10
+        Installed-Build-Depends:
+         autoconf (= 2.71-3),
+         base-files (= 12.4),
+         libc6:amd64 (= 2.36-9),
+         zlib1g (= 1:1.2.13.dfsg-1)
+        Environment:
+         DEB_BUILD_OPTIONS="parallel=4 nocheck"
+         LANG="C.UTF-8"
+         QUOTED="say \"hi\" \o/"
+        "#
+    );
+
+    #[test]
+    fn should_parse_buildinfo() {
+        let paragraphs = parse_str(BUILDINFO).unwrap();
+        let buildinfo = Buildinfo::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(buildinfo.source, "hello");
+        assert_eq!(buildinfo.version, "2.10-3");
+        assert_eq!(buildinfo.build_architecture, Some("amd64"));
+        assert_eq!(
+            buildinfo.build_path,
+            Some("/build/reproducible-path/hello-2.10")
+        );
+        assert_eq!(buildinfo.checksums_md5.len(), 1);
+        assert_eq!(buildinfo.checksums_sha256[0].size, 53180);
+    }
+
+    #[test]
+    fn should_parse_installed_build_depends() {
+        let paragraphs = parse_str(BUILDINFO).unwrap();
+        let buildinfo = Buildinfo::from_paragraph(&paragraphs[0]).unwrap();
+        let deps = &buildinfo.installed_build_depends;
+        assert_eq!(deps.len(), 4);
+        assert_eq!(
+            deps[2],
+            InstalledPackage {
+                name: "libc6",
+                architecture: Some("amd64"),
+                version: "2.36-9",
+            }
+        );
+        assert_eq!(deps[3].version, "1:1.2.13.dfsg-1");
+        assert_eq!(deps[2].to_string(), "libc6:amd64 (= 2.36-9)");
+    }
+
+    #[test]
+    fn should_reject_unversioned_installed_package() {
+        assert_eq!(
+            InstalledPackage::parse("autoconf"),
+            Err(BuildinfoError::InvalidInstalledPackage(String::from(
+                "autoconf"
+            )))
+        );
+        assert!(InstalledPackage::parse("autoconf (>= 1)").is_err());
+    }
+
+    #[test]
+    fn should_parse_environment() {
+        let paragraphs = parse_str(BUILDINFO).unwrap();
+        let buildinfo = Buildinfo::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(buildinfo.environment.len(), 3);
+        assert_eq!(buildinfo.env("LANG"), Some("C.UTF-8"));
+        assert_eq!(
+            buildinfo.env("DEB_BUILD_OPTIONS"),
+            Some("parallel=4 nocheck")
+        );
+        assert_eq!(buildinfo.env("QUOTED"), Some(r#"say "hi" \o/"#));
+        assert_eq!(buildinfo.env("HOME"), None);
+    }
+
+    #[test]
+    fn should_reject_unquoted_environment_value() {
+        assert_eq!(
+            parse_environment_line("LANG=C"),
+            Err(BuildinfoError::InvalidEnvironment(String::from("LANG=C")))
+        );
+        assert!(parse_environment_line(r#"A="unterminated"#).is_err());
+        assert!(parse_environment_line(r#"A="un"quoted""#).is_err());
+    }
+
+    #[test]
+    fn should_keep_backslashes_in_environment_value() {
+        assert_eq!(
+            parse_environment_line(r#"PATH="C:\tools\path""#),
+            Ok(("PATH", String::from(r"C:\tools\path")))
+        );
+        assert_eq!(
+            parse_environment_line(r#"X="foo\""#),
+            Ok(("X", String::from(r"foo\")))
+        );
+    }
+
+    #[test]
+    fn should_diff_build_dependencies() {
+        let new = BUILDINFO
+            .replace("autoconf (= 2.71-3),\n", "")
+            .replace("libc6:amd64 (= 2.36-9)", "libc6:amd64 (= 2.37-1)")
+            .replace(
+                "zlib1g (= 1:1.2.13.dfsg-1)",
+                "zlib1g (= 1:1.2.13.dfsg-1),\n m4 (= 1.4.19-4)",
+            );
+        let old_paragraphs = parse_str(BUILDINFO).unwrap();
+        let new_paragraphs = parse_str(&new).unwrap();
+        let old = Buildinfo::from_paragraph(&old_paragraphs[0]).unwrap();
+        let new = Buildinfo::from_paragraph(&new_paragraphs[0]).unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].name, "autoconf");
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].name, "m4");
+        assert_eq!(
+            diff.changed,
+            vec![VersionChange {
+                name: "libc6",
+                architecture: Some("amd64"),
+                old_version: "2.36-9",
+                new_version: "2.37-1",
+            }]
+        );
+        assert!(!diff.is_empty());
+        assert!(old.diff(&old).is_empty());
+    }
+}
@@ -0,0 +1,445 @@
+//! Upload control files (`.changes`).
+//!
+//! A `.changes` file describes an upload to the archive: which source and binary packages it
+//! contains, the target distribution, the changelog entries, and the list of uploaded files along
+//! with their checksums.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{formats::changes::{Changes, UrgencyLevel}, parse_str};
+//! let paragraphs = parse_str("\
+//! Source: foo
+//! Binary: foo foo-doc
+//! Version: 1.0-1
+//! Distribution: unstable
+//! Urgency: medium
+//! Maintainer: Jane Doe <jane@example.org>
+//! Closes: 123456
+//! Changes:
+//!  foo (1.0-1) unstable; urgency=medium
+//!  .
+//!    * Initial release. (Closes: #123456)
+//! Checksums-Sha256:
+//!  0123abcd 1024 foo_1.0-1.dsc
+//! Files:
+//!  4567cdef 1024 misc optional foo_1.0-1.dsc
+//! ").unwrap();
+//!
+//! let changes = Changes::from_paragraph(&paragraphs[0]).unwrap();
+//! assert_eq!(changes.binary, vec!["foo", "foo-doc"]);
+//! assert_eq!(changes.urgency.as_ref().unwrap().level, UrgencyLevel::Medium);
+//! assert_eq!(changes.closes, vec![123456]);
+//! assert!(changes.validate().is_empty());
+//! ```
+
+use crate::{
+    formats::checksums::{columns, parse_checksums, Checksum, ChecksumError},
+    Paragraph,
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// An error when interpreting a `.changes` paragraph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ChangesError {
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// A line in the `Files` field is invalid.
+    InvalidFile(String),
+    /// A line in one of the `Checksums-*` fields is invalid.
+    InvalidChecksum(ChecksumError),
+    /// The `Urgency` field has an unknown urgency level.
+    InvalidUrgency(String),
+    /// The `Closes` field contains something that isn't a bug number.
+    InvalidBugNumber(String),
+}
+
+impl fmt::Display for ChangesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangesError::MissingField(field) => write!(f, "missing required field {}", field),
+            ChangesError::InvalidFile(line) => write!(f, "invalid Files line '{}'", line),
+            ChangesError::InvalidChecksum(err) => write!(f, "{}", err),
+            ChangesError::InvalidUrgency(value) => write!(f, "invalid urgency '{}'", value),
+            ChangesError::InvalidBugNumber(value) => write!(f, "invalid bug number '{}'", value),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChangesError {}
+
+impl From<ChecksumError> for ChangesError {
+    fn from(err: ChecksumError) -> Self {
+        ChangesError::InvalidChecksum(err)
+    }
+}
+
+/// An inconsistency between the file tables of a `.changes` file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ValidationError {
+    /// A file listed in `Checksums-Sha256` is missing from `Files`.
+    NotInFiles(String),
+    /// A file listed in `Files` is missing from `Checksums-Sha256`.
+    NotInChecksums(String),
+    /// A file has different sizes in `Files` and `Checksums-Sha256`.
+    SizeMismatch {
+        name: String,
+        files_size: u64,
+        checksums_size: u64,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NotInFiles(name) => {
+                write!(f, "{} is listed in Checksums-Sha256 but not in Files", name)
+            }
+            ValidationError::NotInChecksums(name) => {
+                write!(f, "{} is listed in Files but not in Checksums-Sha256", name)
+            }
+            ValidationError::SizeMismatch {
+                name,
+                files_size,
+                checksums_size,
+            } => write!(
+                f,
+                "{} has size {} in Files but {} in Checksums-Sha256",
+                name, files_size, checksums_size
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+/// A single line in the `Files` field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct File<'a> {
+    pub md5: &'a str,
+    pub size: u64,
+    pub section: &'a str,
+    pub priority: &'a str,
+    pub name: &'a str,
+}
+
+impl<'a> File<'a> {
+    fn parse(line: &'a str) -> Result<Self, ChangesError> {
+        let invalid = || ChangesError::InvalidFile(String::from(line));
+        let [md5, size, section, priority, name] = columns(line).ok_or_else(invalid)?;
+        Ok(File {
+            md5,
+            size: size.parse().map_err(|_| invalid())?,
+            section,
+            priority,
+            name,
+        })
+    }
+}
+
+/// The urgency level of an upload.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum UrgencyLevel {
+    Low,
+    Medium,
+    High,
+    Emergency,
+    Critical,
+}
+
+/// The value of the `Urgency` field: an urgency level and an optional comment.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Urgency<'a> {
+    pub level: UrgencyLevel,
+    pub comment: Option<&'a str>,
+}
+
+impl<'a> Urgency<'a> {
+    fn parse(value: &'a str) -> Result<Self, ChangesError> {
+        let (level, comment) = match value.split_once(char::is_whitespace) {
+            Some((level, comment)) => (level, Some(comment.trim())),
+            None => (value, None),
+        };
+        let level = match level.to_ascii_lowercase().as_str() {
+            "low" => UrgencyLevel::Low,
+            "medium" => UrgencyLevel::Medium,
+            "high" => UrgencyLevel::High,
+            "emergency" => UrgencyLevel::Emergency,
+            "critical" => UrgencyLevel::Critical,
+            _ => return Err(ChangesError::InvalidUrgency(String::from(value))),
+        };
+        Ok(Urgency { level, comment })
+    }
+}
+
+/// The interpreted contents of a `.changes` file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Changes<'a> {
+    pub format: Option<&'a str>,
+    pub date: Option<&'a str>,
+    pub source: &'a str,
+    /// The binary packages in the upload.
+    pub binary: Vec<&'a str>,
+    /// The architectures in the upload, including `source` for source uploads.
+    pub architecture: Vec<&'a str>,
+    pub version: &'a str,
+    /// The target distributions.
+    pub distribution: Vec<&'a str>,
+    pub urgency: Option<Urgency<'a>>,
+    pub maintainer: &'a str,
+    pub changed_by: Option<&'a str>,
+    pub description: Option<&'a str>,
+    /// The bug numbers closed by this upload.
+    pub closes: Vec<u64>,
+    /// The changelog entries, as free text.
+    pub changes: Option<&'a str>,
+    pub checksums_sha1: Vec<Checksum<'a>>,
+    pub checksums_sha256: Vec<Checksum<'a>>,
+    pub files: Vec<File<'a>>,
+}
+
+impl<'a> Changes<'a> {
+    /// Interpret a `.changes` paragraph.
+    ///
+    /// Signed files need to be unwrapped with
+    /// [`strip_signature`](../fn.strip_signature.html) before parsing.
+    pub fn from_paragraph(paragraph: &'a Paragraph<'_>) -> Result<Self, ChangesError> {
+        let required = |name| paragraph.get(name).ok_or(ChangesError::MissingField(name));
+        let words = |name| {
+            paragraph
+                .get(name)
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+        };
+        let checksums = |name| {
+            paragraph
+                .get(name)
+                .map_or_else(|| Ok(Vec::new()), parse_checksums)
+        };
+
+        Ok(Changes {
+            format: paragraph.get("Format"),
+            date: paragraph.get("Date"),
+            source: required("Source")?,
+            binary: words("Binary"),
+            architecture: words("Architecture"),
+            version: required("Version")?,
+            distribution: words("Distribution"),
+            urgency: paragraph.get("Urgency").map(Urgency::parse).transpose()?,
+            maintainer: required("Maintainer")?,
+            changed_by: paragraph.get("Changed-By"),
+            description: paragraph.get("Description"),
+            closes: words("Closes")
+                .into_iter()
+                .map(|bug| {
+                    bug.trim_start_matches('#')
+                        .parse()
+                        .map_err(|_| ChangesError::InvalidBugNumber(String::from(bug)))
+                })
+                .collect::<Result<_, _>>()?,
+            changes: paragraph.get("Changes"),
+            checksums_sha1: checksums("Checksums-Sha1")?,
+            checksums_sha256: checksums("Checksums-Sha256")?,
+            files: required("Files")?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(File::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Check that `Files` and `Checksums-Sha256` list the same files with the same sizes.
+    ///
+    /// All problems are returned; an empty list means the tables are consistent.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        for checksum in &self.checksums_sha256 {
+            match self.files.iter().find(|file| file.name == checksum.name) {
+                None => errors.push(ValidationError::NotInFiles(String::from(checksum.name))),
+                Some(file) if file.size != checksum.size => {
+                    errors.push(ValidationError::SizeMismatch {
+                        name: String::from(checksum.name),
+                        files_size: file.size,
+                        checksums_size: checksum.size,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for file in &self.files {
+            if !self
+                .checksums_sha256
+                .iter()
+                .any(|checksum| checksum.name == file.name)
+            {
+                errors.push(ValidationError::NotInChecksums(String::from(file.name)));
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{formats::strip_signature, parse_str};
+    use alloc::vec;
+    use indoc::indoc;
+
+    const CHANGES: &str = indoc!(
+        "
+        -----BEGIN PGP SIGNED MESSAGE-----
+        Hash: SHA512
+
+        Format: 1.8
+        Date: Sat, 02 Sep 2023 12:00:00 +0200
+        Source: hello
+        Binary: hello hello-dbgsym
+        Architecture: source amd64
+        Version: 2.10-3
+        Distribution: unstable experimental
+        Urgency: low (no hurry)
+        Maintainer: Santiago Vila <sanvila@debian.org>
+        Changed-By: Jane Doe <jane@example.org>
+        Description:
+         hello      - example package based on GNU hello
+        Closes: 871622 #893083
+        Changes:
+         hello (2.10-3) unstable; urgency=low
+         .
+           * Update standards version. Closes: #871622, #893083.
+        Checksums-Sha1:
+         8d1c5a3ab8e3c9b7a1e7b4fbd23ab1a02e6b43d9 1847 hello_2.10-3.dsc
+         b8c6b4c53d8f1b3e8d5f2a1e4c3b2a1908f7e6d5 53180 hello_2.10-3_amd64.deb
+        Checksums-Sha256:
+         a7b3e3d0b1c6c92f4d64df5e0b1d7b8f2cb5c1e8a2f2b0a9d8c7e6f5a4b3c2d1 1847 hello_2.10-3.dsc
+         f1e2d3c4b5a6978877665544332211ffeeddccbbaa9988776655443322110000 53180 hello_2.10-3_amd64.deb
+        Files:
+         5b7c2f1e0d9c8b7a6f5e4d3c2b1a0f9e 1847 devel optional hello_2.10-3.dsc
+         0f9e8d7c6b5a49382716a5b4c3d2e1f0 53180 devel optional hello_2.10-3_amd64.deb
+        -----BEGIN PGP SIGNATURE-----
+
+        iQIzBAEBCgAdFiEE
+        -----END PGP SIGNATURE-----
+        "
+    );
+
+    #[test]
+    fn should_parse_signed_changes_file() {
+        let input = strip_signature(CHANGES);
+        let paragraphs = parse_str(&input).unwrap();
+        let changes = Changes::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(changes.format, Some("1.8"));
+        assert_eq!(changes.source, "hello");
+        assert_eq!(changes.binary, vec!["hello", "hello-dbgsym"]);
+        assert_eq!(changes.architecture, vec!["source", "amd64"]);
+        assert_eq!(changes.distribution, vec!["unstable", "experimental"]);
+        assert_eq!(changes.changed_by, Some("Jane Doe <jane@example.org>"));
+        assert_eq!(changes.closes, vec![871622, 893083]);
+        assert!(changes
+            .changes
+            .unwrap()
+            .contains("Update standards version"));
+        assert_eq!(changes.checksums_sha1.len(), 2);
+    }
+
+    #[test]
+    fn should_parse_files_table() {
+        let input = strip_signature(CHANGES);
+        let paragraphs = parse_str(&input).unwrap();
+        let changes = Changes::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(
+            changes.files[1],
+            File {
+                md5: "0f9e8d7c6b5a49382716a5b4c3d2e1f0",
+                size: 53180,
+                section: "devel",
+                priority: "optional",
+                name: "hello_2.10-3_amd64.deb",
+            }
+        );
+    }
+
+    #[test]
+    fn should_parse_urgency_with_comment() {
+        let input = strip_signature(CHANGES);
+        let paragraphs = parse_str(&input).unwrap();
+        let changes = Changes::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(
+            changes.urgency,
+            Some(Urgency {
+                level: UrgencyLevel::Low,
+                comment: Some("(no hurry)"),
+            })
+        );
+        assert_eq!(
+            Urgency::parse("CRITICAL"),
+            Ok(Urgency {
+                level: UrgencyLevel::Critical,
+                comment: None
+            })
+        );
+        assert!(Urgency::parse("whenever").is_err());
+    }
+
+    #[test]
+    fn should_report_missing_required_field() {
+        let input = "Source: foo\nVersion: 1\nFiles:\n";
+        let paragraphs = parse_str(input).unwrap();
+        let changes = Changes::from_paragraph(&paragraphs[0]);
+        assert_eq!(changes, Err(ChangesError::MissingField("Maintainer")));
+    }
+
+    #[test]
+    fn should_report_invalid_bug_number() {
+        let input = "Source: a\nVersion: 1\nMaintainer: m\nCloses: 12 abc\nFiles:\n";
+        let paragraphs = parse_str(input).unwrap();
+        let changes = Changes::from_paragraph(&paragraphs[0]);
+        assert_eq!(
+            changes,
+            Err(ChangesError::InvalidBugNumber(String::from("abc")))
+        );
+    }
+
+    #[test]
+    fn should_validate_consistent_tables() {
+        let input = strip_signature(CHANGES);
+        let paragraphs = parse_str(&input).unwrap();
+        let changes = Changes::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(changes.validate(), vec![]);
+    }
+
+    #[test]
+    fn should_report_inconsistent_tables() {
+        let input = indoc!(
+            "
+            Source: a
+            Version: 1
+            Maintainer: m
+            Checksums-Sha256:
+             aaaa 10 a.dsc
+             bbbb 20 a.tar.xz
+            Files:
+             cccc 11 misc optional a.dsc
+             dddd 30 misc optional a.deb
+            "
+        );
+        let paragraphs = parse_str(input).unwrap();
+        let changes = Changes::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(
+            changes.validate(),
+            vec![
+                ValidationError::SizeMismatch {
+                    name: String::from("a.dsc"),
+                    files_size: 11,
+                    checksums_size: 10,
+                },
+                ValidationError::NotInFiles(String::from("a.tar.xz")),
+                ValidationError::NotInChecksums(String::from("a.deb")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Checksum tables such as `Checksums-Sha256`.
+//!
+//! Several control files list files along with their checksums in multiline fields. Every line
+//! consists of the hash, the file size and the file name, separated by whitespace.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// A single line in a checksum table.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Checksum<'a> {
+    pub hash: &'a str,
+    pub size: u64,
+    pub name: &'a str,
+}
+
+/// An invalid line in a checksum table.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ChecksumError {
+    /// The offending line.
+    pub line: String,
+}
+
+impl fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid checksum line '{}'", self.line)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChecksumError {}
+
+impl fmt::Display for Checksum<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.hash, self.size, self.name)
+    }
+}
+
+/// Split a table line into exactly `N` whitespace-separated columns.
+pub(crate) fn columns<const N: usize>(line: &str) -> Option<[&str; N]> {
+    let mut columns = [""; N];
+    let mut words = line.split_whitespace();
+    for column in columns.iter_mut() {
+        *column = words.next()?;
+    }
+    match words.next() {
+        Some(_) => None,
+        None => Some(columns),
+    }
+}
+
+/// Parse a single checksum table line.
+pub fn parse_checksum(line: &str) -> Result<Checksum<'_>, ChecksumError> {
+    let invalid = || ChecksumError {
+        line: String::from(line),
+    };
+    let [hash, size, name] = columns(line).ok_or_else(invalid)?;
+    Ok(Checksum {
+        hash,
+        size: size.parse().map_err(|_| invalid())?,
+        name,
+    })
+}
+
+/// Parse the value of a checksum table field. Empty lines are skipped.
+pub fn parse_checksums(value: &str) -> Result<Vec<Checksum<'_>>, ChecksumError> {
+    value
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_checksum)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn should_parse_checksum_table() {
+        let result = parse_checksums("\nabc 12 foo_1.0.dsc\ndef 345 foo_1.0.tar.xz");
+        assert_eq!(
+            result,
+            Ok(vec![
+                Checksum {
+                    hash: "abc",
+                    size: 12,
+                    name: "foo_1.0.dsc"
+                },
+                Checksum {
+                    hash: "def",
+                    size: 345,
+                    name: "foo_1.0.tar.xz"
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn should_reject_line_with_wrong_number_of_columns() {
+        assert!(parse_checksums("abc 12").is_err());
+        assert!(parse_checksums("abc 12 foo bar").is_err());
+    }
+
+    #[test]
+    fn should_reject_invalid_size() {
+        assert_eq!(
+            parse_checksums("abc -1 foo"),
+            Err(ChecksumError {
+                line: String::from("abc -1 foo")
+            })
+        );
+    }
+}
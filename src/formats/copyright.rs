@@ -0,0 +1,510 @@
+//! Machine-readable `debian/copyright` files ([DEP-5]).
+//!
+//! A machine-readable copyright file starts with a header paragraph, followed by `Files`
+//! paragraphs that assign copyright and license information to sets of files, and standalone
+//! `License` paragraphs containing the full text of licenses referenced elsewhere.
+//!
+//! [DEP-5]: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{formats::copyright::Copyright, parse_str};
+//! let paragraphs = parse_str("\
+//! Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/
+//! Upstream-Name: foo
+//!
+//! Files: *
+//! Copyright: 2023 Jane Doe
+//! License: GPL-2+
+//!
+//! Files: debian/*
+//! Copyright: 2023 John Doe
+//! License: MIT or Expat
+//!
+//! License: GPL-2+
+//!  This program is free software...
+//! ").unwrap();
+//!
+//! let copyright = Copyright::from_paragraphs(&paragraphs).unwrap();
+//! assert_eq!(copyright.files_for("src/main.c").unwrap().license.name, "GPL-2+");
+//! assert_eq!(copyright.files_for("debian/rules").unwrap().license.name, "MIT or Expat");
+//! assert_eq!(copyright.undefined_licenses(), vec!["MIT", "Expat"]);
+//! ```
+
+use crate::Paragraph;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An error when interpreting a copyright file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CopyrightError {
+    /// The file has no header paragraph with a `Format` field.
+    MissingHeader,
+    /// A required field is missing from a paragraph. The paragraph is identified by its index.
+    MissingField {
+        paragraph: usize,
+        field: &'static str,
+    },
+    /// A paragraph is neither a `Files` nor a standalone `License` paragraph.
+    UnknownParagraph(usize),
+}
+
+impl fmt::Display for CopyrightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CopyrightError::MissingHeader => write!(f, "missing header paragraph"),
+            CopyrightError::MissingField { paragraph, field } => write!(
+                f,
+                "paragraph {} is missing required field {}",
+                paragraph, field
+            ),
+            CopyrightError::UnknownParagraph(paragraph) => {
+                write!(
+                    f,
+                    "paragraph {} is neither a Files nor a License paragraph",
+                    paragraph
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CopyrightError {}
+
+/// The value of a `License` field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct License<'a> {
+    /// The license expression on the first line, e.g. `GPL-2+ or Artistic-1.0`.
+    pub name: &'a str,
+    /// The license text in the continuation lines, if any.
+    pub text: Option<&'a str>,
+}
+
+impl<'a> License<'a> {
+    fn parse(value: &'a str) -> Self {
+        let (name, text) = match value.split_once('\n') {
+            Some((name, text)) => (name, Some(text)),
+            None => (value, None),
+        };
+        License {
+            name: name.trim(),
+            text: text.filter(|text| !text.trim().is_empty()),
+        }
+    }
+
+    /// Return the individual license short names in the license expression.
+    ///
+    /// The expression is split at `or` and `and` operators and commas. Exceptions (`with ...
+    /// exception`) remain part of the short name they apply to.
+    pub fn short_names(&self) -> Vec<&'a str> {
+        let mut names = Vec::new();
+        let mut start = None;
+        let mut end = 0;
+        for (pos, word) in words_with_positions(self.name) {
+            let word_end = pos + word.len();
+            let word = word.trim_end_matches(',');
+            if word.eq_ignore_ascii_case("or") || word.eq_ignore_ascii_case("and") {
+                if let Some(start) = start.take() {
+                    names.push(self.name[start..end].trim_end_matches(','));
+                }
+                continue;
+            }
+            if start.is_none() {
+                start = Some(pos);
+            }
+            end = word_end;
+        }
+        if let Some(start) = start {
+            names.push(self.name[start..end].trim_end_matches(','));
+        }
+        names
+    }
+}
+
+/// Iterate over the whitespace-separated words in a string along with their byte offsets.
+fn words_with_positions(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.split(char::is_whitespace)
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - s.as_ptr() as usize, word))
+}
+
+/// The header paragraph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Header<'a> {
+    pub format: &'a str,
+    pub upstream_name: Option<&'a str>,
+    pub upstream_contact: Option<&'a str>,
+    pub source: Option<&'a str>,
+    pub disclaimer: Option<&'a str>,
+    pub comment: Option<&'a str>,
+    pub license: Option<License<'a>>,
+    pub copyright: Option<&'a str>,
+}
+
+/// A `Files` paragraph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Files<'a> {
+    /// The glob patterns from the `Files` field.
+    pub patterns: Vec<&'a str>,
+    pub copyright: &'a str,
+    pub license: License<'a>,
+    pub comment: Option<&'a str>,
+}
+
+impl Files<'_> {
+    /// Return true if any of the patterns in this paragraph match the given path.
+    pub fn matches(&self, path: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, path))
+    }
+}
+
+/// A standalone `License` paragraph.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StandaloneLicense<'a> {
+    pub license: License<'a>,
+    pub comment: Option<&'a str>,
+}
+
+/// Match a path against a `Files` pattern.
+///
+/// `*` matches any sequence of characters including `/`, `?` matches a single character, and a
+/// backslash escapes `*`, `?` and itself. Patterns always match the whole path.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    let path = path.strip_prefix("./").unwrap_or(path);
+    glob_match_chars(pattern.as_bytes(), path.as_bytes())
+}
+
+fn glob_match_chars(pattern: &[u8], path: &[u8]) -> bool {
+    // Iterative matching with backtracking to the most recent `*`.
+    let (mut p, mut s) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while s < path.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, s));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => {
+                // Step over a whole UTF-8 character.
+                s += 1;
+                while s < path.len() && path[s] & 0xc0 == 0x80 {
+                    s += 1;
+                }
+                p += 1;
+                continue;
+            }
+            Some(b'\\') if pattern.get(p + 1).is_some() && pattern[p + 1] == path[s] => {
+                p += 2;
+                s += 1;
+                continue;
+            }
+            Some(&c) if c != b'\\' && c == path[s] => {
+                p += 1;
+                s += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match star {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                star = Some((star_p, star_s + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// The interpreted contents of a machine-readable copyright file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Copyright<'a> {
+    pub header: Header<'a>,
+    pub files: Vec<Files<'a>>,
+    pub licenses: Vec<StandaloneLicense<'a>>,
+}
+
+impl<'a> Copyright<'a> {
+    /// Interpret the paragraphs of a copyright file.
+    pub fn from_paragraphs(paragraphs: &'a [Paragraph<'_>]) -> Result<Self, CopyrightError> {
+        let (header, rest) = paragraphs
+            .split_first()
+            .ok_or(CopyrightError::MissingHeader)?;
+        let header = Header {
+            format: header.get("Format").ok_or(CopyrightError::MissingHeader)?,
+            upstream_name: header.get("Upstream-Name"),
+            upstream_contact: header.get("Upstream-Contact"),
+            source: header.get("Source"),
+            disclaimer: header.get("Disclaimer"),
+            comment: header.get("Comment"),
+            license: header.get("License").map(License::parse),
+            copyright: header.get("Copyright"),
+        };
+
+        let mut files = Vec::new();
+        let mut licenses = Vec::new();
+        for (index, paragraph) in rest.iter().enumerate() {
+            let index = index + 1;
+            let required = |field| {
+                paragraph.get(field).ok_or(CopyrightError::MissingField {
+                    paragraph: index,
+                    field,
+                })
+            };
+            if let Some(patterns) = paragraph.get("Files") {
+                files.push(Files {
+                    patterns: patterns.split_whitespace().collect(),
+                    copyright: required("Copyright")?,
+                    license: License::parse(required("License")?),
+                    comment: paragraph.get("Comment"),
+                });
+            } else if let Some(license) = paragraph.get("License") {
+                licenses.push(StandaloneLicense {
+                    license: License::parse(license),
+                    comment: paragraph.get("Comment"),
+                });
+            } else {
+                return Err(CopyrightError::UnknownParagraph(index));
+            }
+        }
+
+        Ok(Copyright {
+            header,
+            files,
+            licenses,
+        })
+    }
+
+    /// Return the `Files` paragraph that applies to a path.
+    ///
+    /// As required by the specification, the last paragraph with a matching pattern wins.
+    pub fn files_for(&self, path: &str) -> Option<&Files<'a>> {
+        self.files.iter().rev().find(|files| files.matches(path))
+    }
+
+    /// Return the full text of the license with the given short name.
+    ///
+    /// Standalone `License` paragraphs are searched first, followed by any `Files` or header
+    /// paragraph that includes the license text.
+    pub fn license_text(&self, short_name: &str) -> Option<&'a str> {
+        let standalone = self.licenses.iter().map(|license| &license.license);
+        let inline = self
+            .header
+            .license
+            .iter()
+            .chain(self.files.iter().map(|files| &files.license));
+        standalone
+            .chain(inline)
+            .filter(|license| license.name.eq_ignore_ascii_case(short_name))
+            .find_map(|license| license.text)
+    }
+
+    /// Return the short names of all licenses that are referenced but whose text is not defined
+    /// anywhere in the file.
+    ///
+    /// A short name with an exception (e.g. `GPL-2+ with OpenSSL exception`) counts as defined if
+    /// either the full name or the base license is defined.
+    pub fn undefined_licenses(&self) -> Vec<&'a str> {
+        let mut undefined = Vec::new();
+        let referenced = self
+            .header
+            .license
+            .iter()
+            .chain(self.files.iter().map(|files| &files.license))
+            .filter(|license| license.text.is_none());
+        for license in referenced {
+            for name in license.short_names() {
+                let base = split_exception(name);
+                if self.license_text(name).is_none()
+                    && self.license_text(base).is_none()
+                    && !undefined.contains(&name)
+                {
+                    undefined.push(name);
+                }
+            }
+        }
+        undefined
+    }
+}
+
+/// Return the license short name without a trailing `with ... exception` clause.
+fn split_exception(name: &str) -> &str {
+    words_with_positions(name)
+        .find(|(_, word)| word.eq_ignore_ascii_case("with"))
+        .map_or(name, |(pos, _)| name[..pos].trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::vec;
+    use indoc::indoc;
+
+    const COPYRIGHT: &str = indoc!(
+        "
+        Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/
+        Upstream-Name: ostree
+        Source: https://github.com/ostreedev/ostree
+        License: LGPL-2+
+
+        Files: *
+        Copyright: 2011-2019 Colin Walters
+        License: LGPL-2+
+
+        Files: src/libotutil/* src/libostree/ostree-lzma-*.c
+        Copyright: 2014 Red Hat, Inc.
+        License: LGPL-2+ or MIT
+
+        Files: tests/*.sh
+         debian/*
+        Copyright: 2013 Simon McVittie
+        License: GPL-2+ with OpenSSL exception
+
+        Files: bsdiff/*
+        Copyright: 2003-2005 Colin Percival
+        License: BSD-2-clause
+         Redistribution and use in source and binary forms...
+
+        License: LGPL-2+
+         This library is free software...
+
+        License: GPL-2+
+         This program is free software...
+        "
+    );
+
+    mod glob {
+        use super::*;
+
+        #[test]
+        fn should_match_star_across_directories() {
+            assert!(glob_match("*", "a/b/c.txt"));
+            assert!(glob_match("src/*", "src/a/b.c"));
+            assert!(glob_match("*.c", "src/a/b.c"));
+            assert!(!glob_match("src/*", "tests/a.c"));
+        }
+
+        #[test]
+        fn should_match_question_mark_as_single_char() {
+            assert!(glob_match("a?c", "abc"));
+            assert!(glob_match("a?c", "aäc"));
+            assert!(!glob_match("a?c", "abbc"));
+            assert!(!glob_match("a?c", "ac"));
+        }
+
+        #[test]
+        fn should_match_escaped_characters_literally() {
+            assert!(glob_match(r"a\*b", "a*b"));
+            assert!(!glob_match(r"a\*b", "axb"));
+            assert!(glob_match(r"a\?", "a?"));
+            assert!(glob_match(r"a\\b", r"a\b"));
+        }
+
+        #[test]
+        fn should_match_whole_path() {
+            assert!(!glob_match("src", "src/main.c"));
+            assert!(glob_match("README", "./README"));
+            assert!(glob_match("a*b*c", "axxbyyc"));
+            assert!(!glob_match("a*b*c", "axxbyy"));
+        }
+    }
+
+    #[test]
+    fn should_parse_header() {
+        let paragraphs = parse_str(COPYRIGHT).unwrap();
+        let copyright = Copyright::from_paragraphs(&paragraphs).unwrap();
+        assert_eq!(copyright.header.upstream_name, Some("ostree"));
+        assert_eq!(copyright.header.license.unwrap().name, "LGPL-2+");
+        assert_eq!(copyright.files.len(), 4);
+        assert_eq!(copyright.licenses.len(), 2);
+        assert_eq!(copyright.files[2].patterns, vec!["tests/*.sh", "debian/*"]);
+    }
+
+    #[test]
+    fn should_use_last_matching_files_paragraph() {
+        let paragraphs = parse_str(COPYRIGHT).unwrap();
+        let copyright = Copyright::from_paragraphs(&paragraphs).unwrap();
+        let files_for = |path| copyright.files_for(path).unwrap().license.name;
+        assert_eq!(files_for("README.md"), "LGPL-2+");
+        assert_eq!(files_for("src/libotutil/ot-fs-utils.c"), "LGPL-2+ or MIT");
+        assert_eq!(
+            files_for("src/libostree/ostree-lzma-common.c"),
+            "LGPL-2+ or MIT"
+        );
+        assert_eq!(files_for("src/libostree/ostree-repo.c"), "LGPL-2+");
+        assert_eq!(files_for("debian/rules"), "GPL-2+ with OpenSSL exception");
+        assert_eq!(
+            files_for("tests/libtest.sh"),
+            "GPL-2+ with OpenSSL exception"
+        );
+    }
+
+    #[test]
+    fn should_split_license_expressions() {
+        let license = License::parse("GPL-2+ or Artistic-1.0, and BSD-3-clause");
+        assert_eq!(
+            license.short_names(),
+            vec!["GPL-2+", "Artistic-1.0", "BSD-3-clause"]
+        );
+        let license = License::parse("GPL-2+ with OpenSSL exception or MIT");
+        assert_eq!(
+            license.short_names(),
+            vec!["GPL-2+ with OpenSSL exception", "MIT"]
+        );
+    }
+
+    #[test]
+    fn should_resolve_license_text() {
+        let paragraphs = parse_str(COPYRIGHT).unwrap();
+        let copyright = Copyright::from_paragraphs(&paragraphs).unwrap();
+        assert_eq!(
+            copyright.license_text("GPL-2+"),
+            Some("This program is free software...")
+        );
+        assert_eq!(
+            copyright.license_text("bsd-2-clause"),
+            Some("Redistribution and use in source and binary forms...")
+        );
+        assert_eq!(copyright.license_text("MIT"), None);
+    }
+
+    #[test]
+    fn should_report_undefined_licenses() {
+        let paragraphs = parse_str(COPYRIGHT).unwrap();
+        let copyright = Copyright::from_paragraphs(&paragraphs).unwrap();
+        assert_eq!(copyright.undefined_licenses(), vec!["MIT"]);
+    }
+
+    #[test]
+    fn should_require_header() {
+        let paragraphs = parse_str("Files: *\nCopyright: x\nLicense: MIT\n").unwrap();
+        assert_eq!(
+            Copyright::from_paragraphs(&paragraphs),
+            Err(CopyrightError::MissingHeader)
+        );
+    }
+
+    #[test]
+    fn should_report_missing_fields_and_unknown_paragraphs() {
+        let paragraphs = parse_str("Format: x\n\nFiles: *\nLicense: MIT\n").unwrap();
+        assert_eq!(
+            Copyright::from_paragraphs(&paragraphs),
+            Err(CopyrightError::MissingField {
+                paragraph: 1,
+                field: "Copyright"
+            })
+        );
+        let paragraphs = parse_str("Format: x\n\nComment: what\n").unwrap();
+        assert_eq!(
+            Copyright::from_paragraphs(&paragraphs),
+            Err(CopyrightError::UnknownParagraph(1))
+        );
+    }
+}
@@ -0,0 +1,534 @@
+//! Source package control files (`debian/control`).
+//!
+//! A `debian/control` file consists of a source paragraph describing the source package, followed
+//! by one binary paragraph for every binary package built from it. The file is kept in its
+//! [lossless](../../lossless/index.html) form, so comments and formatting survive when binary
+//! paragraphs are added or removed and the file is written back.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::formats::debian_control::ControlFile;
+//! let control = ControlFile::parse("\
+//! Source: foo
+//! Maintainer: Jane Doe <jane@example.org>
+//! Build-Depends: debhelper-compat (= 13)
+//!
+//! Package: foo
+//! Architecture: any
+//! Description: foo
+//! ").unwrap();
+//!
+//! assert!(control.validate().is_empty());
+//! assert_eq!(control.source().unwrap().name(), Some("foo"));
+//! assert_eq!(control.source().unwrap().build_depends(), Some("debhelper-compat (= 13)"));
+//! assert_eq!(control.binaries().map(|b| b.package()).collect::<Vec<_>>(), vec![Some("foo")]);
+//! ```
+
+use crate::{
+    lossless::{Document, Paragraph},
+    parse_relations, reduce_relations, Alternatives, BuildContext, RelationError, SyntaxError,
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// The fields required in the source paragraph.
+const REQUIRED_SOURCE_FIELDS: &[&str] = &["Source", "Maintainer"];
+
+/// The fields required in every binary paragraph.
+const REQUIRED_BINARY_FIELDS: &[&str] = &["Package", "Architecture"];
+
+/// A problem found when validating a control file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ValidationError {
+    /// The file doesn't contain any paragraphs.
+    Empty,
+    /// A required field is missing from the source paragraph.
+    MissingSourceField(&'static str),
+    /// A required field is missing from a binary paragraph.
+    ///
+    /// The paragraph is identified by its index among the binary paragraphs, and its package name
+    /// if it has one.
+    MissingBinaryField {
+        index: usize,
+        package: Option<String>,
+        field: &'static str,
+    },
+    /// More than one binary paragraph declares the same package.
+    DuplicatePackage(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Empty => write!(f, "control file is empty"),
+            ValidationError::MissingSourceField(field) => {
+                write!(f, "source paragraph is missing required field {}", field)
+            }
+            ValidationError::MissingBinaryField {
+                package: Some(package),
+                field,
+                ..
+            } => write!(f, "package {} is missing required field {}", package, field),
+            ValidationError::MissingBinaryField {
+                index,
+                package: None,
+                field,
+            } => write!(
+                f,
+                "binary paragraph {} is missing required field {}",
+                index, field
+            ),
+            ValidationError::DuplicatePackage(package) => {
+                write!(f, "package {} is declared more than once", package)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+/// The value of the `Rules-Requires-Root` field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum RulesRequiresRoot<'a> {
+    /// The `binary` targets don't need root.
+    No,
+    /// The `binary` targets must be run as (fake)root.
+    BinaryTargets,
+    /// Root is needed only for the given keywords.
+    Keywords(Vec<&'a str>),
+}
+
+impl<'a> RulesRequiresRoot<'a> {
+    fn parse(value: &'a str) -> Self {
+        match value.trim() {
+            "no" => RulesRequiresRoot::No,
+            "binary-targets" => RulesRequiresRoot::BinaryTargets,
+            keywords => RulesRequiresRoot::Keywords(keywords.split_whitespace().collect()),
+        }
+    }
+}
+
+/// The source paragraph of a control file.
+#[derive(Debug, Clone, Copy)]
+pub struct Source<'a> {
+    pub paragraph: &'a Paragraph,
+}
+
+impl<'a> Source<'a> {
+    /// Return the source package name.
+    pub fn name(&self) -> Option<&'a str> {
+        self.paragraph.get("Source")
+    }
+
+    /// Return the `Maintainer` field.
+    pub fn maintainer(&self) -> Option<&'a str> {
+        self.paragraph.get("Maintainer")
+    }
+
+    /// Return the `Build-Depends` field.
+    pub fn build_depends(&self) -> Option<&'a str> {
+        self.paragraph.get("Build-Depends")
+    }
+
+    /// Return the `Build-Depends-Indep` field.
+    pub fn build_depends_indep(&self) -> Option<&'a str> {
+        self.paragraph.get("Build-Depends-Indep")
+    }
+
+    /// Return the `Build-Depends-Arch` field.
+    pub fn build_depends_arch(&self) -> Option<&'a str> {
+        self.paragraph.get("Build-Depends-Arch")
+    }
+
+    /// Return the `Build-Conflicts` field.
+    pub fn build_conflicts(&self) -> Option<&'a str> {
+        self.paragraph.get("Build-Conflicts")
+    }
+
+    /// Return the `Build-Conflicts-Indep` field.
+    pub fn build_conflicts_indep(&self) -> Option<&'a str> {
+        self.paragraph.get("Build-Conflicts-Indep")
+    }
+
+    /// Return the `Build-Conflicts-Arch` field.
+    pub fn build_conflicts_arch(&self) -> Option<&'a str> {
+        self.paragraph.get("Build-Conflicts-Arch")
+    }
+
+    /// Parse the given relationship fields and reduce them for the build environment.
+    fn reduced_relations(
+        &self,
+        fields: &[&str],
+        context: &BuildContext<'a>,
+    ) -> Result<Vec<Alternatives<'a>>, RelationError> {
+        let mut relations = Vec::new();
+        for field in fields {
+            if let Some(value) = self.paragraph.get(field) {
+                relations.extend(reduce_relations(&parse_relations(value)?, context));
+            }
+        }
+        Ok(relations)
+    }
+
+    /// Return the build dependencies that `dpkg-checkbuilddeps` checks in the given build
+    /// environment.
+    ///
+    /// This combines `Build-Depends`, `Build-Depends-Arch` and `Build-Depends-Indep`, and drops
+    /// alternatives whose architecture restrictions or build profile formulas don't hold. See
+    /// [`reduce_relations`](../../fn.reduce_relations.html).
+    pub fn build_dependencies(
+        &self,
+        context: &BuildContext<'a>,
+    ) -> Result<Vec<Alternatives<'a>>, RelationError> {
+        self.reduced_relations(
+            &["Build-Depends", "Build-Depends-Arch", "Build-Depends-Indep"],
+            context,
+        )
+    }
+
+    /// Return the build conflicts that `dpkg-checkbuilddeps` checks in the given build
+    /// environment, combining `Build-Conflicts`, `Build-Conflicts-Arch` and
+    /// `Build-Conflicts-Indep`.
+    pub fn build_conflicts_in(
+        &self,
+        context: &BuildContext<'a>,
+    ) -> Result<Vec<Alternatives<'a>>, RelationError> {
+        self.reduced_relations(
+            &[
+                "Build-Conflicts",
+                "Build-Conflicts-Arch",
+                "Build-Conflicts-Indep",
+            ],
+            context,
+        )
+    }
+
+    /// Return the parsed `Rules-Requires-Root` field.
+    pub fn rules_requires_root(&self) -> Option<RulesRequiresRoot<'a>> {
+        self.paragraph
+            .get("Rules-Requires-Root")
+            .map(RulesRequiresRoot::parse)
+    }
+
+    /// Return the `Standards-Version` field.
+    pub fn standards_version(&self) -> Option<&'a str> {
+        self.paragraph.get("Standards-Version")
+    }
+
+    /// Return all `Vcs-*` fields as pairs of the VCS type (e.g. `Git` or `Browser`) and value.
+    pub fn vcs(&self) -> Vec<(&'a str, &'a str)> {
+        self.paragraph
+            .fields()
+            .filter_map(|field| {
+                let name = field.name();
+                match (name.get(..4), name.get(4..)) {
+                    (Some(prefix), Some(kind)) if prefix.eq_ignore_ascii_case("Vcs-") => {
+                        Some((kind, field.value()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Return the test suites declared in the `Testsuite` field.
+    pub fn testsuite(&self) -> Vec<&'a str> {
+        self.paragraph
+            .get("Testsuite")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|suite| !suite.is_empty())
+            .collect()
+    }
+}
+
+/// A binary paragraph of a control file.
+#[derive(Debug, Clone, Copy)]
+pub struct Binary<'a> {
+    pub paragraph: &'a Paragraph,
+}
+
+impl<'a> Binary<'a> {
+    /// Return the binary package name.
+    pub fn package(&self) -> Option<&'a str> {
+        self.paragraph.get("Package")
+    }
+
+    /// Return the `Architecture` field.
+    pub fn architecture(&self) -> Option<&'a str> {
+        self.paragraph.get("Architecture")
+    }
+}
+
+/// A `debian/control` file.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ControlFile {
+    pub document: Document,
+}
+
+impl ControlFile {
+    /// Parse a complete control file.
+    pub fn parse(input: &str) -> Result<ControlFile, SyntaxError<'_>> {
+        Ok(ControlFile {
+            document: Document::parse(input)?,
+        })
+    }
+
+    /// Return the source paragraph, i.e. the first paragraph in the file.
+    pub fn source(&self) -> Option<Source<'_>> {
+        self.document
+            .paragraphs()
+            .next()
+            .map(|paragraph| Source { paragraph })
+    }
+
+    /// Return the binary paragraphs, i.e. all paragraphs after the first.
+    pub fn binaries(&self) -> impl Iterator<Item = Binary<'_>> {
+        self.document
+            .paragraphs()
+            .skip(1)
+            .map(|paragraph| Binary { paragraph })
+    }
+
+    /// Return the binary paragraph for a package.
+    pub fn binary(&self, package: &str) -> Option<Binary<'_>> {
+        self.binaries()
+            .find(|binary| binary.package() == Some(package))
+    }
+
+    /// Return a mutable reference to the paragraph of a binary package.
+    pub fn binary_mut(&mut self, package: &str) -> Option<&mut Paragraph> {
+        self.document
+            .paragraphs_mut()
+            .skip(1)
+            .find(|paragraph| paragraph.get("Package") == Some(package))
+    }
+
+    /// Append a binary paragraph at the end of the file.
+    pub fn add_binary(&mut self, paragraph: Paragraph) {
+        self.document.push_paragraph(paragraph);
+    }
+
+    /// Remove the binary paragraph for a package and return it.
+    ///
+    /// Comments between paragraphs are kept.
+    pub fn remove_binary(&mut self, package: &str) -> Option<Paragraph> {
+        let index = self
+            .binaries()
+            .position(|binary| binary.package() == Some(package))?;
+        self.document.remove_paragraph(index + 1)
+    }
+
+    /// Check that all required fields are present and that no binary package is declared twice.
+    ///
+    /// All problems are returned; an empty list means the file is valid.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let source = match self.source() {
+            Some(source) => source,
+            None => {
+                errors.push(ValidationError::Empty);
+                return errors;
+            }
+        };
+        for field in REQUIRED_SOURCE_FIELDS {
+            if source.paragraph.field(field).is_none() {
+                errors.push(ValidationError::MissingSourceField(field));
+            }
+        }
+
+        let mut seen = Vec::new();
+        for (index, binary) in self.binaries().enumerate() {
+            let package = binary.package();
+            for field in REQUIRED_BINARY_FIELDS {
+                if binary.paragraph.field(field).is_none() {
+                    errors.push(ValidationError::MissingBinaryField {
+                        index,
+                        package: package.map(String::from),
+                        field,
+                    });
+                }
+            }
+            if let Some(package) = package {
+                if seen.contains(&package) {
+                    errors.push(ValidationError::DuplicatePackage(String::from(package)));
+                } else {
+                    seen.push(package);
+                }
+            }
+        }
+        errors
+    }
+}
+
+impl fmt::Display for ControlFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+    use indoc::indoc;
+
+    const CONTROL: &str = indoc!(
+        "
+        Source: foo
+        Maintainer: Jane Doe <jane@example.org>
+        Build-Depends: debhelper-compat (= 13), libbar-dev
+        Build-Depends-Indep: pandoc
+        Rules-Requires-Root: dpkg/target-subcommand  my/keyword
+        Standards-Version: 4.6.2
+        Vcs-Git: https://salsa.debian.org/foo.git
+        Vcs-Browser: https://salsa.debian.org/foo
+        Testsuite: autopkgtest, autopkgtest-pkg-python
+
+        # the main package
+        Package: foo
+        Architecture: any
+        Description: foo
+
+        Package: foo-doc
+        Architecture: all
+        Description: foo documentation
+        "
+    );
+
+    #[test]
+    fn should_expose_source_fields() {
+        let control = ControlFile::parse(CONTROL).unwrap();
+        let source = control.source().unwrap();
+        assert_eq!(source.name(), Some("foo"));
+        assert_eq!(source.maintainer(), Some("Jane Doe <jane@example.org>"));
+        assert_eq!(source.build_depends_indep(), Some("pandoc"));
+        assert_eq!(source.build_depends_arch(), None);
+        assert_eq!(source.standards_version(), Some("4.6.2"));
+        assert_eq!(
+            source.vcs(),
+            vec![
+                ("Git", "https://salsa.debian.org/foo.git"),
+                ("Browser", "https://salsa.debian.org/foo")
+            ]
+        );
+        assert_eq!(
+            source.testsuite(),
+            vec!["autopkgtest", "autopkgtest-pkg-python"]
+        );
+    }
+
+    #[test]
+    fn should_parse_rules_requires_root() {
+        assert_eq!(RulesRequiresRoot::parse("no"), RulesRequiresRoot::No);
+        assert_eq!(
+            RulesRequiresRoot::parse("binary-targets"),
+            RulesRequiresRoot::BinaryTargets
+        );
+        let control = ControlFile::parse(CONTROL).unwrap();
+        assert_eq!(
+            control.source().unwrap().rules_requires_root(),
+            Some(RulesRequiresRoot::Keywords(vec![
+                "dpkg/target-subcommand",
+                "my/keyword"
+            ]))
+        );
+    }
+
+    #[test]
+    fn should_accept_valid_file() {
+        let control = ControlFile::parse(CONTROL).unwrap();
+        assert_eq!(control.validate(), vec![]);
+    }
+
+    #[test]
+    fn should_report_missing_and_duplicate_fields() {
+        let control = ControlFile::parse(indoc!(
+            "
+            Source: foo
+
+            Package: foo
+
+            Architecture: all
+
+            Package: foo
+            Architecture: any
+            "
+        ))
+        .unwrap();
+        assert_eq!(
+            control.validate(),
+            vec![
+                ValidationError::MissingSourceField("Maintainer"),
+                ValidationError::MissingBinaryField {
+                    index: 0,
+                    package: Some("foo".to_string()),
+                    field: "Architecture"
+                },
+                ValidationError::MissingBinaryField {
+                    index: 1,
+                    package: None,
+                    field: "Package"
+                },
+                ValidationError::DuplicatePackage("foo".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_empty_file() {
+        let control = ControlFile::parse("# nothing here\n").unwrap();
+        assert_eq!(control.validate(), vec![ValidationError::Empty]);
+    }
+
+    #[test]
+    fn should_remove_binary_and_keep_comments() {
+        let mut control = ControlFile::parse(CONTROL).unwrap();
+        let removed = control.remove_binary("foo-doc").unwrap();
+        assert_eq!(removed.get("Architecture"), Some("all"));
+        assert!(control.binary("foo-doc").is_none());
+        assert!(control.to_string().ends_with(indoc!(
+            "
+            Testsuite: autopkgtest, autopkgtest-pkg-python
+
+            # the main package
+            Package: foo
+            Architecture: any
+            Description: foo
+            "
+        )));
+    }
+
+    #[test]
+    fn should_add_binary() {
+        let mut control = ControlFile::parse(CONTROL).unwrap();
+        let mut paragraph = Paragraph::default();
+        paragraph.set("Package", "foo-dbg");
+        paragraph.set("Architecture", "any");
+        control.add_binary(paragraph);
+        assert!(control.to_string().ends_with(indoc!(
+            "
+            Description: foo documentation
+
+            Package: foo-dbg
+            Architecture: any
+            "
+        )));
+        assert_eq!(control.binaries().count(), 3);
+    }
+
+    #[test]
+    fn should_modify_binary_in_place() {
+        let mut control = ControlFile::parse(CONTROL).unwrap();
+        control
+            .binary_mut("foo")
+            .unwrap()
+            .set("Architecture", "linux-any");
+        assert_eq!(
+            control.binary("foo").unwrap().architecture(),
+            Some("linux-any")
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Structure of extended package descriptions.
+//!
+//! The `Description` field follows the rules in Debian Policy 5.6.13: the first line is the
+//! synopsis, and every continuation line of the extended description is either a blank line
+//! (` .`), a verbatim line (indented by two or more spaces) or part of a paragraph that may be
+//! rewrapped. Because the regular parser strips the indentation of continuation lines, the
+//! functions in this module work on the raw field value instead, for example from
+//! [`lossless::Field::raw_value`](../../lossless/struct.Field.html#method.raw_value).
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::formats::description::{description_md5, Block, Description};
+//! let raw = "\
+//! package manager
+//!  oma is a package manager
+//!  with a friendly interface.
+//!  .
+//!    oma install foo";
+//! let description = Description::parse(raw);
+//! assert_eq!(description.synopsis, "package manager");
+//! assert_eq!(description.blocks, vec![
+//!     Block::Paragraph(vec!["oma is a package manager", "with a friendly interface."]),
+//!     Block::Blank,
+//!     Block::Verbatim(vec!["  oma install foo"]),
+//! ]);
+//! assert_eq!(description.to_plain_text(30), "\
+//! package manager
+//! oma is a package manager with
+//! a friendly interface.
+//!
+//!   oma install foo
+//! ");
+//! assert_eq!(description_md5(raw).len(), 32);
+//! ```
+
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::Write;
+use md5::{Digest, Md5};
+
+/// A block of lines in an extended description.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Block<'a> {
+    /// Consecutive lines of a paragraph, which may be rewrapped.
+    Paragraph(Vec<&'a str>),
+    /// Consecutive verbatim lines, which must be displayed as they are.
+    ///
+    /// Every line keeps the indentation beyond the single space that marks a continuation line.
+    Verbatim(Vec<&'a str>),
+    /// A blank line, written as ` .`.
+    Blank,
+}
+
+/// A parsed `Description` field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Description<'a> {
+    pub synopsis: &'a str,
+    /// The blocks of the extended description, in order.
+    pub blocks: Vec<Block<'a>>,
+    raw: &'a str,
+}
+
+impl<'a> Description<'a> {
+    /// Parse a raw `Description` value.
+    ///
+    /// The first line is the synopsis; all further lines must keep the indentation they have in
+    /// the control file. Lines consisting of a space, a full stop and further characters are
+    /// reserved by Policy and are skipped.
+    pub fn parse(raw: &'a str) -> Self {
+        let mut lines = raw.lines();
+        let synopsis = lines.next().unwrap_or_default().trim();
+        let mut blocks: Vec<Block<'a>> = Vec::new();
+        for line in lines {
+            let line = line
+                .strip_prefix([' ', '\t'])
+                .unwrap_or(line)
+                .trim_end_matches('\r');
+            if line.trim_end() == "." {
+                blocks.push(Block::Blank);
+            } else if line.starts_with([' ', '\t']) {
+                match blocks.last_mut() {
+                    Some(Block::Verbatim(lines)) => lines.push(line),
+                    _ => blocks.push(Block::Verbatim(vec![line])),
+                }
+            } else if line.starts_with('.') || line.trim().is_empty() {
+                continue;
+            } else {
+                match blocks.last_mut() {
+                    Some(Block::Paragraph(lines)) => lines.push(line),
+                    _ => blocks.push(Block::Paragraph(vec![line])),
+                }
+            }
+        }
+        Description {
+            synopsis,
+            blocks,
+            raw,
+        }
+    }
+
+    /// Return the raw value this description was parsed from.
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Return the `Description-md5` of this description.
+    pub fn md5(&self) -> String {
+        description_md5(self.raw)
+    }
+
+    /// Render the description as plain text.
+    ///
+    /// Paragraphs are rewrapped to lines of at most `width` characters where possible; a width
+    /// of 0 puts every paragraph on a single line. Verbatim lines are kept as they are. The
+    /// result starts with the synopsis and ends with a newline.
+    pub fn to_plain_text(&self, width: usize) -> String {
+        let mut text = String::from(self.synopsis);
+        text.push('\n');
+        for block in &self.blocks {
+            match block {
+                Block::Paragraph(lines) => {
+                    let words = lines.iter().flat_map(|line| line.split_whitespace());
+                    wrap(&mut text, words, width);
+                }
+                Block::Verbatim(lines) => {
+                    for line in lines {
+                        text.push_str(line);
+                        text.push('\n');
+                    }
+                }
+                Block::Blank => text.push('\n'),
+            }
+        }
+        text
+    }
+}
+
+/// Append the words to the text, wrapped to the given width.
+fn wrap<'w>(text: &mut String, words: impl Iterator<Item = &'w str>, width: usize) {
+    let mut line_length = 0;
+    for word in words {
+        let length = word.chars().count();
+        if line_length > 0 {
+            if width > 0 && line_length + 1 + length > width {
+                text.push('\n');
+                line_length = 0;
+            } else {
+                text.push(' ');
+                line_length += 1;
+            }
+        }
+        text.push_str(word);
+        line_length += length;
+    }
+    text.push('\n');
+}
+
+/// Compute the `Description-md5` of a raw `Description` value.
+///
+/// Like APT, the hash is computed over the raw value, including the indentation of continuation
+/// lines, followed by a newline.
+pub fn description_md5(raw: &str) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(raw.trim_end_matches('\n').as_bytes());
+    hasher.update(b"\n");
+    let mut hex = String::with_capacity(32);
+    for byte in hasher.finalize() {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lossless::Document;
+    use indoc::indoc;
+
+    const CONTROL: &str = indoc!(
+        "
+        Package: foo
+        Description: short summary
+         First paragraph,
+         continued.
+         .
+         Example:
+           $ foo --bar
+             output
+         Second paragraph.
+         .
+         .
+         .reserved line
+        "
+    );
+
+    #[test]
+    fn should_parse_blocks_from_lossless_field() {
+        let doc = Document::parse(CONTROL).unwrap();
+        let raw = doc
+            .paragraphs()
+            .next()
+            .unwrap()
+            .field("Description")
+            .unwrap()
+            .raw_value();
+        let description = Description::parse(&raw);
+        assert_eq!(description.synopsis, "short summary");
+        assert_eq!(
+            description.blocks,
+            vec![
+                Block::Paragraph(vec!["First paragraph,", "continued."]),
+                Block::Blank,
+                Block::Paragraph(vec!["Example:"]),
+                Block::Verbatim(vec!["  $ foo --bar", "    output"]),
+                Block::Paragraph(vec!["Second paragraph."]),
+                Block::Blank,
+                Block::Blank,
+            ]
+        );
+    }
+
+    #[test]
+    fn should_parse_synopsis_only() {
+        let description = Description::parse("just a synopsis");
+        assert_eq!(description.synopsis, "just a synopsis");
+        assert!(description.blocks.is_empty());
+        assert_eq!(description.to_plain_text(80), "just a synopsis\n");
+    }
+
+    #[test]
+    fn should_render_plain_text() {
+        let description = Description::parse(
+            "summary\n one two three four five six\n .\n   verbatim  line\n seven",
+        );
+        assert_eq!(
+            description.to_plain_text(13),
+            "summary\none two three\nfour five six\n\n  verbatim  line\nseven\n"
+        );
+        assert_eq!(
+            description.to_plain_text(0),
+            "summary\none two three four five six\n\n  verbatim  line\nseven\n"
+        );
+    }
+
+    #[test]
+    fn should_not_break_long_words() {
+        let description = Description::parse("s\n supercalifragilistic a");
+        assert_eq!(description.to_plain_text(5), "s\nsupercalifragilistic\na\n");
+    }
+
+    #[test]
+    fn should_compute_description_md5() {
+        // Same as `printf 'GNU Bourne Again SHell\n' | md5sum`.
+        assert_eq!(
+            description_md5("GNU Bourne Again SHell"),
+            "714091debe4a57d712a58c5a4643d255"
+        );
+        let description = Description::parse("summary\n line\n .\n more");
+        assert_eq!(description.md5(), "3a3bc35a191e2eaba41970e62006925a");
+        assert_eq!(
+            description.md5(),
+            description_md5("summary\n line\n .\n more\n")
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Typed models for specific kinds of control files.
+//!
+//! The generic parser treats every control file as a sequence of paragraphs with string fields.
+//! The modules in here build on that to interpret the fields of well-known file formats.
+
+use alloc::{borrow::Cow, string::String};
+
+pub mod apt_sources;
+pub mod autopkgtest;
+pub mod buildinfo;
+pub mod changes;
+pub mod checksums;
+pub mod copyright;
+pub mod debian_control;
+pub mod description;
+pub mod pdiff;
+pub mod preferences;
+pub mod status;
+pub mod substvars;
+pub mod translation;
+
+/// Remove the OpenPGP cleartext signature framing from a control file.
+///
+/// Files such as `.changes` and `.dsc` are usually signed, which wraps the control data in armor
+/// lines the control file parser doesn't accept. This returns the signed text with dash-escaping
+/// undone. Input that isn't signed is returned unchanged. The signature is *not* verified.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::formats::strip_signature;
+/// let signed = "\
+/// -----BEGIN PGP SIGNED MESSAGE-----
+/// Hash: SHA512
+///
+/// Source: foo
+/// - --dashes: escaped
+/// -----BEGIN PGP SIGNATURE-----
+///
+/// iQIzBAEBCgAdFiEE
+/// -----END PGP SIGNATURE-----
+/// ";
+/// assert_eq!(strip_signature(signed), "Source: foo\n--dashes: escaped\n");
+/// ```
+pub fn strip_signature(input: &str) -> Cow<'_, str> {
+    let mut lines = input
+        .split_inclusive('\n')
+        .skip_while(|line| line.trim().is_empty());
+    match lines.next() {
+        Some(line) if line.trim_end() == "-----BEGIN PGP SIGNED MESSAGE-----" => {}
+        _ => return Cow::Borrowed(input),
+    }
+
+    // Skip the armor headers, which end at the first empty line.
+    let lines = lines.skip_while(|line| !line.trim().is_empty()).skip(1);
+    let mut output = String::new();
+    for line in lines {
+        if line.trim_end() == "-----BEGIN PGP SIGNATURE-----" {
+            break;
+        }
+        output.push_str(line.strip_prefix("- ").unwrap_or(line));
+    }
+    Cow::Owned(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_return_unsigned_input_unchanged() {
+        let input = "Source: foo\n";
+        assert!(matches!(
+            strip_signature(input),
+            Cow::Borrowed("Source: foo\n")
+        ));
+    }
+
+    #[test]
+    fn should_strip_signature_without_armor_headers() {
+        let input = "-----BEGIN PGP SIGNED MESSAGE-----\n\nA: b\n-----BEGIN PGP SIGNATURE-----\n";
+        assert_eq!(strip_signature(input), "A: b\n");
+    }
+}
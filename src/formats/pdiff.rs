@@ -0,0 +1,592 @@
+//! Incremental index updates (`Packages.diff/Index` and the patches it lists).
+//!
+//! Repositories can publish the changes to an index such as `Packages` as a series of ed scripts
+//! in a `.diff` directory next to it. The `Index` file in that directory lists the SHA256 sum of
+//! the current index (`SHA256-Current`), the sums of earlier versions of the index
+//! (`SHA256-History`) and the sums of the patches that update them (`SHA256-Patches`, and
+//! `SHA256-Download` for the compressed patch files). A client looks up the sum of its local copy
+//! in the history and applies the patches from there on.
+//!
+//! In an index with `X-Patch-Precedence: merged`, every patch instead updates its version of the
+//! index to the current one directly, so only a single patch needs to be applied.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{formats::pdiff::{sha256_hex, PdiffIndex}, parse_str};
+//! let old = b"Package: a\nVersion: 1\n";
+//! let new = b"Package: a\nVersion: 2\n";
+//! let patch = b"2c\nVersion: 2\n.\n";
+//!
+//! let index = format!(
+//!     "SHA256-Current: {} 22\nSHA256-History:\n {} 22 2024-01-01-0000.00\n\
+//!      SHA256-Patches:\n {} 16 2024-01-01-0000.00\n",
+//!     sha256_hex(new),
+//!     sha256_hex(old),
+//!     sha256_hex(patch),
+//! );
+//! let paragraphs = parse_str(&index).unwrap();
+//! let index = PdiffIndex::from_paragraph(&paragraphs[0]).unwrap();
+//!
+//! let patches = index.patches_for(&sha256_hex(old)).unwrap();
+//! assert_eq!(patches.len(), 1);
+//! assert_eq!(patches[0].name, "2024-01-01-0000.00");
+//! assert_eq!(index.apply(old, &[patch]).unwrap(), new);
+//! ```
+
+use crate::{
+    formats::checksums::{columns, parse_checksums, Checksum, ChecksumError},
+    Paragraph,
+};
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::{self, Write};
+use sha2::{Digest, Sha256};
+
+/// An error when interpreting a diff index or applying patches.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PdiffError {
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// A line in one of the `SHA256-*` fields is invalid.
+    InvalidChecksum(ChecksumError),
+    /// The local file is neither the current version nor in the history.
+    UnknownVersion(String),
+    /// A history entry has no corresponding patch.
+    MissingPatch(String),
+    /// The number of patches passed in doesn't match the number of patches to apply.
+    PatchCount { expected: usize, actual: usize },
+    /// A patch doesn't have the SHA256 sum listed in the index.
+    PatchMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    /// The result of applying a patch doesn't have the SHA256 sum listed in the index.
+    ResultMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    /// A line of an ed script is invalid. Lines are numbered from 1.
+    InvalidScript { line: usize, reason: &'static str },
+}
+
+impl fmt::Display for PdiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PdiffError::MissingField(field) => write!(f, "missing required field {}", field),
+            PdiffError::InvalidChecksum(err) => write!(f, "{}", err),
+            PdiffError::UnknownVersion(hash) => {
+                write!(f, "file with SHA256 {} is not in the patch history", hash)
+            }
+            PdiffError::MissingPatch(name) => write!(f, "no patch listed for {}", name),
+            PdiffError::PatchCount { expected, actual } => {
+                write!(f, "expected {} patches, got {}", expected, actual)
+            }
+            PdiffError::PatchMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "patch {} has SHA256 {}, expected {}",
+                name, actual, expected
+            ),
+            PdiffError::ResultMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "result of patch {} has SHA256 {}, expected {}",
+                name, actual, expected
+            ),
+            PdiffError::InvalidScript { line, reason } => {
+                write!(f, "invalid ed script at line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PdiffError {}
+
+impl From<ChecksumError> for PdiffError {
+    fn from(err: ChecksumError) -> Self {
+        PdiffError::InvalidChecksum(err)
+    }
+}
+
+/// Return the SHA256 sum of the data as a lowercase hex string.
+pub fn sha256_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(data) {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+/// The SHA256 sum and size of a file.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct FileHash<'a> {
+    pub hash: &'a str,
+    pub size: u64,
+}
+
+/// The contents of a `.diff/Index` file.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct PdiffIndex<'a> {
+    /// The current version of the index.
+    pub current: FileHash<'a>,
+    /// Earlier versions of the index, from oldest to newest, named after the patch that updates
+    /// them.
+    pub history: Vec<Checksum<'a>>,
+    /// The uncompressed patches.
+    pub patches: Vec<Checksum<'a>>,
+    /// The compressed patch files as they are downloaded, such as `<name>.gz`.
+    pub download: Vec<Checksum<'a>>,
+    /// True if every patch updates its version directly to the current one.
+    pub merged: bool,
+}
+
+impl<'a> PdiffIndex<'a> {
+    /// Interpret the paragraph of a `.diff/Index` file.
+    pub fn from_paragraph(paragraph: &'a Paragraph<'_>) -> Result<Self, PdiffError> {
+        let current = paragraph
+            .get("SHA256-Current")
+            .ok_or(PdiffError::MissingField("SHA256-Current"))?;
+        let invalid = || ChecksumError {
+            line: String::from(current),
+        };
+        let [hash, size] = columns(current).ok_or_else(invalid)?;
+        let checksums = |field| paragraph.get(field).map_or(Ok(Vec::new()), parse_checksums);
+        Ok(PdiffIndex {
+            current: FileHash {
+                hash,
+                size: size.parse().map_err(|_| invalid())?,
+            },
+            history: checksums("SHA256-History")?,
+            patches: checksums("SHA256-Patches")?,
+            download: checksums("SHA256-Download")?,
+            merged: paragraph.get("X-Patch-Precedence") == Some("merged"),
+        })
+    }
+
+    /// Return the patches to apply to the file with the given SHA256 sum, along with the sum of
+    /// the result of each patch.
+    fn plan(&self, sha256: &str) -> Result<Vec<(&Checksum<'a>, &'a str)>, PdiffError> {
+        if sha256 == self.current.hash {
+            return Ok(Vec::new());
+        }
+        let start = self
+            .history
+            .iter()
+            .rposition(|entry| entry.hash == sha256)
+            .ok_or_else(|| PdiffError::UnknownVersion(String::from(sha256)))?;
+        let end = if self.merged {
+            start + 1
+        } else {
+            self.history.len()
+        };
+        (start..end)
+            .map(|index| {
+                let name = self.history[index].name;
+                let patch = self
+                    .patches
+                    .iter()
+                    .find(|patch| patch.name == name)
+                    .ok_or_else(|| PdiffError::MissingPatch(String::from(name)))?;
+                let result = match self.history.get(index + 1) {
+                    Some(next) if !self.merged => next.hash,
+                    _ => self.current.hash,
+                };
+                Ok((patch, result))
+            })
+            .collect()
+    }
+
+    /// Return the patches that update the file with the given SHA256 sum to the current version,
+    /// in the order they must be applied.
+    ///
+    /// If the file is already current, no patches are returned.
+    pub fn patches_for(&self, sha256: &str) -> Result<Vec<&Checksum<'a>>, PdiffError> {
+        Ok(self
+            .plan(sha256)?
+            .into_iter()
+            .map(|(patch, _)| patch)
+            .collect())
+    }
+
+    /// Update a file to the current version.
+    ///
+    /// `patches` are the uncompressed contents of the patches returned by
+    /// [`patches_for`](#method.patches_for) for the file, in the same order. Every patch and the
+    /// result of applying it are checked against the sums in the index.
+    pub fn apply(&self, input: &[u8], patches: &[&[u8]]) -> Result<Vec<u8>, PdiffError> {
+        let plan = self.plan(&sha256_hex(input))?;
+        if plan.len() != patches.len() {
+            return Err(PdiffError::PatchCount {
+                expected: plan.len(),
+                actual: patches.len(),
+            });
+        }
+        let mut data = input.to_vec();
+        for ((patch, result), content) in plan.into_iter().zip(patches) {
+            let actual = sha256_hex(content);
+            if actual != patch.hash {
+                return Err(PdiffError::PatchMismatch {
+                    name: String::from(patch.name),
+                    expected: String::from(patch.hash),
+                    actual,
+                });
+            }
+            data = apply_ed_script(&data, content)?;
+            let actual = sha256_hex(&data);
+            if actual != result {
+                return Err(PdiffError::ResultMismatch {
+                    name: String::from(patch.name),
+                    expected: String::from(result),
+                    actual,
+                });
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Split data into lines without their line terminators.
+fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    if data.is_empty() {
+        return Vec::new();
+    }
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    data.split(|byte| *byte == b'\n').collect()
+}
+
+/// Parse an ed line address, which is empty for the current line.
+fn parse_address(address: &[u8], current: usize) -> Option<(usize, usize)> {
+    let number = |digits: &[u8]| -> Option<usize> {
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        core::str::from_utf8(digits).ok()?.parse().ok()
+    };
+    if address.is_empty() {
+        return Some((current, current));
+    }
+    match address.iter().position(|byte| *byte == b',') {
+        Some(comma) => Some((number(&address[..comma])?, number(&address[comma + 1..])?)),
+        None => number(address).map(|line| (line, line)),
+    }
+}
+
+/// Apply an ed script as produced by `diff --ed` to the input.
+///
+/// Only the commands `diff` writes are supported: appending (`a`), changing (`c`) and deleting
+/// (`d`) lines, and `s/.//`, which `diff` uses to write lines that consist of a single dot.
+pub fn apply_ed_script(input: &[u8], script: &[u8]) -> Result<Vec<u8>, PdiffError> {
+    let mut lines = split_lines(input);
+    let mut current = lines.len();
+    let mut script = split_lines(script)
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+    while let Some((number, command)) = script.next() {
+        let invalid = |reason| PdiffError::InvalidScript {
+            line: number,
+            reason,
+        };
+        if command == b"s/.//" {
+            let line = current
+                .checked_sub(1)
+                .and_then(|index| lines.get_mut(index))
+                .filter(|line| !line.is_empty())
+                .ok_or_else(|| invalid("no line to substitute"))?;
+            *line = &line[1..];
+            continue;
+        }
+        let (action, address) = command
+            .split_last()
+            .ok_or_else(|| invalid("empty command"))?;
+        let (start, end) =
+            parse_address(address, current).ok_or_else(|| invalid("invalid address"))?;
+        let mut text = || {
+            let mut text = Vec::new();
+            loop {
+                match script.next() {
+                    Some((_, b".")) => return Ok(text),
+                    Some((_, line)) => text.push(line),
+                    None => return Err(invalid("unterminated text")),
+                }
+            }
+        };
+        match action {
+            b'a' => {
+                if start != end || start > lines.len() {
+                    return Err(invalid("address out of range"));
+                }
+                let text = text()?;
+                current = start + text.len();
+                lines.splice(start..start, text);
+            }
+            b'c' | b'd' => {
+                if start == 0 || start > end || end > lines.len() {
+                    return Err(invalid("address out of range"));
+                }
+                let text = if *action == b'c' { text()? } else { vec![] };
+                let inserted = text.len();
+                lines.splice(start - 1..end, text);
+                current = if *action == b'c' {
+                    start - 1 + inserted
+                } else {
+                    start.min(lines.len())
+                };
+            }
+            _ => return Err(invalid("unknown command")),
+        }
+    }
+
+    let mut output = Vec::with_capacity(input.len());
+    for line in lines {
+        output.extend_from_slice(line);
+        output.push(b'\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::format;
+    use indoc::indoc;
+
+    const VERSIONS: [&[u8]; 3] = [
+        b"Package: a\nVersion: 1\n\nPackage: b\nVersion: 1\n",
+        b"Package: a\nVersion: 2\n\nPackage: b\nVersion: 1\n",
+        b"Package: a\nVersion: 2\n\nPackage: c\nVersion: 1\n",
+    ];
+
+    const PATCHES: [&[u8]; 2] = [b"2c\nVersion: 2\n.\n", b"4c\nPackage: c\n.\n"];
+
+    fn index_input(patches: &[&[u8]], merged: bool) -> String {
+        let mut input = format!(
+            "SHA256-Current: {} {}\nSHA256-History:\n",
+            sha256_hex(VERSIONS[2]),
+            VERSIONS[2].len()
+        );
+        for (i, version) in VERSIONS[..2].iter().enumerate() {
+            input += &format!(" {} {} T-{}\n", sha256_hex(version), version.len(), i);
+        }
+        input += "SHA256-Patches:\n";
+        for (i, patch) in patches.iter().enumerate() {
+            input += &format!(" {} {} T-{}\n", sha256_hex(patch), patch.len(), i);
+        }
+        if merged {
+            input += "X-Patch-Precedence: merged\n";
+        }
+        input
+    }
+
+    #[test]
+    fn should_compute_sha256() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn should_apply_diff_ed_output() {
+        // Generated with `diff --ed` from "a b c d e" to "a B . x c e f".
+        let script = indoc!(
+            "
+            5a
+            f
+            .
+            4d
+            2c
+            B
+            ..
+            .
+            s/.//
+            a
+            x
+            .
+            "
+        );
+        assert_eq!(
+            apply_ed_script(b"a\nb\nc\nd\ne\n", script.as_bytes()),
+            Ok(b"a\nB\n.\nx\nc\ne\nf\n".to_vec())
+        );
+        assert_eq!(
+            apply_ed_script(b"a\nb\nc\n", b"0a\nz\n.\n1,2d\n"),
+            Ok(b"b\nc\n".to_vec())
+        );
+        assert_eq!(apply_ed_script(b"a\nb\n", b"1,2d\n"), Ok(Vec::new()));
+        assert_eq!(apply_ed_script(b"", b"0a\nz\n.\n"), Ok(b"z\n".to_vec()));
+        assert_eq!(apply_ed_script(b"a", b""), Ok(b"a\n".to_vec()));
+    }
+
+    #[test]
+    fn should_reject_invalid_scripts() {
+        for (script, line, reason) in [
+            ("3d\n", 1, "address out of range"),
+            ("0c\nx\n.\n", 1, "address out of range"),
+            ("2,1d\n", 1, "address out of range"),
+            ("1,2a\nx\n.\n", 1, "address out of range"),
+            ("1d\n1x\n", 2, "unknown command"),
+            ("1d\n\n", 2, "empty command"),
+            ("a,1d\n", 1, "invalid address"),
+            ("1a\nx\n", 1, "unterminated text"),
+            ("1d\n1d\ns/.//\n", 3, "no line to substitute"),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                apply_ed_script(b"a\nb\n", script.as_bytes()),
+                Err(PdiffError::InvalidScript {
+                    line: *line,
+                    reason
+                }),
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn should_parse_index() {
+        let paragraphs = parse_str(indoc!(
+            "
+            SHA256-Current: 01 100
+            SHA256-History:
+             02 90 2024-01-01-0000.00
+            SHA256-Patches:
+             03 10 2024-01-01-0000.00
+            SHA256-Download:
+             04 8 2024-01-01-0000.00.gz
+            X-Patch-Precedence: merged
+            "
+        ))
+        .unwrap();
+        let index = PdiffIndex::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(
+            index.current,
+            FileHash {
+                hash: "01",
+                size: 100
+            }
+        );
+        assert_eq!(index.history[0].hash, "02");
+        assert_eq!(index.patches[0].size, 10);
+        assert_eq!(index.download[0].name, "2024-01-01-0000.00.gz");
+        assert!(index.merged);
+
+        let paragraphs = parse_str("SHA256-History:\n 02 90 x\n").unwrap();
+        assert_eq!(
+            PdiffIndex::from_paragraph(&paragraphs[0]),
+            Err(PdiffError::MissingField("SHA256-Current"))
+        );
+        let paragraphs = parse_str("SHA256-Current: 01\n").unwrap();
+        assert_eq!(
+            PdiffIndex::from_paragraph(&paragraphs[0]),
+            Err(PdiffError::InvalidChecksum(ChecksumError {
+                line: "01".into()
+            }))
+        );
+    }
+
+    #[test]
+    fn should_apply_patch_chain() {
+        let patches = PATCHES;
+        let input = index_input(&patches, false);
+        let paragraphs = parse_str(&input).unwrap();
+        let index = PdiffIndex::from_paragraph(&paragraphs[0]).unwrap();
+
+        let names = |hash: &str| {
+            index
+                .patches_for(hash)
+                .unwrap()
+                .iter()
+                .map(|patch| patch.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&sha256_hex(VERSIONS[0])), vec!["T-0", "T-1"]);
+        assert_eq!(names(&sha256_hex(VERSIONS[1])), vec!["T-1"]);
+        assert!(names(&sha256_hex(VERSIONS[2])).is_empty());
+
+        assert_eq!(index.apply(VERSIONS[0], &patches), Ok(VERSIONS[2].to_vec()));
+        assert_eq!(
+            index.apply(VERSIONS[1], &patches[1..]),
+            Ok(VERSIONS[2].to_vec())
+        );
+        assert_eq!(index.apply(VERSIONS[2], &[]), Ok(VERSIONS[2].to_vec()));
+        assert_eq!(
+            index.apply(VERSIONS[0], &patches[1..]),
+            Err(PdiffError::PatchCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            index.apply(b"unknown", &[]),
+            Err(PdiffError::UnknownVersion(sha256_hex(b"unknown")))
+        );
+    }
+
+    #[test]
+    fn should_verify_every_step() {
+        let input = index_input(&PATCHES, false);
+        let paragraphs = parse_str(&input).unwrap();
+        let index = PdiffIndex::from_paragraph(&paragraphs[0]).unwrap();
+        let wrong: &[u8] = b"4c\nPackage: c\n.\n\n";
+        assert_eq!(
+            index.apply(VERSIONS[0], &[PATCHES[0], wrong]),
+            Err(PdiffError::PatchMismatch {
+                name: "T-1".into(),
+                expected: sha256_hex(PATCHES[1]),
+                actual: sha256_hex(wrong),
+            })
+        );
+
+        // The patch matches the index, but its result doesn't.
+        let wrong: &[u8] = b"4c\nPackage: d\n.\n";
+        let input = index_input(&[PATCHES[0], wrong], false);
+        let paragraphs = parse_str(&input).unwrap();
+        let index = PdiffIndex::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(
+            index.apply(VERSIONS[0], &[PATCHES[0], wrong]),
+            Err(PdiffError::ResultMismatch {
+                name: "T-1".into(),
+                expected: sha256_hex(VERSIONS[2]),
+                actual: sha256_hex(b"Package: a\nVersion: 2\n\nPackage: d\nVersion: 1\n"),
+            })
+        );
+    }
+
+    #[test]
+    fn should_apply_merged_patch() {
+        let merged: &[u8] = b"4c\nPackage: c\n.\n2c\nVersion: 2\n.\n";
+        let patches = [merged, b"4c\nPackage: c\n.\n"];
+        let input = index_input(&patches, true);
+        let paragraphs = parse_str(&input).unwrap();
+        let index = PdiffIndex::from_paragraph(&paragraphs[0]).unwrap();
+        let plan = index.patches_for(&sha256_hex(VERSIONS[0])).unwrap();
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].name, "T-0");
+        assert_eq!(
+            index.apply(VERSIONS[0], &[merged]),
+            Ok(VERSIONS[2].to_vec())
+        );
+
+        let mut input = input;
+        let last = input.rfind("T-1").unwrap();
+        input.replace_range(last..last + 3, "T-2");
+        let paragraphs = parse_str(&input).unwrap();
+        let index = PdiffIndex::from_paragraph(&paragraphs[0]).unwrap();
+        assert_eq!(
+            index.patches_for(&sha256_hex(VERSIONS[1])),
+            Err(PdiffError::MissingPatch("T-1".into()))
+        );
+    }
+}
@@ -0,0 +1,776 @@
+//! APT preferences (`/etc/apt/preferences`, `/etc/apt/preferences.d/*`).
+//!
+//! Every paragraph assigns a pin priority to the versions of some packages that match its `Pin`
+//! expression. Package names and pin values can be exact strings, fnmatch(3) glob patterns, or
+//! regular expressions enclosed in slashes. See apt_preferences(5) for details.
+//!
+//! Regular expressions require the `regex` feature, which is enabled by default. Without it, they
+//! are rejected with [`PreferencesError::RegexUnsupported`](enum.PreferencesError.html).
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{formats::preferences::{Preferences, ReleaseInfo}, parse_str};
+//! let paragraphs = parse_str("\
+//! Package: *
+//! Pin: release a=experimental
+//! Pin-Priority: 100
+//!
+//! Package: oma
+//! Pin: version 1.3*
+//! Pin-Priority: 1001
+//! ").unwrap();
+//! let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+//!
+//! let experimental = ReleaseInfo {
+//!     archive: Some("experimental"),
+//!     ..ReleaseInfo::default()
+//! };
+//! assert_eq!(preferences.priority("apt", "2.9.0", &experimental), 100);
+//! assert_eq!(preferences.priority("oma", "1.3.2", &experimental), 1001);
+//! assert_eq!(preferences.priority("apt", "2.7.0", &ReleaseInfo::default()), 500);
+//! ```
+
+use crate::Paragraph;
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// The default priority of versions in releases that are not `NotAutomatic`.
+pub const DEFAULT_PRIORITY: i32 = 500;
+
+/// The default priority of versions in `NotAutomatic` releases.
+pub const NOT_AUTOMATIC_PRIORITY: i32 = 1;
+
+/// The default priority of versions in `NotAutomatic` releases with `ButAutomaticUpgrades`.
+pub const AUTOMATIC_UPGRADES_PRIORITY: i32 = 100;
+
+/// An error when interpreting a preferences file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PreferencesError {
+    /// A required field is missing from the paragraph with the given index.
+    MissingField { index: usize, field: &'static str },
+    /// An invalid `Pin` expression.
+    InvalidPin(String),
+    /// A `Pin-Priority` that is not an integer.
+    InvalidPriority(String),
+    /// An invalid regular expression.
+    InvalidRegex(String),
+    /// A regular expression was used, but the `regex` feature is disabled.
+    RegexUnsupported(String),
+}
+
+impl fmt::Display for PreferencesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreferencesError::MissingField { index, field } => {
+                write!(f, "paragraph {} is missing required field {}", index, field)
+            }
+            PreferencesError::InvalidPin(pin) => write!(f, "invalid pin '{}'", pin),
+            PreferencesError::InvalidPriority(priority) => {
+                write!(f, "invalid pin priority '{}'", priority)
+            }
+            PreferencesError::InvalidRegex(regex) => {
+                write!(f, "invalid regular expression '{}'", regex)
+            }
+            PreferencesError::RegexUnsupported(regex) => write!(
+                f,
+                "regular expression '{}' requires the regex feature",
+                regex
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PreferencesError {}
+
+/// A pattern matching package names or pin values.
+#[derive(Debug, Clone)]
+pub enum Pattern<'a> {
+    /// `*`, which matches everything.
+    Any,
+    /// A plain string, which must match exactly.
+    Exact(&'a str),
+    /// A glob pattern containing `*`, `?` or `[`, matched like fnmatch(3).
+    Glob(&'a str),
+    /// A regular expression, written as `/regex/`. It matches anywhere in the value unless it is
+    /// anchored.
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl<'a> Pattern<'a> {
+    /// Parse a pattern.
+    pub fn parse(pattern: &'a str) -> Result<Self, PreferencesError> {
+        if pattern == "*" {
+            Ok(Pattern::Any)
+        } else if let Some(regex) = pattern
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            Self::parse_regex(regex)
+        } else if pattern.contains(['*', '?', '[']) {
+            Ok(Pattern::Glob(pattern))
+        } else {
+            Ok(Pattern::Exact(pattern))
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    fn parse_regex(regex: &str) -> Result<Self, PreferencesError> {
+        regex::Regex::new(regex)
+            .map(Pattern::Regex)
+            .map_err(|_| PreferencesError::InvalidRegex(String::from(regex)))
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn parse_regex(regex: &str) -> Result<Self, PreferencesError> {
+        Err(PreferencesError::RegexUnsupported(String::from(regex)))
+    }
+
+    /// Return true if the value matches this pattern.
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Any => true,
+            Pattern::Exact(exact) => *exact == value,
+            Pattern::Glob(glob) => fnmatch(glob, value),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// Match a value against a glob pattern like fnmatch(3) without flags, as APT does.
+///
+/// `*` and `?` also match `/`, bracket expressions such as `[a-z]` or `[!0-9]` match a single
+/// character, and a backslash escapes the following character.
+fn fnmatch(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let value = value.chars().collect::<Vec<_>>();
+    let (mut p, mut v) = (0, 0);
+    // The position after the last `*` and the value position it currently matches up to.
+    let mut star = None;
+    while v < value.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p + 1, v));
+            p += 1;
+            continue;
+        }
+        if p < pattern.len() {
+            let (len, matched) = match_char(&pattern[p..], value[v]);
+            if matched {
+                p += len;
+                v += 1;
+                continue;
+            }
+        }
+        // Let the last `*` match one more character and try again.
+        match star {
+            Some((after_star, matched_to)) => {
+                star = Some((after_star, matched_to + 1));
+                p = after_star;
+                v = matched_to + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match a character against the first element of a pattern, which isn't `*`. Returns the length
+/// of the element and whether it matched.
+fn match_char(pattern: &[char], c: char) -> (usize, bool) {
+    match pattern[0] {
+        '?' => (1, true),
+        '\\' if pattern.len() > 1 => (2, pattern[1] == c),
+        '[' => match match_bracket(&pattern[1..], c) {
+            Some((len, matched)) => (len + 1, matched),
+            // An unterminated bracket is a literal `[`.
+            None => (1, c == '['),
+        },
+        literal => (1, literal == c),
+    }
+}
+
+/// Match a character against a bracket expression, starting after the `[`. Returns the length up
+/// to and including the closing `]` and whether it matched, or `None` if there is no closing `]`.
+fn match_bracket(pattern: &[char], c: char) -> Option<(usize, bool)> {
+    let negated = matches!(pattern.first(), Some('!') | Some('^'));
+    let mut i = usize::from(negated);
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let mut start = *pattern.get(i)?;
+        if start == ']' && !first {
+            return Some((i + 1, matched != negated));
+        }
+        first = false;
+        if start == '\\' {
+            i += 1;
+            start = *pattern.get(i)?;
+        }
+        i += 1;
+        match (pattern.get(i), pattern.get(i + 1)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                matched |= start <= c && c <= end;
+                i += 2;
+            }
+            _ => matched |= start == c,
+        }
+    }
+}
+
+impl PartialEq for Pattern<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Pattern::Any, Pattern::Any) => true,
+            (Pattern::Exact(a), Pattern::Exact(b)) | (Pattern::Glob(a), Pattern::Glob(b)) => a == b,
+            #[cfg(feature = "regex")]
+            (Pattern::Regex(a), Pattern::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Pattern<'_> {}
+
+impl fmt::Display for Pattern<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Any => f.write_str("*"),
+            Pattern::Exact(value) | Pattern::Glob(value) => f.write_str(value),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => write!(f, "/{}/", regex.as_str()),
+        }
+    }
+}
+
+/// A key in a `Pin: release` expression.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ReleaseKey {
+    /// `a=`, the archive (`Suite` in the Release file).
+    Archive,
+    /// `n=`, the codename.
+    Codename,
+    /// `o=`, the origin.
+    Origin,
+    /// `l=`, the label.
+    Label,
+    /// `c=`, the component.
+    Component,
+    /// `b=`, the architecture.
+    Architecture,
+    /// `v=`, the release version.
+    Version,
+}
+
+impl ReleaseKey {
+    fn from_letter(letter: &str) -> Option<Self> {
+        match letter {
+            "a" => Some(ReleaseKey::Archive),
+            "n" => Some(ReleaseKey::Codename),
+            "o" => Some(ReleaseKey::Origin),
+            "l" => Some(ReleaseKey::Label),
+            "c" => Some(ReleaseKey::Component),
+            "b" => Some(ReleaseKey::Architecture),
+            "v" => Some(ReleaseKey::Version),
+            _ => None,
+        }
+    }
+
+    /// Return the letter used for this key in pin expressions.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReleaseKey::Archive => "a",
+            ReleaseKey::Codename => "n",
+            ReleaseKey::Origin => "o",
+            ReleaseKey::Label => "l",
+            ReleaseKey::Component => "c",
+            ReleaseKey::Architecture => "b",
+            ReleaseKey::Version => "v",
+        }
+    }
+}
+
+/// A typed `Pin` expression.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Pin<'a> {
+    /// `Pin: version <pattern>`.
+    Version(Pattern<'a>),
+    /// `Pin: release a=...,n=...`. All conditions must match.
+    Release(Vec<(ReleaseKey, Pattern<'a>)>),
+    /// `Pin: origin <host>`. An empty host matches local package files.
+    Origin(&'a str),
+}
+
+impl<'a> Pin<'a> {
+    /// Parse a `Pin` field value.
+    pub fn parse(value: &'a str) -> Result<Self, PreferencesError> {
+        let invalid = || PreferencesError::InvalidPin(String::from(value));
+        let value = value.trim();
+        let (kind, rest) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+        let rest = rest.trim();
+        match kind {
+            "version" if !rest.is_empty() => Ok(Pin::Version(Pattern::parse(rest)?)),
+            "origin" => Ok(Pin::Origin(unquote(rest))),
+            "release" => {
+                let mut conditions = Vec::new();
+                for condition in rest.split(',').map(str::trim) {
+                    let (key, pattern) = match condition.split_once('=') {
+                        Some((key, pattern)) => (
+                            ReleaseKey::from_letter(key.trim()).ok_or_else(invalid)?,
+                            pattern,
+                        ),
+                        // A bare value is the archive.
+                        None => (ReleaseKey::Archive, condition),
+                    };
+                    let pattern = unquote(pattern.trim());
+                    if pattern.is_empty() {
+                        return Err(invalid());
+                    }
+                    conditions.push((key, Pattern::parse(pattern)?));
+                }
+                Ok(Pin::Release(conditions))
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Return true if the given version from the given release matches this pin.
+    pub fn matches(&self, version: &str, release: &ReleaseInfo<'_>) -> bool {
+        match self {
+            Pin::Version(pattern) => pattern.matches(version),
+            Pin::Origin(site) => release.site.unwrap_or_default() == *site,
+            Pin::Release(conditions) => conditions.iter().all(|(key, pattern)| {
+                release
+                    .get(*key)
+                    .is_some_and(|value| pattern.matches(value))
+            }),
+        }
+    }
+}
+
+/// Remove surrounding double quotes, if any.
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// The metadata of the package index a version is available from.
+///
+/// Most fields come from the `Release` file, see
+/// [`from_paragraph`](#method.from_paragraph). The component, architecture and site depend on the
+/// individual package index and the source it was downloaded from.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct ReleaseInfo<'a> {
+    pub archive: Option<&'a str>,
+    pub codename: Option<&'a str>,
+    pub origin: Option<&'a str>,
+    pub label: Option<&'a str>,
+    pub component: Option<&'a str>,
+    pub architecture: Option<&'a str>,
+    pub version: Option<&'a str>,
+    /// The host name the index was downloaded from, or `None` for local files.
+    pub site: Option<&'a str>,
+    pub not_automatic: bool,
+    pub but_automatic_upgrades: bool,
+}
+
+impl<'a> ReleaseInfo<'a> {
+    /// Read the release metadata from a `Release` file paragraph.
+    pub fn from_paragraph(paragraph: &'a Paragraph<'_>) -> Self {
+        let yes = |name| paragraph.get(name) == Some("yes");
+        ReleaseInfo {
+            archive: paragraph.get("Suite"),
+            codename: paragraph.get("Codename"),
+            origin: paragraph.get("Origin"),
+            label: paragraph.get("Label"),
+            component: None,
+            architecture: None,
+            version: paragraph.get("Version"),
+            site: None,
+            not_automatic: yes("NotAutomatic"),
+            but_automatic_upgrades: yes("ButAutomaticUpgrades"),
+        }
+    }
+
+    /// Return the value for a `Pin: release` key.
+    pub fn get(&self, key: ReleaseKey) -> Option<&'a str> {
+        match key {
+            ReleaseKey::Archive => self.archive,
+            ReleaseKey::Codename => self.codename,
+            ReleaseKey::Origin => self.origin,
+            ReleaseKey::Label => self.label,
+            ReleaseKey::Component => self.component,
+            ReleaseKey::Architecture => self.architecture,
+            ReleaseKey::Version => self.version,
+        }
+    }
+
+    /// Return the priority of versions from this release if no preference applies.
+    pub fn default_priority(&self) -> i32 {
+        match (self.not_automatic, self.but_automatic_upgrades) {
+            (false, _) => DEFAULT_PRIORITY,
+            (true, false) => NOT_AUTOMATIC_PRIORITY,
+            (true, true) => AUTOMATIC_UPGRADES_PRIORITY,
+        }
+    }
+}
+
+/// A single paragraph in a preferences file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Preference<'a> {
+    /// The package patterns from the `Package` field.
+    pub packages: Vec<Pattern<'a>>,
+    pub pin: Pin<'a>,
+    pub priority: i32,
+    pub explanation: Option<&'a str>,
+}
+
+impl<'a> Preference<'a> {
+    /// Interpret a single preferences paragraph. `index` is only used for error reporting.
+    pub fn from_paragraph(
+        paragraph: &'a Paragraph<'_>,
+        index: usize,
+    ) -> Result<Self, PreferencesError> {
+        let required = |field| {
+            paragraph
+                .get(field)
+                .ok_or(PreferencesError::MissingField { index, field })
+        };
+        let priority = required("Pin-Priority")?;
+        Ok(Preference {
+            packages: required("Package")?
+                .split_whitespace()
+                .map(Pattern::parse)
+                .collect::<Result<_, _>>()?,
+            pin: Pin::parse(required("Pin")?)?,
+            priority: priority
+                .trim()
+                .parse()
+                .map_err(|_| PreferencesError::InvalidPriority(String::from(priority)))?,
+            explanation: paragraph.get("Explanation"),
+        })
+    }
+
+    /// Return true if this is a general preference that applies to all packages.
+    pub fn is_general(&self) -> bool {
+        self.packages.iter().all(|pattern| *pattern == Pattern::Any)
+    }
+
+    /// Return true if this preference applies to the given version of the given package.
+    pub fn matches(&self, package: &str, version: &str, release: &ReleaseInfo<'_>) -> bool {
+        self.packages.iter().any(|pattern| pattern.matches(package))
+            && self.pin.matches(version, release)
+    }
+}
+
+/// The interpreted contents of one or more preferences files.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Preferences<'a> {
+    /// The preferences in the order they were read.
+    pub entries: Vec<Preference<'a>>,
+}
+
+impl<'a> Preferences<'a> {
+    /// Interpret all paragraphs of a preferences file.
+    ///
+    /// To combine several files, extend `entries` in the order APT reads them.
+    pub fn from_paragraphs(paragraphs: &'a [Paragraph<'_>]) -> Result<Self, PreferencesError> {
+        let entries = paragraphs
+            .iter()
+            .enumerate()
+            .map(|(index, paragraph)| Preference::from_paragraph(paragraph, index))
+            .collect::<Result<_, _>>()?;
+        Ok(Preferences { entries })
+    }
+
+    /// Return the preference that applies to the given version of the given package.
+    ///
+    /// As in APT, the first matching preference for specific packages takes precedence over the
+    /// first matching general preference. Among preferences of the same kind, a later match with a
+    /// negative priority still overrides an earlier match with a non-negative one, so blacklists
+    /// can follow broader pins.
+    pub fn find(
+        &self,
+        package: &str,
+        version: &str,
+        release: &ReleaseInfo<'_>,
+    ) -> Option<&Preference<'a>> {
+        let select = |general: bool| {
+            let mut matching = self.entries.iter().filter(|entry| {
+                entry.is_general() == general && entry.matches(package, version, release)
+            });
+            let first = matching.next()?;
+            if first.priority < 0 {
+                Some(first)
+            } else {
+                matching.find(|entry| entry.priority < 0).or(Some(first))
+            }
+        };
+        select(false).or_else(|| select(true))
+    }
+
+    /// Compute the pin priority of the given version of the given package from the given
+    /// release.
+    ///
+    /// If no preference applies, this is the release's
+    /// [default priority](struct.ReleaseInfo.html#method.default_priority).
+    pub fn priority(&self, package: &str, version: &str, release: &ReleaseInfo<'_>) -> i32 {
+        self.find(package, version, release)
+            .map(|entry| entry.priority)
+            .unwrap_or_else(|| release.default_priority())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::vec;
+    use indoc::indoc;
+
+    const PREFERENCES: &str = indoc!(
+        "
+        Explanation: Keep experimental away
+        Package: *
+        Pin: release a=experimental
+        Pin-Priority: 1
+
+        Package: linux-kernel-* oma
+        Pin: release o=AOSC, n=stable, c=main
+        Pin-Priority: 900
+
+        Package: firefox
+        Pin: origin \"mirror.example.org\"
+        Pin-Priority: -1
+
+        Package: apt
+        Pin: version 2.7*
+        Pin-Priority: 1001
+        "
+    );
+
+    fn aosc_stable() -> ReleaseInfo<'static> {
+        ReleaseInfo {
+            origin: Some("AOSC"),
+            codename: Some("stable"),
+            component: Some("main"),
+            site: Some("repo.aosc.io"),
+            ..ReleaseInfo::default()
+        }
+    }
+
+    fn experimental() -> ReleaseInfo<'static> {
+        ReleaseInfo {
+            archive: Some("experimental"),
+            site: Some("mirror.example.org"),
+            not_automatic: true,
+            ..ReleaseInfo::default()
+        }
+    }
+
+    #[test]
+    fn should_parse_pins() {
+        assert_eq!(
+            Pin::parse("version 1.0*"),
+            Ok(Pin::Version(Pattern::Glob("1.0*")))
+        );
+        assert_eq!(
+            Pin::parse("release a=unstable, l=Debian"),
+            Ok(Pin::Release(vec![
+                (ReleaseKey::Archive, Pattern::Exact("unstable")),
+                (ReleaseKey::Label, Pattern::Exact("Debian")),
+            ]))
+        );
+        assert_eq!(
+            Pin::parse("release unstable"),
+            Ok(Pin::Release(vec![(
+                ReleaseKey::Archive,
+                Pattern::Exact("unstable")
+            )]))
+        );
+        assert_eq!(Pin::parse("origin \"\""), Ok(Pin::Origin("")));
+        assert_eq!(
+            Pin::parse("origin deb.example.org"),
+            Ok(Pin::Origin("deb.example.org"))
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_pins() {
+        for pin in ["", "version", "release x=1", "release a=", "codename sid"].iter() {
+            assert_eq!(
+                Pin::parse(pin),
+                Err(PreferencesError::InvalidPin(String::from(*pin)))
+            );
+        }
+    }
+
+    #[test]
+    fn should_parse_preferences() {
+        let paragraphs = parse_str(PREFERENCES).unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        assert_eq!(preferences.entries.len(), 4);
+        assert!(preferences.entries[0].is_general());
+        assert_eq!(
+            preferences.entries[0].explanation,
+            Some("Keep experimental away")
+        );
+        assert!(!preferences.entries[1].is_general());
+        assert_eq!(preferences.entries[2].priority, -1);
+    }
+
+    #[test]
+    fn should_report_missing_fields_and_invalid_priority() {
+        let paragraphs = parse_str("Package: foo\nPin: version 1\n").unwrap();
+        assert_eq!(
+            Preferences::from_paragraphs(&paragraphs),
+            Err(PreferencesError::MissingField {
+                index: 0,
+                field: "Pin-Priority"
+            })
+        );
+        let paragraphs = parse_str("Package: foo\nPin: version 1\nPin-Priority: high\n").unwrap();
+        assert_eq!(
+            Preferences::from_paragraphs(&paragraphs),
+            Err(PreferencesError::InvalidPriority(String::from("high")))
+        );
+    }
+
+    #[test]
+    fn should_match_glob_packages() {
+        let paragraphs = parse_str(PREFERENCES).unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        let release = aosc_stable();
+        assert_eq!(preferences.priority("linux-kernel-6.6", "1", &release), 900);
+        assert_eq!(preferences.priority("oma", "1", &release), 900);
+        assert_eq!(preferences.priority("liboma", "1", &release), 500);
+    }
+
+    #[test]
+    fn should_match_like_fnmatch() {
+        let matches = |pattern, value| Pattern::parse(pattern).unwrap().matches(value);
+        assert!(matches("lib[ab]*", "libapt-pkg6.0"));
+        assert!(matches("lib[ab]*", "libbz2-1.0"));
+        assert!(!matches("lib[ab]*", "libc6"));
+        assert!(matches("lib[!a-c]*", "libz"));
+        assert!(!matches("lib[!a-c]*", "libc6"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("a[", "a["));
+        assert!(matches(r"a\*", "a*"));
+        assert!(!matches(r"a\*", "ab"));
+        assert!(matches("*/main", "stable/updates/main"));
+        assert!(!matches("./oma*", "oma"));
+        assert!(matches("*a*b", "xaxab"));
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn should_match_regex_packages() {
+        let paragraphs =
+            parse_str("Package: /^oma(-.*)?$/\nPin: release n=stable\nPin-Priority: 900\n")
+                .unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        let release = aosc_stable();
+        assert_eq!(preferences.priority("oma", "1", &release), 900);
+        assert_eq!(preferences.priority("oma-refresh", "1", &release), 900);
+        assert_eq!(preferences.priority("liboma", "1", &release), 500);
+    }
+
+    #[test]
+    fn should_require_all_release_conditions() {
+        let paragraphs = parse_str(PREFERENCES).unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        let release = ReleaseInfo {
+            component: Some("contrib"),
+            ..aosc_stable()
+        };
+        assert_eq!(preferences.priority("oma", "1", &release), 500);
+    }
+
+    #[test]
+    fn should_prefer_specific_entries_over_general_entries() {
+        let paragraphs = parse_str(PREFERENCES).unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        let release = experimental();
+        assert_eq!(preferences.priority("bash", "5.2", &release), 1);
+        assert_eq!(preferences.priority("firefox", "130.0", &release), -1);
+        assert_eq!(preferences.priority("apt", "2.7.14", &release), 1001);
+        assert_eq!(preferences.priority("apt", "2.9.0", &release), 1);
+    }
+
+    #[test]
+    fn should_let_later_negative_priorities_win() {
+        let paragraphs = parse_str(indoc!(
+            "
+            Package: firefox
+            Pin: release o=AOSC
+            Pin-Priority: 500
+
+            Package: firefox
+            Pin: version 130.*
+            Pin-Priority: -1
+
+            Package: firefox
+            Pin: version 131.*
+            Pin-Priority: 990
+            "
+        ))
+        .unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        let release = aosc_stable();
+        assert_eq!(preferences.priority("firefox", "130.0", &release), -1);
+        assert_eq!(preferences.priority("firefox", "131.0", &release), 500);
+    }
+
+    #[test]
+    fn should_use_release_default_priority() {
+        let preferences = Preferences::default();
+        assert_eq!(preferences.priority("foo", "1", &aosc_stable()), 500);
+        assert_eq!(preferences.priority("foo", "1", &experimental()), 1);
+        let backports = ReleaseInfo {
+            but_automatic_upgrades: true,
+            ..experimental()
+        };
+        assert_eq!(preferences.priority("foo", "1", &backports), 100);
+    }
+
+    #[test]
+    fn should_read_release_info_from_release_file() {
+        let paragraphs = parse_str(indoc!(
+            "
+            Origin: Debian
+            Label: Debian Backports
+            Suite: bookworm-backports
+            Codename: bookworm-backports
+            NotAutomatic: yes
+            ButAutomaticUpgrades: yes
+            "
+        ))
+        .unwrap();
+        let release = ReleaseInfo::from_paragraph(&paragraphs[0]);
+        assert_eq!(release.archive, Some("bookworm-backports"));
+        assert_eq!(release.get(ReleaseKey::Label), Some("Debian Backports"));
+        assert_eq!(release.default_priority(), 100);
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn should_reject_invalid_regex() {
+        assert_eq!(
+            Pattern::parse("/foo(/"),
+            Err(PreferencesError::InvalidRegex(String::from("foo(")))
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "regex"))]
+    fn should_reject_regex_without_regex_feature() {
+        assert_eq!(
+            Pattern::parse("/^oma/"),
+            Err(PreferencesError::RegexUnsupported(String::from("^oma")))
+        );
+    }
+}
@@ -0,0 +1,653 @@
+//! The dpkg status database.
+//!
+//! dpkg records the state of every known package in `/var/lib/dpkg/status`, and the packages
+//! available for installation in `/var/lib/dpkg/available`. Both files are control files with one
+//! paragraph per package. This module interprets the dpkg-specific fields (`Status`, `Conffiles`,
+//! `Config-Version`, `Triggers-Pending` and `Triggers-Awaited`) and allows modifying and writing back
+//! the database without losing any fields it doesn't know about.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::formats::status::{Database, State};
+//! let mut db = Database::parse("
+//! Package: foo
+//! Status: install ok unpacked
+//! Architecture: amd64
+//! Version: 1.0-1
+//! ").unwrap();
+//!
+//! let entry = db.get_mut("foo", None).unwrap();
+//! let mut status = entry.status().unwrap().unwrap();
+//! status.state = State::Installed;
+//! entry.set_status(status);
+//!
+//! assert_eq!(db.to_string(), "\
+//! Package: foo
+//! Status: install ok installed
+//! Architecture: amd64
+//! Version: 1.0-1
+//! ");
+//! ```
+
+use crate::{
+    parse_str, write_paragraphs, BufParse, BufParseInput, OwnedParagraph, ReadError, SyntaxError,
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+
+/// An error in the value of a dpkg status field.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StatusError {
+    /// The `Status` field doesn't consist of a valid want, flag and state word.
+    InvalidStatus(String),
+    /// A line in the `Conffiles` field doesn't have a path and a hash.
+    InvalidConffile(String),
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusError::InvalidStatus(value) => write!(f, "invalid status '{}'", value),
+            StatusError::InvalidConffile(value) => write!(f, "invalid conffile line '{}'", value),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StatusError {}
+
+/// Generate an enum for one of the words in the `Status` field, along with its conversions.
+macro_rules! status_word {
+    ($(#[$meta: meta])* $name: ident { $($(#[$vmeta: meta])* $variant: ident => $word: expr,)+ }) => {
+        $(#[$meta])*
+        #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+        pub enum $name {
+            $($(#[$vmeta])* $variant,)+
+        }
+
+        impl $name {
+            /// Return the word used for this value in the status database.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $word,)+
+                }
+            }
+
+            fn from_word(word: &str) -> Option<Self> {
+                match word {
+                    $($word => Some($name::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+status_word! {
+    /// The selection state of a package, i.e. what the user wants to happen to it.
+    Want {
+        /// The user hasn't expressed a preference.
+        Unknown => "unknown",
+        /// The package is selected for installation.
+        Install => "install",
+        /// The package is kept at its current version.
+        Hold => "hold",
+        /// The package is selected for removal, keeping its configuration files.
+        Deinstall => "deinstall",
+        /// The package is selected for removal, including its configuration files.
+        Purge => "purge",
+    }
+}
+
+status_word! {
+    /// An error flag on a package.
+    Flag {
+        /// No error.
+        Ok => "ok",
+        /// The package is broken and needs to be reinstalled.
+        Reinstreq => "reinstreq",
+    }
+}
+
+status_word! {
+    /// The installation state of a package.
+    State {
+        /// The package is not installed.
+        NotInstalled => "not-installed",
+        /// Only the configuration files of the package are present.
+        ConfigFiles => "config-files",
+        /// The installation of the package was started but not completed.
+        HalfInstalled => "half-installed",
+        /// The package is unpacked but not configured.
+        Unpacked => "unpacked",
+        /// The package is unpacked and its configuration was started but not completed.
+        HalfConfigured => "half-configured",
+        /// The package is waiting for trigger processing by another package.
+        TriggersAwaited => "triggers-awaited",
+        /// The package has pending triggers.
+        TriggersPending => "triggers-pending",
+        /// The package is unpacked and configured.
+        Installed => "installed",
+    }
+}
+
+/// The value of a `Status` field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Status {
+    pub want: Want,
+    pub flag: Flag,
+    pub state: State,
+}
+
+impl FromStr for Status {
+    type Err = StatusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || StatusError::InvalidStatus(String::from(s));
+        let mut words = s.split_whitespace();
+        let want = words.next().and_then(Want::from_word).ok_or_else(invalid)?;
+        let flag = words.next().and_then(Flag::from_word).ok_or_else(invalid)?;
+        let state = words
+            .next()
+            .and_then(State::from_word)
+            .ok_or_else(invalid)?;
+        if words.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Status { want, flag, state })
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.want, self.flag, self.state)
+    }
+}
+
+/// A single entry in the `Conffiles` field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Conffile<'a> {
+    /// The absolute path of the configuration file.
+    pub path: &'a str,
+    /// The MD5 hash of the file as shipped by the package, or `newconffile` for a configuration
+    /// file that hasn't been installed yet.
+    pub hash: &'a str,
+    /// The configuration file is no longer shipped by the package.
+    pub obsolete: bool,
+    /// The configuration file will be removed on the next upgrade.
+    pub remove_on_upgrade: bool,
+}
+
+impl Conffile<'_> {
+    /// Return true if the configuration file hasn't been installed yet.
+    pub fn is_new_conffile(&self) -> bool {
+        self.hash == "newconffile"
+    }
+}
+
+impl fmt::Display for Conffile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.path, self.hash)?;
+        if self.obsolete {
+            f.write_str(" obsolete")?;
+        }
+        if self.remove_on_upgrade {
+            f.write_str(" remove-on-upgrade")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the lines of a `Conffiles` field.
+fn parse_conffiles(value: &str) -> Result<Vec<Conffile<'_>>, StatusError> {
+    let mut conffiles = Vec::new();
+    for line in value.lines().filter(|line| !line.trim().is_empty()) {
+        let invalid = || StatusError::InvalidConffile(String::from(line));
+        let mut words = line.split_whitespace();
+        let path = words.next().ok_or_else(invalid)?;
+        let hash = words.next().ok_or_else(invalid)?;
+        let mut conffile = Conffile {
+            path,
+            hash,
+            obsolete: false,
+            remove_on_upgrade: false,
+        };
+        for flag in words {
+            match flag {
+                "obsolete" => conffile.obsolete = true,
+                "remove-on-upgrade" => conffile.remove_on_upgrade = true,
+                _ => return Err(invalid()),
+            }
+        }
+        conffiles.push(conffile);
+    }
+    Ok(conffiles)
+}
+
+/// A single package in the status database.
+///
+/// The entry keeps the full paragraph, so any fields not covered by the accessors here are
+/// preserved in their original order when the database is written back.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Entry {
+    pub paragraph: OwnedParagraph,
+}
+
+impl From<OwnedParagraph> for Entry {
+    fn from(paragraph: OwnedParagraph) -> Self {
+        Entry { paragraph }
+    }
+}
+
+impl Entry {
+    /// Return the package name.
+    pub fn package(&self) -> Option<&str> {
+        self.paragraph.get("Package")
+    }
+
+    /// Return the package architecture.
+    pub fn architecture(&self) -> Option<&str> {
+        self.paragraph.get("Architecture")
+    }
+
+    /// Return the package version.
+    pub fn version(&self) -> Option<&str> {
+        self.paragraph.get("Version")
+    }
+
+    /// Return the parsed `Status` field, or `None` if the entry doesn't have one.
+    pub fn status(&self) -> Result<Option<Status>, StatusError> {
+        self.paragraph.get("Status").map(str::parse).transpose()
+    }
+
+    /// Replace the `Status` field.
+    pub fn set_status(&mut self, status: Status) {
+        self.paragraph.set("Status", status.to_string());
+    }
+
+    /// Return the parsed `Conffiles` field, or an empty list if the entry doesn't have one.
+    pub fn conffiles(&self) -> Result<Vec<Conffile<'_>>, StatusError> {
+        self.paragraph
+            .get("Conffiles")
+            .map_or_else(|| Ok(Vec::new()), parse_conffiles)
+    }
+
+    /// Replace the `Conffiles` field, or remove it if the list is empty.
+    pub fn set_conffiles(&mut self, conffiles: &[Conffile<'_>]) {
+        if conffiles.is_empty() {
+            self.paragraph.remove("Conffiles");
+            return;
+        }
+        let mut value = String::new();
+        for conffile in conffiles {
+            value.push('\n');
+            value.push_str(&conffile.to_string());
+        }
+        self.paragraph.set("Conffiles", value);
+    }
+
+    /// Return the version of the package that was last successfully configured.
+    pub fn config_version(&self) -> Option<&str> {
+        self.paragraph.get("Config-Version")
+    }
+
+    /// Return the names of the triggers that are pending for this package.
+    pub fn triggers_pending(&self) -> impl Iterator<Item = &str> {
+        self.word_list("Triggers-Pending")
+    }
+
+    /// Return the names of the packages whose trigger processing this package is waiting for.
+    pub fn triggers_awaited(&self) -> impl Iterator<Item = &str> {
+        self.word_list("Triggers-Awaited")
+    }
+
+    fn word_list(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.paragraph
+            .get(name)
+            .unwrap_or_default()
+            .split_whitespace()
+    }
+
+    fn matches(&self, package: &str, architecture: Option<&str>) -> bool {
+        self.package() == Some(package)
+            && (architecture.is_none() || self.architecture() == architecture)
+    }
+}
+
+/// The contents of a dpkg status or available file.
+///
+/// Entries are kept in the order they were read in, so writing an unmodified database reproduces
+/// its input, except for comments and formatting details that the parser doesn't retain.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Database {
+    pub entries: Vec<Entry>,
+}
+
+impl Database {
+    /// Parse a complete status database.
+    pub fn parse(input: &str) -> Result<Database, SyntaxError<'_>> {
+        let entries = parse_str(input)?
+            .into_iter()
+            .map(|paragraph| Entry::from(OwnedParagraph::from(paragraph)))
+            .collect();
+        Ok(Database { entries })
+    }
+
+    /// Read a status database incrementally from a buffered parser.
+    pub fn read<R: BufParseInput>(
+        parse: &mut BufParse<R>,
+    ) -> Result<Database, ReadError<R::Error>> {
+        let mut entries = Vec::new();
+        while let Some(paragraph) = parse.next_owned()? {
+            entries.push(Entry::from(paragraph));
+        }
+        Ok(Database { entries })
+    }
+
+    /// Return the entry for a package.
+    ///
+    /// If `architecture` is `None`, the first entry with a matching package name is returned
+    /// regardless of its architecture.
+    pub fn get(&self, package: &str, architecture: Option<&str>) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.matches(package, architecture))
+    }
+
+    /// Return a mutable reference to the entry for a package.
+    pub fn get_mut(&mut self, package: &str, architecture: Option<&str>) -> Option<&mut Entry> {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.matches(package, architecture))
+    }
+
+    /// Insert an entry, replacing an existing entry for the same package and architecture.
+    ///
+    /// A replaced entry keeps its position in the database. New entries are appended.
+    pub fn insert(&mut self, entry: Entry) {
+        let existing = self.entries.iter_mut().find(|existing| {
+            existing.package() == entry.package() && existing.architecture() == entry.architecture()
+        });
+        match existing {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Remove the entry for a package and return it.
+    pub fn remove(&mut self, package: &str, architecture: Option<&str>) -> Option<Entry> {
+        let pos = self
+            .entries
+            .iter()
+            .position(|entry| entry.matches(package, architecture))?;
+        Some(self.entries.remove(pos))
+    }
+
+    /// Atomically replace the file at `path` with the contents of this database.
+    ///
+    /// Like dpkg, the database is first written to a temporary file next to the target (with a
+    /// `-new` suffix), which is synced to disk and then renamed over the target. Readers will see
+    /// either the old or the new database, never a partially written one.
+    #[cfg(feature = "std")]
+    pub fn write_atomic(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let path = path.as_ref();
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push("-new");
+        let tmp_path = std::path::PathBuf::from(tmp_name);
+
+        let result = (|| {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(self.to_string().as_bytes())?;
+            file.sync_all()?;
+            std::fs::rename(&tmp_path, path)
+        })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        result
+    }
+}
+
+impl fmt::Display for Database {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_paragraphs(f, self.entries.iter().map(|entry| &entry.paragraph))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use indoc::indoc;
+
+    const STATUS: &str = indoc!(
+        "
+        Package: base-files
+        Essential: yes
+        Status: install ok installed
+        Priority: required
+        Architecture: amd64
+        Version: 12.4
+        Conffiles:
+         /etc/debian_version 4e2b12a2b9a4b2a4e8a2f3cf0e2e5e21
+         /etc/host.conf 4eb63731c9f5e30903ac4fc07a7fe3d6
+         /etc/old.conf 0c2c9a6d3d8f0e12c3b6e0e1f2b3c4d5 obsolete
+        Config-Version: 12.3
+
+        Package: man-db
+        Status: install ok triggers-pending
+        Architecture: amd64
+        Version: 2.11.2-2
+        Triggers-Pending: /usr/share/man
+        X-Custom: kept
+
+        Package: libc-bin
+        Status: deinstall reinstreq half-configured
+        Architecture: amd64
+        Version: 2.36-9
+        Triggers-Awaited: man-db dpkg
+        "
+    );
+
+    mod status {
+        use super::*;
+
+        #[test]
+        fn should_parse_status() {
+            let status: Status = "hold ok half-installed".parse().unwrap();
+            assert_eq!(
+                status,
+                Status {
+                    want: Want::Hold,
+                    flag: Flag::Ok,
+                    state: State::HalfInstalled,
+                }
+            );
+        }
+
+        #[test]
+        fn should_write_status() {
+            let status = Status {
+                want: Want::Purge,
+                flag: Flag::Reinstreq,
+                state: State::ConfigFiles,
+            };
+            assert_eq!(status.to_string(), "purge reinstreq config-files");
+        }
+
+        #[test]
+        fn should_reject_unknown_words() {
+            let result = "install ok broken".parse::<Status>();
+            assert_eq!(
+                result,
+                Err(StatusError::InvalidStatus("install ok broken".to_string()))
+            );
+        }
+
+        #[test]
+        fn should_reject_wrong_number_of_words() {
+            assert!("install ok".parse::<Status>().is_err());
+            assert!("install ok installed extra".parse::<Status>().is_err());
+        }
+    }
+
+    mod entry {
+        use super::*;
+
+        #[test]
+        fn should_parse_conffiles() {
+            let db = Database::parse(STATUS).unwrap();
+            let conffiles = db.entries[0].conffiles().unwrap();
+            assert_eq!(conffiles.len(), 3);
+            assert_eq!(
+                conffiles[2],
+                Conffile {
+                    path: "/etc/old.conf",
+                    hash: "0c2c9a6d3d8f0e12c3b6e0e1f2b3c4d5",
+                    obsolete: true,
+                    remove_on_upgrade: false,
+                }
+            );
+        }
+
+        #[test]
+        fn should_recognize_new_conffiles() {
+            let conffiles =
+                parse_conffiles("/etc/a newconffile\n/etc/b 123 remove-on-upgrade").unwrap();
+            assert!(conffiles[0].is_new_conffile());
+            assert!(!conffiles[1].is_new_conffile());
+            assert!(conffiles[1].remove_on_upgrade);
+        }
+
+        #[test]
+        fn should_reject_conffile_without_hash() {
+            let result = parse_conffiles("/etc/a");
+            assert_eq!(
+                result,
+                Err(StatusError::InvalidConffile("/etc/a".to_string()))
+            );
+        }
+
+        #[test]
+        fn should_return_triggers() {
+            let db = Database::parse(STATUS).unwrap();
+            assert_eq!(
+                db.entries[1].triggers_pending().collect::<Vec<_>>(),
+                vec!["/usr/share/man"]
+            );
+            assert_eq!(
+                db.entries[2].triggers_awaited().collect::<Vec<_>>(),
+                vec!["man-db", "dpkg"]
+            );
+            assert_eq!(db.entries[0].triggers_pending().count(), 0);
+        }
+
+        #[test]
+        fn should_return_config_version() {
+            let db = Database::parse(STATUS).unwrap();
+            assert_eq!(db.entries[0].config_version(), Some("12.3"));
+            assert_eq!(db.entries[1].config_version(), None);
+        }
+
+        #[test]
+        fn should_write_conffiles_as_multiline_field() {
+            let mut entry = Entry::default();
+            entry.set_conffiles(&[Conffile {
+                path: "/etc/a",
+                hash: "123",
+                obsolete: true,
+                remove_on_upgrade: false,
+            }]);
+            assert_eq!(
+                entry.paragraph.to_string(),
+                "Conffiles:\n /etc/a 123 obsolete\n"
+            );
+            entry.set_conffiles(&[]);
+            assert_eq!(entry.paragraph.get("Conffiles"), None);
+        }
+    }
+
+    mod database {
+        use super::*;
+
+        #[test]
+        fn should_round_trip_database() {
+            let db = Database::parse(STATUS).unwrap();
+            assert_eq!(db.to_string(), STATUS.trim_start());
+        }
+
+        #[test]
+        fn should_find_entries_by_name_and_architecture() {
+            let db = Database::parse(STATUS).unwrap();
+            assert_eq!(db.get("man-db", None).unwrap().version(), Some("2.11.2-2"));
+            assert!(db.get("man-db", Some("amd64")).is_some());
+            assert!(db.get("man-db", Some("i386")).is_none());
+            assert!(db.get("dpkg", None).is_none());
+        }
+
+        #[test]
+        fn should_keep_unknown_fields_when_updating_status() {
+            let mut db = Database::parse(STATUS).unwrap();
+            let entry = db.get_mut("man-db", None).unwrap();
+            entry.set_status(Status {
+                want: Want::Install,
+                flag: Flag::Ok,
+                state: State::Installed,
+            });
+            entry.paragraph.remove("Triggers-Pending");
+            let names = entry
+                .paragraph
+                .fields
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names,
+                vec!["Package", "Status", "Architecture", "Version", "X-Custom"]
+            );
+            assert_eq!(
+                entry.status(),
+                Ok(Some(Status {
+                    want: Want::Install,
+                    flag: Flag::Ok,
+                    state: State::Installed,
+                }))
+            );
+        }
+
+        #[test]
+        fn should_replace_entry_on_insert() {
+            let mut db = Database::parse(STATUS).unwrap();
+            let mut entry = db.get("man-db", None).unwrap().clone();
+            entry.paragraph.set("Version", "2.12.0-1");
+            db.insert(entry);
+            assert_eq!(db.entries.len(), 3);
+            assert_eq!(db.entries[1].version(), Some("2.12.0-1"));
+
+            let mut entry = Entry::default();
+            entry.paragraph.set("Package", "dpkg");
+            entry.paragraph.set("Architecture", "amd64");
+            db.insert(entry);
+            assert_eq!(db.entries.len(), 4);
+        }
+
+        #[test]
+        fn should_remove_entry() {
+            let mut db = Database::parse(STATUS).unwrap();
+            let removed = db.remove("base-files", Some("amd64")).unwrap();
+            assert_eq!(removed.version(), Some("12.4"));
+            assert_eq!(db.entries.len(), 2);
+        }
+    }
+}
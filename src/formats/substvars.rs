@@ -0,0 +1,555 @@
+//! Substitution variables (`debian/substvars`, `debian/*.substvars`).
+//!
+//! Fields in `debian/control` can refer to variables such as `${misc:Depends}` or
+//! `${binary:Version}`, which `dpkg-gencontrol` replaces when it generates the control file of a
+//! binary package. Most variables are written to substvars files by debhelper, with one
+//! `name=value` assignment per line. Assignments written as `name?=value` are optional and don't
+//! cause a warning if the variable is never used. See deb-substvars(5) for details.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{formats::substvars::{Substvars, SubstvarsWarning}, parse_str};
+//! let mut substvars = Substvars::new();
+//! substvars.read("\
+//! misc:Depends=debconf (>= 0.5)
+//! shlibs:Depends=libc6 (>= 2.34)
+//! misc:Pre-Depends?=
+//! ").unwrap();
+//! substvars.set_version_substvars("1.2-1", "1.2-1+b1");
+//!
+//! let mut paragraphs = parse_str("\
+//! Package: oma
+//! Depends: ${shlibs:Depends}, ${misc:Depends}, oma-data (= ${source:Version})
+//! Pre-Depends: ${misc:Pre-Depends}
+//! Recommends: ${oma:Recommends}
+//! ").unwrap();
+//! let warnings = substvars.substitute_paragraph(&mut paragraphs[0]).unwrap();
+//!
+//! assert_eq!(
+//!     paragraphs[0].get("Depends"),
+//!     Some("libc6 (>= 2.34), debconf (>= 0.5), oma-data (= 1.2-1)")
+//! );
+//! assert_eq!(paragraphs[0].get("Pre-Depends"), None);
+//! assert_eq!(
+//!     warnings,
+//!     vec![SubstvarsWarning::Undefined(String::from("oma:Recommends"))]
+//! );
+//! ```
+
+use crate::Paragraph;
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use core::fmt;
+
+/// The maximum number of substitutions without progress through the value, which stops infinite
+/// recursion like `dpkg-gencontrol` does.
+const MAX_SUBSTITUTIONS: usize = 50;
+
+/// Fields whose values are comma-separated lists. Empty items left by substitutions are removed
+/// from them.
+const COMMA_FIELDS: &[&str] = &[
+    "Breaks",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Built-Using",
+    "Conflicts",
+    "Depends",
+    "Enhances",
+    "Installed-Build-Depends",
+    "Pre-Depends",
+    "Provides",
+    "Recommends",
+    "Replaces",
+    "Static-Built-Using",
+    "Suggests",
+    "Testsuite-Triggers",
+    "Uploaders",
+];
+
+/// An error when reading a substvars file or substituting variables.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SubstvarsError {
+    /// A line of a substvars file is not a valid assignment. Lines are numbered from 1.
+    InvalidLine { line: usize, content: String },
+    /// Substituting variables in the value doesn't end, most likely because a variable refers to
+    /// itself.
+    TooManySubstitutions(String),
+}
+
+impl fmt::Display for SubstvarsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubstvarsError::InvalidLine { line, content } => {
+                write!(
+                    f,
+                    "bad line in substvars file at line {}: '{}'",
+                    line, content
+                )
+            }
+            SubstvarsError::TooManySubstitutions(value) => {
+                write!(f, "too many substitutions - recursive ? - in '{}'", value)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SubstvarsError {}
+
+/// A problem with the use of variables that doesn't prevent substitution.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum SubstvarsWarning {
+    /// A variable is used, but not defined. It is replaced with an empty string.
+    Undefined(String),
+    /// A variable is defined, but not used and not optional.
+    Unused(String),
+    /// A deprecated variable is used.
+    Deprecated(String),
+}
+
+impl fmt::Display for SubstvarsWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubstvarsWarning::Undefined(name) => write!(
+                f,
+                "substitution variable ${{{}}} used, but is not defined",
+                name
+            ),
+            SubstvarsWarning::Unused(name) => write!(
+                f,
+                "substitution variable ${{{}}} unused, but is defined",
+                name
+            ),
+            SubstvarsWarning::Deprecated(name) => {
+                write!(f, "deprecated substitution variable ${{{}}}", name)
+            }
+        }
+    }
+}
+
+/// A single assignment in a substvars file.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Substvar<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    /// True if the assignment uses `?=`, so the variable may remain unused.
+    pub optional: bool,
+}
+
+/// Return true if the string is a valid variable name in a substvars file.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == ':')
+}
+
+/// Parse the contents of a substvars file.
+///
+/// Empty lines and comment lines starting with `#` are skipped. Trailing whitespace is removed
+/// from values, but leading whitespace is kept.
+pub fn parse_substvars(input: &str) -> Result<Vec<Substvar<'_>>, SubstvarsError> {
+    let mut substvars = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let line = line.trim_end();
+        let invalid = || SubstvarsError::InvalidLine {
+            line: index + 1,
+            content: String::from(line),
+        };
+        let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+        let (name, optional) = match name.strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        if !is_valid_name(name) {
+            return Err(invalid());
+        }
+        substvars.push(Substvar {
+            name,
+            value,
+            optional,
+        });
+    }
+    Ok(substvars)
+}
+
+/// Find the first variable reference in the value and return its byte range and name.
+fn find_variable(value: &str) -> Option<(usize, usize, &str)> {
+    value.match_indices("${").find_map(|(start, _)| {
+        let rest = &value[start + 2..];
+        let length = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == ':'))
+            .unwrap_or(rest.len());
+        if length > 0 && rest[length..].starts_with('}') {
+            Some((start, start + length + 3, &rest[..length]))
+        } else {
+            None
+        }
+    })
+}
+
+/// A variable and its usage.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Variable {
+    value: String,
+    /// True if the variable may remain unused without a warning.
+    optional: bool,
+    deprecated: bool,
+    used: bool,
+}
+
+/// A set of substitution variables, as used by `dpkg-gencontrol`.
+///
+/// A new set contains the built-in variables `Newline`, `Space` and `Tab`. The other variables
+/// dpkg provides automatically can be added with
+/// [`set_version_substvars`](#method.set_version_substvars),
+/// [`set_arch_substvars`](#method.set_arch_substvars) and
+/// [`set_field_substvars`](#method.set_field_substvars). Variables that depend on the running
+/// dpkg or vendor, such as `dpkg:Version` or `vendor:Id`, can be set with
+/// [`set_optional`](#method.set_optional).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Substvars {
+    vars: BTreeMap<String, Variable>,
+}
+
+impl Default for Substvars {
+    fn default() -> Self {
+        let mut substvars = Substvars {
+            vars: BTreeMap::new(),
+        };
+        substvars.set_optional("Newline", "\n");
+        substvars.set_optional("Space", " ");
+        substvars.set_optional("Tab", "\t");
+        substvars
+    }
+}
+
+impl Substvars {
+    /// Create a set with the built-in variables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&mut self, name: &str, value: &str, optional: bool) {
+        self.vars.insert(
+            String::from(name),
+            Variable {
+                value: String::from(value),
+                optional,
+                deprecated: false,
+                used: false,
+            },
+        );
+    }
+
+    /// Set a variable, replacing any previous value.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.insert(name, value, false);
+    }
+
+    /// Set a variable that may remain unused without a warning, replacing any previous value.
+    pub fn set_optional(&mut self, name: &str, value: &str) {
+        self.insert(name, value, true);
+    }
+
+    /// Return the value of a variable.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(|var| var.value.as_str())
+    }
+
+    /// Read the assignments of a substvars file. Later assignments replace earlier ones.
+    pub fn read(&mut self, input: &str) -> Result<(), SubstvarsError> {
+        for substvar in parse_substvars(input)? {
+            self.insert(substvar.name, substvar.value, substvar.optional);
+        }
+        Ok(())
+    }
+
+    /// Set the version variables `binary:Version`, `source:Version`, `source:Upstream-Version`
+    /// and the deprecated `Source-Version`.
+    ///
+    /// The upstream version is the source version without its Debian revision, but with its
+    /// epoch.
+    pub fn set_version_substvars(&mut self, source_version: &str, binary_version: &str) {
+        self.set_optional("binary:Version", binary_version);
+        self.set_optional("source:Version", source_version);
+        let upstream = source_version
+            .rsplit_once('-')
+            .map_or(source_version, |(upstream, _)| upstream);
+        self.set_optional("source:Upstream-Version", upstream);
+        self.set_optional("Source-Version", binary_version);
+        if let Some(var) = self.vars.get_mut("Source-Version") {
+            var.deprecated = true;
+        }
+    }
+
+    /// Set the `Arch` variable to the host architecture.
+    pub fn set_arch_substvars(&mut self, arch: &str) {
+        self.set_optional("Arch", arch);
+    }
+
+    /// Set a variable `prefix:Field` for every field of the paragraph, such as `F:Package`.
+    pub fn set_field_substvars(&mut self, paragraph: &Paragraph<'_>, prefix: &str) {
+        for field in &paragraph.fields {
+            self.set_optional(&format!("{}:{}", prefix, field.name), &field.value);
+        }
+    }
+
+    /// Replace all variable references in a value and mark the variables as used.
+    ///
+    /// Substituted values are scanned for variables again, so variables can refer to each other.
+    /// Undefined variables are replaced with an empty string, and a warning is added for them.
+    pub fn substitute(
+        &mut self,
+        value: &str,
+        warnings: &mut Vec<SubstvarsWarning>,
+    ) -> Result<String, SubstvarsError> {
+        let mut value = String::from(value);
+        let mut count = 0;
+        let mut remaining = 0;
+        while let Some((start, end, name)) = find_variable(&value) {
+            // Only substitutions that don't make progress through the value count as recursion.
+            if value.len() - end < remaining {
+                count = 0;
+            }
+            if count >= MAX_SUBSTITUTIONS {
+                return Err(SubstvarsError::TooManySubstitutions(value));
+            }
+            remaining = value.len() - end;
+            let replacement = match self.vars.get_mut(name) {
+                Some(var) => {
+                    var.used = true;
+                    count += 1;
+                    if var.deprecated {
+                        warnings.push(SubstvarsWarning::Deprecated(String::from(name)));
+                    }
+                    var.value.clone()
+                }
+                None => {
+                    warnings.push(SubstvarsWarning::Undefined(String::from(name)));
+                    String::new()
+                }
+            };
+            value.replace_range(start..end, &replacement);
+        }
+        Ok(value)
+    }
+
+    /// Return the names of variables that are neither used nor optional.
+    pub fn unused(&self) -> impl Iterator<Item = &str> {
+        self.vars
+            .iter()
+            .filter(|(_, var)| !var.used && !var.optional)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Substitute variables in all fields of a paragraph like `dpkg-gencontrol` does.
+    ///
+    /// The fields of the paragraph are available as `F:Field` variables. Empty items are removed
+    /// from comma-separated fields such as `Depends` whose values changed, and fields that end up
+    /// empty are removed. The returned warnings include the variables that remain unused.
+    pub fn substitute_paragraph(
+        &mut self,
+        paragraph: &mut Paragraph<'_>,
+    ) -> Result<Vec<SubstvarsWarning>, SubstvarsError> {
+        self.set_field_substvars(paragraph, "F");
+        let mut warnings = Vec::new();
+        for field in &mut paragraph.fields {
+            let value = self.substitute(&field.value, &mut warnings)?;
+            if value == field.value {
+                continue;
+            }
+            let is_list = COMMA_FIELDS
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(field.name));
+            field.value = if is_list {
+                let items = value.split(',').filter(|item| !item.trim().is_empty());
+                String::from(items.collect::<Vec<_>>().join(",").trim())
+            } else {
+                String::from(value.trim())
+            };
+        }
+        paragraph.fields.retain(|field| !field.value.is_empty());
+        warnings.extend(
+            self.unused()
+                .map(|name| SubstvarsWarning::Unused(String::from(name))),
+        );
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::{string::ToString, vec};
+    use indoc::indoc;
+
+    #[test]
+    fn should_parse_substvars() {
+        let input = indoc!(
+            "
+            # generated by debhelper
+            misc:Depends=debconf (>= 0.5) | debconf-2.0
+            misc:Pre-Depends?=
+
+            shlibs:Depends= libc6 (>= 2.34)\t
+            _private=a=b
+            "
+        );
+        assert_eq!(
+            parse_substvars(input),
+            Ok(vec![
+                Substvar {
+                    name: "misc:Depends",
+                    value: "debconf (>= 0.5) | debconf-2.0",
+                    optional: false,
+                },
+                Substvar {
+                    name: "misc:Pre-Depends",
+                    value: "",
+                    optional: true,
+                },
+                Substvar {
+                    name: "shlibs:Depends",
+                    value: " libc6 (>= 2.34)",
+                    optional: false,
+                },
+                Substvar {
+                    name: "_private",
+                    value: "a=b",
+                    optional: false,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_lines() {
+        for (input, line) in [
+            ("a=1\nno assignment\n", 2),
+            ("=1", 1),
+            ("-a=1", 1),
+            ("a b=1", 1),
+            ("a??=1", 1),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                parse_substvars(input),
+                Err(SubstvarsError::InvalidLine {
+                    line: *line,
+                    content: input.lines().nth(line - 1).unwrap().to_string(),
+                }),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn should_substitute_variables() {
+        let mut substvars = Substvars::new();
+        substvars.set("a", "${b}${Space}1");
+        substvars.set("b", "${c}");
+        substvars.set("c", "2");
+        let mut warnings = Vec::new();
+        assert_eq!(
+            substvars.substitute("${a}, ${x}, $a, ${}, $${c}", &mut warnings),
+            Ok("2 1, , $a, ${}, $2".to_string())
+        );
+        assert_eq!(warnings, vec![SubstvarsWarning::Undefined("x".to_string())]);
+        assert_eq!(substvars.unused().count(), 0);
+    }
+
+    #[test]
+    fn should_detect_recursion() {
+        let mut substvars = Substvars::new();
+        substvars.set("a", "x${a}");
+        assert!(matches!(
+            substvars.substitute("${a}", &mut Vec::new()),
+            Err(SubstvarsError::TooManySubstitutions(_))
+        ));
+
+        // Many substitutions are fine if they make progress.
+        substvars.set("b", "1");
+        let value = "${b}".repeat(MAX_SUBSTITUTIONS + 1);
+        assert_eq!(
+            substvars.substitute(&value, &mut Vec::new()),
+            Ok("1".repeat(MAX_SUBSTITUTIONS + 1))
+        );
+    }
+
+    #[test]
+    fn should_set_version_substvars() {
+        let mut substvars = Substvars::new();
+        substvars.set_version_substvars("1:2.0-rc1-3", "1:2.0-rc1-3+b1");
+        assert_eq!(substvars.get("source:Upstream-Version"), Some("1:2.0-rc1"));
+        assert_eq!(substvars.get("binary:Version"), Some("1:2.0-rc1-3+b1"));
+        substvars.set_version_substvars("1.0", "1.0+b1");
+        assert_eq!(substvars.get("source:Upstream-Version"), Some("1.0"));
+
+        let mut warnings = Vec::new();
+        assert_eq!(
+            substvars.substitute("${Source-Version}", &mut warnings),
+            Ok("1.0+b1".to_string())
+        );
+        assert_eq!(
+            warnings,
+            vec![SubstvarsWarning::Deprecated("Source-Version".to_string())]
+        );
+    }
+
+    #[test]
+    fn should_substitute_paragraph() {
+        let mut paragraphs = parse_str(indoc!(
+            "
+            Package: oma
+            Architecture: ${Arch}
+            Depends: ${misc:Depends}, ${shlibs:Depends},
+             oma-data (= ${binary:Version}),
+             ${empty}
+            Suggests: ${empty}
+            Description: ${F:Package} is a package manager
+            "
+        ))
+        .unwrap();
+        let mut substvars = Substvars::new();
+        substvars
+            .read("misc:Depends=\nshlibs:Depends=libc6\nempty=\nextra=1\nopt?=2\n")
+            .unwrap();
+        substvars.set_version_substvars("1.2-1", "1.2-1");
+        substvars.set_arch_substvars("amd64");
+
+        let warnings = substvars.substitute_paragraph(&mut paragraphs[0]).unwrap();
+        assert_eq!(
+            paragraphs[0],
+            parse_str(indoc!(
+                "
+                Package: oma
+                Architecture: amd64
+                Depends: libc6,
+                 oma-data (= 1.2-1)
+                Description: oma is a package manager
+                "
+            ))
+            .unwrap()
+            .remove(0)
+        );
+        assert_eq!(
+            warnings,
+            vec![SubstvarsWarning::Unused("extra".to_string())]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "substitution variable ${extra} unused, but is defined"
+        );
+    }
+}
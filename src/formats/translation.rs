@@ -0,0 +1,282 @@
+//! Translated package descriptions (`Translation-*` indices).
+//!
+//! Repositories ship the long descriptions of packages in separate `Translation-xx` files, one per
+//! language. Every paragraph identifies a description by the package name and the MD5 sum of the
+//! English description (`Description-md5`), and gives the translation in a `Description-xx` field.
+//! `Packages` entries carry the same `Description-md5` field, which links them to their
+//! translations.
+//!
+//! # Example
+//! ```
+//! # #[cfg(feature = "std")] {
+//! # use oma_debcontrol::{formats::translation::Translations, BufParse, OwnedParagraph, parse_str};
+//! let input: &[u8] = "\
+//! Package: oma
+//! Description-md5: 9c2b2f6a6ac8a38d5b2f1e7e0a4f1f0d
+//! Description-zh_CN: 包管理器
+//! ".as_bytes();
+//! let mut parse = BufParse::new(input, 1024);
+//! let translations = Translations::read(&mut parse).unwrap();
+//!
+//! let mut entry = OwnedParagraph::from(parse_str("\
+//! Package: oma
+//! Description: package manager
+//! Description-md5: 9c2b2f6a6ac8a38d5b2f1e7e0a4f1f0d
+//! ").unwrap().remove(0));
+//! assert_eq!(translations.description(&entry), Some("包管理器"));
+//!
+//! translations.localize(&mut entry);
+//! assert_eq!(entry.get("Description-zh_CN"), Some("包管理器"));
+//! # }
+//! ```
+
+use crate::{BufParse, BufParseInput, OwnedParagraph, ReadError};
+use alloc::{collections::BTreeMap, string::String};
+
+/// The prefix of description fields, followed by a language code or `md5`.
+const DESCRIPTION_PREFIX: &str = "Description-";
+
+/// A single translated description.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Translation {
+    /// The language code from the field name, such as `de` or `zh_CN`.
+    pub language: String,
+    pub description: String,
+}
+
+impl Translation {
+    /// Return the name of the field this translation is stored in, such as `Description-de`.
+    pub fn field_name(&self) -> String {
+        let mut name = String::from(DESCRIPTION_PREFIX);
+        name.push_str(&self.language);
+        name
+    }
+}
+
+/// A lookup of translated descriptions by package name and description MD5 sum.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Translations {
+    /// Translations by package name, then by MD5 sum, so that lookups can use borrowed keys.
+    index: BTreeMap<String, BTreeMap<String, Translation>>,
+}
+
+impl Translations {
+    /// Create an empty lookup.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a `Translation-*` index from a buffered parser.
+    ///
+    /// Paragraphs without a package name, MD5 sum or translated description are skipped.
+    pub fn read<R: BufParseInput>(
+        parse: &mut BufParse<R>,
+    ) -> Result<Translations, ReadError<R::Error>> {
+        let mut translations = Translations::new();
+        translations.extend(parse)?;
+        Ok(translations)
+    }
+
+    /// Add all translations from another `Translation-*` index.
+    ///
+    /// Translations that are already present are kept, so reading the indices in order of
+    /// language preference makes the most preferred translation win.
+    pub fn extend<R: BufParseInput>(
+        &mut self,
+        parse: &mut BufParse<R>,
+    ) -> Result<(), ReadError<R::Error>> {
+        while let Some(paragraph) = parse.next_owned()? {
+            self.insert(&paragraph);
+        }
+        Ok(())
+    }
+
+    /// Add the translation from a single `Translation-*` paragraph.
+    ///
+    /// Returns false if the paragraph is incomplete or a translation for the same package and
+    /// MD5 sum is already present.
+    pub fn insert(&mut self, paragraph: &OwnedParagraph) -> bool {
+        let translation = paragraph.fields.iter().find_map(|field| {
+            let language = field.name.get(DESCRIPTION_PREFIX.len()..)?;
+            let is_translation = field.name[..DESCRIPTION_PREFIX.len()]
+                .eq_ignore_ascii_case(DESCRIPTION_PREFIX)
+                && !language.is_empty()
+                && !language.eq_ignore_ascii_case("md5");
+            if is_translation {
+                Some(Translation {
+                    language: String::from(language),
+                    description: field.value.clone(),
+                })
+            } else {
+                None
+            }
+        });
+        let (package, md5, translation) = match (
+            paragraph.get("Package"),
+            paragraph.get("Description-md5"),
+            translation,
+        ) {
+            (Some(package), Some(md5), Some(translation)) => (package, md5, translation),
+            _ => return false,
+        };
+        let md5 = md5.trim();
+        let descriptions = self.index.entry(String::from(package)).or_default();
+        if descriptions.contains_key(md5) {
+            return false;
+        }
+        descriptions.insert(String::from(md5), translation);
+        true
+    }
+
+    /// Return the translation for the given package and description MD5 sum.
+    pub fn get(&self, package: &str, md5: &str) -> Option<&Translation> {
+        self.index.get(package)?.get(md5)
+    }
+
+    /// Return the number of translations.
+    pub fn len(&self) -> usize {
+        self.index.values().map(BTreeMap::len).sum()
+    }
+
+    /// Return true if there are no translations.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Return the translation for a `Packages` entry.
+    fn translation_for(&self, entry: &OwnedParagraph) -> Option<&Translation> {
+        self.get(entry.get("Package")?, entry.get("Description-md5")?.trim())
+    }
+
+    /// Return the description of a `Packages` entry in the translated language.
+    ///
+    /// If there is no translation, the entry's English `Description` is returned instead.
+    pub fn description<'a>(&'a self, entry: &'a OwnedParagraph) -> Option<&'a str> {
+        match self.translation_for(entry) {
+            Some(translation) => Some(&translation.description),
+            None => entry.get("Description"),
+        }
+    }
+
+    /// Add the translated description to a `Packages` entry as a `Description-xx` field.
+    ///
+    /// Returns false and leaves the entry unchanged if there is no translation for it.
+    pub fn localize(&self, entry: &mut OwnedParagraph) -> bool {
+        match self.translation_for(entry) {
+            Some(translation) => {
+                entry.set(&translation.field_name(), translation.description.clone());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use indoc::indoc;
+
+    const TRANSLATION_DE: &str = indoc!(
+        "
+        Package: foo
+        Description-md5: 0123
+        Description-de: Kurz
+         Lange Beschreibung.
+
+        Package: bar
+        Description-md5: 4567
+        Description-de: Bar
+
+        Package: incomplete
+        Description-de: Nichts
+        "
+    );
+
+    const TRANSLATION_FR: &str = indoc!(
+        "
+        Package: foo
+        Description-md5: 0123
+        Description-fr: Court
+
+        Package: baz
+        Description-md5: 89ab
+        Description-fr: Baz
+        "
+    );
+
+    fn read(input: &str) -> Translations {
+        let mut translations = Translations::new();
+        for paragraph in parse_str(input).unwrap() {
+            translations.insert(&OwnedParagraph::from(paragraph));
+        }
+        translations
+    }
+
+    fn entry(input: &str) -> OwnedParagraph {
+        OwnedParagraph::from(parse_str(input).unwrap().remove(0))
+    }
+
+    #[test]
+    fn should_index_by_package_and_md5() {
+        let translations = read(TRANSLATION_DE);
+        assert_eq!(translations.len(), 2);
+        assert_eq!(
+            translations.get("foo", "0123"),
+            Some(&Translation {
+                language: String::from("de"),
+                description: String::from("Kurz\nLange Beschreibung."),
+            })
+        );
+        assert_eq!(translations.get("foo", "4567"), None);
+        assert_eq!(translations.get("incomplete", ""), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn should_build_lookup_from_stream() {
+        let mut parse = BufParse::new(TRANSLATION_DE.as_bytes(), 16);
+        let mut translations = Translations::read(&mut parse).unwrap();
+        assert_eq!(translations.len(), 2);
+        let mut parse = BufParse::new(TRANSLATION_FR.as_bytes(), 16);
+        translations.extend(&mut parse).unwrap();
+        assert_eq!(translations.len(), 3);
+    }
+
+    #[test]
+    fn should_prefer_translations_read_first() {
+        let mut translations = read(TRANSLATION_DE);
+        for paragraph in parse_str(TRANSLATION_FR).unwrap() {
+            translations.insert(&OwnedParagraph::from(paragraph));
+        }
+        assert_eq!(translations.len(), 3);
+        assert_eq!(translations.get("foo", "0123").unwrap().language, "de");
+        assert_eq!(translations.get("baz", "89ab").unwrap().language, "fr");
+    }
+
+    #[test]
+    fn should_merge_translation_into_entry() {
+        let translations = read(TRANSLATION_DE);
+        let mut foo = entry("Package: foo\nDescription: Short\nDescription-md5: 0123\n");
+        assert_eq!(
+            translations.description(&foo),
+            Some("Kurz\nLange Beschreibung.")
+        );
+        assert!(translations.localize(&mut foo));
+        assert_eq!(foo.get("Description"), Some("Short"));
+        assert_eq!(foo.get("Description-de"), Some("Kurz\nLange Beschreibung."));
+    }
+
+    #[test]
+    fn should_fall_back_to_english_description() {
+        let translations = read(TRANSLATION_DE);
+        let mut foo = entry("Package: foo\nDescription: Short\nDescription-md5: ffff\n");
+        assert_eq!(translations.description(&foo), Some("Short"));
+        assert!(!translations.localize(&mut foo));
+        assert_eq!(foo.get("Description-de"), None);
+
+        let no_md5 = entry("Package: bar\nDescription: Bar\n");
+        assert_eq!(translations.description(&no_md5), Some("Bar"));
+    }
+}
@@ -0,0 +1,740 @@
+//! Checking whether packages can be installed, like `dose-distcheck`.
+//!
+//! A package is installable if there is a set of packages that contains it, in which the
+//! `Pre-Depends` and `Depends` of every member are satisfied by other members and no two members
+//! conflict. Members conflict if one of them declares `Conflicts` or `Breaks` on the other, or if
+//! they are different versions of the same package (unless both are `Multi-Arch: same` packages of
+//! the same version for different architectures).
+//!
+//! The check first rules out packages that can't be installed regardless of conflicts because some
+//! dependency has no installable candidate, and then searches for an installation set with
+//! backtracking. For uninstallable packages, it reports an explanation consisting of the chains of
+//! dependencies that lead to a missing dependency, or of an irreducible set of conflicts together
+//! with the dependencies that pull in the conflicting packages.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{parse_str, InstallabilityChecker, OwnedParagraph, PackageSet};
+//! let paragraphs = parse_str("\
+//! Package: a
+//! Version: 1
+//! Architecture: amd64
+//! Depends: b | c
+//!
+//! Package: b
+//! Version: 1
+//! Architecture: amd64
+//! Conflicts: a
+//!
+//! Package: c
+//! Version: 1
+//! Architecture: amd64
+//! Depends: d
+//! ").unwrap().into_iter().map(OwnedParagraph::from).collect::<Vec<_>>();
+//! let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+//! let checker = InstallabilityChecker::new(&set).unwrap();
+//!
+//! let broken = checker.check_all();
+//! assert_eq!(broken.len(), 2);
+//! assert_eq!(broken[0].to_string(), "\
+//! a 1 (amd64) is not installable:
+//!   a 1 (amd64) depends on b | c
+//!   nothing satisfies d of c 1 (amd64)
+//!   b 1 (amd64) conflicts with a 1 (amd64) through a
+//! ");
+//! ```
+
+use crate::{MultiArch, PackageInfo, PackageSet, Relation, VersionError};
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+/// A single reason in the explanation of why a package is not installable.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Reason<'p> {
+    /// The package depends on one of the given alternatives, which leads to the problem.
+    Depends {
+        package: &'p PackageInfo<'p>,
+        dependency: &'p [Relation<'p>],
+    },
+    /// No package satisfies a dependency of the package.
+    Missing {
+        package: &'p PackageInfo<'p>,
+        dependency: &'p [Relation<'p>],
+    },
+    /// Two packages can't be installed together. `relation` is the `Conflicts` or `Breaks` entry
+    /// of `package` that matches `other`, or `None` if the packages are different versions of the
+    /// same package.
+    Conflict {
+        package: &'p PackageInfo<'p>,
+        other: &'p PackageInfo<'p>,
+        relation: Option<&'p Relation<'p>>,
+    },
+}
+
+/// Write alternatives separated by ` | `.
+fn write_alternatives(f: &mut fmt::Formatter<'_>, alternatives: &[Relation<'_>]) -> fmt::Result {
+    for (i, relation) in alternatives.iter().enumerate() {
+        if i > 0 {
+            f.write_str(" | ")?;
+        }
+        write!(f, "{}", relation)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Reason<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Depends {
+                package,
+                dependency,
+            } => {
+                write!(f, "{} depends on ", package)?;
+                write_alternatives(f, dependency)
+            }
+            Reason::Missing {
+                package,
+                dependency,
+            } => {
+                f.write_str("nothing satisfies ")?;
+                write_alternatives(f, dependency)?;
+                write!(f, " of {}", package)
+            }
+            Reason::Conflict {
+                package,
+                other,
+                relation: Some(relation),
+            } => write!(
+                f,
+                "{} conflicts with {} through {}",
+                package, other, relation
+            ),
+            Reason::Conflict {
+                package,
+                other,
+                relation: None,
+            } => write!(
+                f,
+                "{} and {} are versions of the same package",
+                package, other
+            ),
+        }
+    }
+}
+
+/// The explanation of why a package is not installable.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Explanation<'p> {
+    pub package: &'p PackageInfo<'p>,
+    pub reasons: Vec<Reason<'p>>,
+}
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} is not installable:", self.package)?;
+        for reason in &self.reasons {
+            writeln!(f, "  {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// A dependency of a package: the alternatives and the indices of the packages satisfying them.
+struct Clause<'p> {
+    alternatives: &'p [Relation<'p>],
+    candidates: Vec<usize>,
+}
+
+/// A pair of conflicting packages, with the smaller index first.
+type Pair = (usize, usize);
+
+/// Return the pair of two package indices in canonical order.
+fn pair(a: usize, b: usize) -> Pair {
+    (a.min(b), a.max(b))
+}
+
+/// Checks the installability of all packages in a [`PackageSet`](struct.PackageSet.html).
+pub struct InstallabilityChecker<'p> {
+    packages: &'p [PackageInfo<'p>],
+    /// The `Pre-Depends` and `Depends` of every package.
+    clauses: Vec<Vec<Clause<'p>>>,
+    /// The packages every package conflicts with.
+    conflicts: Vec<BTreeSet<usize>>,
+    /// The package and relation declaring each conflict.
+    conflict_sources: BTreeMap<Pair, (usize, Option<&'p Relation<'p>>)>,
+    /// For packages that are not installable regardless of conflicts, the index of a clause none
+    /// of whose candidates is installable.
+    missing: Vec<Option<usize>>,
+}
+
+impl<'p> InstallabilityChecker<'p> {
+    /// Prepare the dependencies and conflicts of all packages in the set.
+    ///
+    /// Fails if a version in a relationship field is invalid.
+    pub fn new(set: &'p PackageSet<'p>) -> Result<Self, VersionError> {
+        let packages = set.packages();
+        let mut clauses = Vec::with_capacity(packages.len());
+        let mut conflicts = vec![BTreeSet::new(); packages.len()];
+        let mut conflict_sources = BTreeMap::new();
+        let mut add_conflict = |a: usize, b: usize, relation| {
+            if a != b {
+                conflicts[a].insert(b);
+                conflicts[b].insert(a);
+                conflict_sources.entry(pair(a, b)).or_insert((a, relation));
+            }
+        };
+
+        for (index, package) in packages.iter().enumerate() {
+            let mut package_clauses = Vec::new();
+            for alternatives in package.pre_depends.iter().chain(&package.depends) {
+                let mut candidates = Vec::new();
+                for relation in alternatives {
+                    for (candidate, _, mismatch) in
+                        set.candidates(relation, package.architecture, false)?
+                    {
+                        if mismatch.is_none() && !candidates.contains(&candidate) {
+                            candidates.push(candidate);
+                        }
+                    }
+                }
+                package_clauses.push(Clause {
+                    alternatives,
+                    candidates,
+                });
+            }
+            clauses.push(package_clauses);
+
+            for relation in package.conflicts.iter().chain(&package.breaks).flatten() {
+                for (other, _, mismatch) in set.candidates(relation, package.architecture, true)? {
+                    if mismatch.is_none() {
+                        add_conflict(index, other, Some(relation));
+                    }
+                }
+            }
+            for other_index in set.indices(package.name) {
+                let other = &packages[*other_index];
+                let coinstallable = package.multi_arch == MultiArch::Same
+                    && other.multi_arch == MultiArch::Same
+                    && package.architecture != other.architecture
+                    && package.version == other.version;
+                if !coinstallable {
+                    add_conflict(index, *other_index, None);
+                }
+            }
+        }
+
+        let mut checker = InstallabilityChecker {
+            packages,
+            clauses,
+            conflicts,
+            conflict_sources,
+            missing: Vec::new(),
+        };
+        checker.find_missing();
+        Ok(checker)
+    }
+
+    /// Find the packages that can't be installed regardless of conflicts.
+    fn find_missing(&mut self) {
+        self.missing = vec![None; self.packages.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..self.packages.len() {
+                if self.missing[index].is_some() {
+                    continue;
+                }
+                let broken = self.clauses[index].iter().position(|clause| {
+                    clause
+                        .candidates
+                        .iter()
+                        .all(|candidate| self.missing[*candidate].is_some())
+                });
+                if broken.is_some() {
+                    self.missing[index] = broken;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Return the packages the checker was created for.
+    pub fn packages(&self) -> &'p [PackageInfo<'p>] {
+        self.packages
+    }
+
+    /// Search for an installation set containing the package, taking only the given conflicts
+    /// into account. Conflicts that prevented adding a package are recorded in `encountered`.
+    fn search(
+        &self,
+        index: usize,
+        active: Option<&BTreeSet<Pair>>,
+        encountered: &mut BTreeSet<Pair>,
+    ) -> Option<Vec<usize>> {
+        let mut search = Search {
+            checker: self,
+            active,
+            encountered,
+            installed: vec![index],
+            members: BTreeSet::new(),
+        };
+        search.members.insert(index);
+        if search.extend(0, 0) {
+            Some(search.installed)
+        } else {
+            None
+        }
+    }
+
+    /// Return a set of packages that can be installed together with the package, including the
+    /// package itself, or `None` if the package is not installable.
+    pub fn installation_set(&self, index: usize) -> Option<Vec<&'p PackageInfo<'p>>> {
+        if self.missing[index].is_some() {
+            return None;
+        }
+        let installed = self.search(index, None, &mut BTreeSet::new())?;
+        Some(installed.into_iter().map(|i| &self.packages[i]).collect())
+    }
+
+    /// Check whether the package with the given index in the package set is installable, and
+    /// explain why if it isn't.
+    pub fn check(&self, index: usize) -> Option<Explanation<'p>> {
+        let mut reasons = Vec::new();
+        if self.missing[index].is_some() {
+            self.explain_missing(index, &mut BTreeSet::new(), &mut reasons);
+        } else {
+            let mut encountered = BTreeSet::new();
+            if self.search(index, None, &mut encountered).is_some() {
+                return None;
+            }
+            self.explain_conflicts(index, encountered, &mut reasons);
+        }
+        Some(Explanation {
+            package: &self.packages[index],
+            reasons,
+        })
+    }
+
+    /// Check all packages and return the explanations for those that are not installable.
+    pub fn check_all(&self) -> Vec<Explanation<'p>> {
+        (0..self.packages.len())
+            .filter_map(|index| self.check(index))
+            .collect()
+    }
+
+    /// Explain why a package is not installable regardless of conflicts, by following its broken
+    /// dependency to missing packages.
+    fn explain_missing(
+        &self,
+        index: usize,
+        visited: &mut BTreeSet<usize>,
+        reasons: &mut Vec<Reason<'p>>,
+    ) {
+        if !visited.insert(index) {
+            return;
+        }
+        let clause = match self.missing[index] {
+            Some(clause) => &self.clauses[index][clause],
+            None => return,
+        };
+        let package = &self.packages[index];
+        if clause.candidates.is_empty() {
+            reasons.push(Reason::Missing {
+                package,
+                dependency: clause.alternatives,
+            });
+            return;
+        }
+        reasons.push(Reason::Depends {
+            package,
+            dependency: clause.alternatives,
+        });
+        for candidate in &clause.candidates {
+            self.explain_missing(*candidate, visited, reasons);
+        }
+    }
+
+    /// Explain why a package is not installable because of conflicts.
+    ///
+    /// The conflicts encountered during the failed search are reduced to a set from which no
+    /// conflict can be removed without making the package installable. The explanation consists of
+    /// these conflicts, the dependencies leading to the conflicting packages, and the dependencies
+    /// whose candidates are all uninstallable regardless of conflicts.
+    fn explain_conflicts(
+        &self,
+        index: usize,
+        encountered: BTreeSet<Pair>,
+        reasons: &mut Vec<Reason<'p>>,
+    ) {
+        let mut core = encountered.clone();
+        for conflict in &encountered {
+            core.remove(conflict);
+            if self
+                .search(index, Some(&core), &mut BTreeSet::new())
+                .is_some()
+            {
+                core.insert(*conflict);
+            }
+        }
+
+        let involved = core.iter().flat_map(|(a, b)| [*a, *b]).collect::<Vec<_>>();
+        let mut edges = BTreeSet::new();
+        for target in &involved {
+            self.dependency_path(index, *target, &mut edges);
+        }
+        let mut visited = involved.iter().copied().collect();
+        let mut depends = edges.into_iter().collect::<Vec<_>>();
+        depends.sort_by_key(|(package, clause)| (*package != index, *package, *clause));
+        for (package, clause) in depends {
+            reasons.push(Reason::Depends {
+                package: &self.packages[package],
+                dependency: self.clauses[package][clause].alternatives,
+            });
+            for candidate in &self.clauses[package][clause].candidates {
+                self.explain_missing(*candidate, &mut visited, reasons);
+            }
+        }
+        for (a, b) in core {
+            let (source, relation) = self.conflict_sources[&(a, b)];
+            let other = if source == a { b } else { a };
+            reasons.push(Reason::Conflict {
+                package: &self.packages[source],
+                other: &self.packages[other],
+                relation,
+            });
+        }
+        reasons.dedup();
+    }
+
+    /// Add the dependencies on a shortest path from one package to another to `edges`, as pairs of
+    /// package and clause index.
+    fn dependency_path(&self, from: usize, to: usize, edges: &mut BTreeSet<(usize, usize)>) {
+        let mut previous = BTreeMap::new();
+        let mut queue = VecDeque::from([from]);
+        let mut seen = BTreeSet::from([from]);
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                break;
+            }
+            for (clause_index, clause) in self.clauses[current].iter().enumerate() {
+                for candidate in &clause.candidates {
+                    if seen.insert(*candidate) {
+                        previous.insert(*candidate, (current, clause_index));
+                        queue.push_back(*candidate);
+                    }
+                }
+            }
+        }
+        let mut current = to;
+        while let Some((package, clause)) = previous.get(&current) {
+            edges.insert((*package, *clause));
+            current = *package;
+        }
+    }
+}
+
+/// The state of a search for an installation set.
+struct Search<'c, 'p> {
+    checker: &'c InstallabilityChecker<'p>,
+    /// The conflicts to take into account, or `None` for all of them.
+    active: Option<&'c BTreeSet<Pair>>,
+    encountered: &'c mut BTreeSet<Pair>,
+    installed: Vec<usize>,
+    members: BTreeSet<usize>,
+}
+
+impl Search<'_, '_> {
+    /// Return a member of the installation set that conflicts with the package.
+    fn conflicting(&self, index: usize) -> Option<usize> {
+        let conflicts = &self.checker.conflicts[index];
+        self.installed.iter().copied().find(|member| {
+            conflicts.contains(member)
+                && match self.active {
+                    Some(active) => active.contains(&pair(index, *member)),
+                    None => true,
+                }
+        })
+    }
+
+    /// Satisfy all dependencies of the installation set, starting at the given clause of the
+    /// given member. Dependencies before it are already satisfied, since adding packages never
+    /// breaks a dependency.
+    fn extend(&mut self, member: usize, clause: usize) -> bool {
+        let checker = self.checker;
+        let (mut member, mut clause) = (member, clause);
+        while member < self.installed.len() {
+            let clauses = &checker.clauses[self.installed[member]];
+            while clause < clauses.len() {
+                let candidates = &clauses[clause].candidates;
+                if candidates
+                    .iter()
+                    .any(|candidate| self.members.contains(candidate))
+                {
+                    clause += 1;
+                    continue;
+                }
+                for candidate in candidates {
+                    if checker.missing[*candidate].is_some() {
+                        continue;
+                    }
+                    if let Some(other) = self.conflicting(*candidate) {
+                        self.encountered.insert(pair(*candidate, other));
+                        continue;
+                    }
+                    self.installed.push(*candidate);
+                    self.members.insert(*candidate);
+                    if self.extend(member, clause + 1) {
+                        return true;
+                    }
+                    self.installed.pop();
+                    self.members.remove(candidate);
+                }
+                return false;
+            }
+            member += 1;
+            clause = 0;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, OwnedParagraph};
+    use alloc::{string::ToString, vec::Vec};
+    use indoc::indoc;
+
+    fn paragraphs(input: &str) -> Vec<OwnedParagraph> {
+        parse_str(input)
+            .unwrap()
+            .into_iter()
+            .map(OwnedParagraph::from)
+            .collect()
+    }
+
+    fn names<'p>(packages: impl IntoIterator<Item = &'p PackageInfo<'p>>) -> Vec<&'p str> {
+        packages.into_iter().map(|package| package.name).collect()
+    }
+
+    #[test]
+    fn should_backtrack_over_alternatives() {
+        let paragraphs = paragraphs(indoc!(
+            "
+            Package: a
+            Version: 1
+            Architecture: amd64
+            Depends: b | c, d
+
+            Package: b
+            Version: 1
+            Architecture: amd64
+            Depends: e
+
+            Package: c
+            Version: 1
+            Architecture: all
+
+            Package: d
+            Version: 1
+            Architecture: amd64
+            Pre-Depends: c
+
+            Package: e
+            Version: 1
+            Architecture: amd64
+            Breaks: d
+            "
+        ));
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let checker = InstallabilityChecker::new(&set).unwrap();
+        assert_eq!(
+            names(checker.installation_set(0).unwrap()),
+            vec!["a", "c", "d"]
+        );
+        assert!(checker.check_all().is_empty());
+    }
+
+    #[test]
+    fn should_explain_missing_dependencies() {
+        let paragraphs = paragraphs(indoc!(
+            "
+            Package: a
+            Version: 1
+            Architecture: amd64
+            Depends: b | c (>= 2)
+
+            Package: b
+            Version: 1
+            Architecture: amd64
+            Depends: d
+
+            Package: c
+            Version: 1
+            Architecture: amd64
+            "
+        ));
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let checker = InstallabilityChecker::new(&set).unwrap();
+        let broken = checker.check_all();
+        assert_eq!(names(broken.iter().map(|e| e.package)), vec!["a", "b"]);
+        assert_eq!(
+            broken[0].to_string(),
+            indoc!(
+                "
+                a 1 (amd64) is not installable:
+                  a 1 (amd64) depends on b | c (>= 2)
+                  nothing satisfies d of b 1 (amd64)
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn should_report_irreducible_conflicts() {
+        let paragraphs = paragraphs(indoc!(
+            "
+            Package: a
+            Version: 1
+            Architecture: amd64
+            Depends: p | q, r
+
+            Package: p
+            Version: 1
+            Architecture: amd64
+            Conflicts: r
+
+            Package: q
+            Version: 1
+            Architecture: amd64
+            Depends: s
+
+            Package: r
+            Version: 1
+            Architecture: amd64
+
+            Package: s
+            Version: 1
+            Architecture: amd64
+            Breaks: r (<< 2)
+            "
+        ));
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let checker = InstallabilityChecker::new(&set).unwrap();
+        let explanation = checker.check(0).unwrap();
+        assert_eq!(
+            explanation.to_string(),
+            indoc!(
+                "
+                a 1 (amd64) is not installable:
+                  a 1 (amd64) depends on p | q
+                  a 1 (amd64) depends on r
+                  q 1 (amd64) depends on s
+                  p 1 (amd64) conflicts with r 1 (amd64) through r
+                  s 1 (amd64) conflicts with r 1 (amd64) through r (<< 2)
+                "
+            )
+        );
+        assert_eq!(checker.check(1), None);
+    }
+
+    #[test]
+    fn should_apply_unqualified_conflicts_to_all_architectures() {
+        let paragraphs = paragraphs(indoc!(
+            "
+            Package: app
+            Version: 1
+            Architecture: i386
+            Depends: a, b:amd64
+
+            Package: a
+            Version: 1
+            Architecture: i386
+            Conflicts: b
+
+            Package: b
+            Version: 1
+            Architecture: amd64
+            "
+        ));
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let checker = InstallabilityChecker::new(&set).unwrap();
+        let explanation = checker.check(0).unwrap();
+        assert_eq!(
+            explanation.reasons.last().unwrap().to_string(),
+            "a 1 (i386) conflicts with b 1 (amd64) through b"
+        );
+        assert_eq!(checker.check(1), None);
+    }
+
+    #[test]
+    fn should_not_install_two_versions_of_a_package() {
+        let paragraphs = paragraphs(indoc!(
+            "
+            Package: a
+            Version: 1
+            Architecture: amd64
+            Depends: x (= 1), y
+
+            Package: y
+            Version: 1
+            Architecture: amd64
+            Depends: x (>= 2)
+
+            Package: x
+            Version: 1
+            Architecture: amd64
+
+            Package: x
+            Version: 2
+            Architecture: amd64
+            "
+        ));
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let checker = InstallabilityChecker::new(&set).unwrap();
+        let explanation = checker.check(0).unwrap();
+        assert_eq!(
+            explanation.reasons.last().unwrap().to_string(),
+            "x 1 (amd64) and x 2 (amd64) are versions of the same package"
+        );
+    }
+
+    #[test]
+    fn should_coinstall_multi_arch_same_packages() {
+        let paragraphs = paragraphs(indoc!(
+            "
+            Package: app
+            Version: 1
+            Architecture: i386
+            Depends: libc6:amd64, libc6
+
+            Package: libc6
+            Version: 2.36-9
+            Architecture: amd64
+            Multi-Arch: same
+
+            Package: libc6
+            Version: 2.36-9
+            Architecture: i386
+            Multi-Arch: same
+
+            Package: tool
+            Version: 1
+            Architecture: i386
+            Depends: tool:amd64
+
+            Package: tool
+            Version: 1
+            Architecture: amd64
+            "
+        ));
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let checker = InstallabilityChecker::new(&set).unwrap();
+        assert_eq!(checker.check(0), None);
+        assert!(checker.check(3).is_some());
+    }
+}
@@ -0,0 +1,241 @@
+//! Classification of fields into simple, folded and multiline fields.
+
+use crate::{Field, OwnedField};
+use alloc::{borrow::Cow, collections::BTreeMap, string::String};
+
+/// The kind of a field, which determines how its continuation lines are interpreted.
+///
+/// See [Debian Policy 5.1](https://www.debian.org/doc/debian-policy/ch-controlfields.html#syntax-of-control-files).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FieldKind {
+    /// A field whose value is a single line.
+    Simple,
+    /// A field whose value may span several lines, but whose line breaks carry no meaning, such
+    /// as `Depends` or `Uploaders`.
+    Folded,
+    /// A field whose lines each carry meaning, such as `Description` or `Checksums-Sha256`.
+    Multiline,
+}
+
+/// Fields with known kinds in `debian/control`, `.dsc`, `.changes`, `.buildinfo`, `Packages`,
+/// `Release` and dpkg status files. Fields that aren't listed here are simple.
+const STANDARD_FIELDS: &[(&str, FieldKind)] = &[
+    ("Binary", FieldKind::Folded),
+    ("Breaks", FieldKind::Folded),
+    ("Build-Conflicts", FieldKind::Folded),
+    ("Build-Conflicts-Arch", FieldKind::Folded),
+    ("Build-Conflicts-Indep", FieldKind::Folded),
+    ("Build-Depends", FieldKind::Folded),
+    ("Build-Depends-Arch", FieldKind::Folded),
+    ("Build-Depends-Indep", FieldKind::Folded),
+    ("Built-Using", FieldKind::Folded),
+    ("Changes", FieldKind::Multiline),
+    ("Checksums-Md5", FieldKind::Multiline),
+    ("Checksums-Sha1", FieldKind::Multiline),
+    ("Checksums-Sha256", FieldKind::Multiline),
+    ("Checksums-Sha512", FieldKind::Multiline),
+    ("Conffiles", FieldKind::Multiline),
+    ("Conflicts", FieldKind::Folded),
+    ("Depends", FieldKind::Folded),
+    ("Description", FieldKind::Multiline),
+    ("Enhances", FieldKind::Folded),
+    ("Environment", FieldKind::Multiline),
+    ("Files", FieldKind::Multiline),
+    ("Installed-Build-Depends", FieldKind::Folded),
+    ("MD5Sum", FieldKind::Multiline),
+    ("Package-List", FieldKind::Multiline),
+    ("Pre-Depends", FieldKind::Folded),
+    ("Provides", FieldKind::Folded),
+    ("Recommends", FieldKind::Folded),
+    ("Replaces", FieldKind::Folded),
+    ("SHA1", FieldKind::Multiline),
+    ("SHA256", FieldKind::Multiline),
+    ("SHA512", FieldKind::Multiline),
+    ("Static-Built-Using", FieldKind::Folded),
+    ("Suggests", FieldKind::Folded),
+    ("Testsuite-Triggers", FieldKind::Folded),
+    ("Uploaders", FieldKind::Folded),
+];
+
+/// Return the kind of a standard field, if it is known.
+fn standard_kind(name: &str) -> Option<FieldKind> {
+    STANDARD_FIELDS
+        .iter()
+        .find(|(field, _)| field.eq_ignore_ascii_case(name))
+        .map(|(_, kind)| *kind)
+}
+
+/// A registry of field names and their kinds.
+///
+/// A new registry knows the standard fields defined by Debian Policy and dpkg. Custom fields can
+/// be registered on top of them, and registrations override the standard kinds. Field names are
+/// compared case-insensitively. Unknown fields are treated as simple fields.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{FieldKind, FieldRegistry};
+/// let mut registry = FieldRegistry::new();
+/// assert_eq!(registry.kind("depends"), FieldKind::Folded);
+/// assert_eq!(registry.kind("X-Oma-Topics"), FieldKind::Simple);
+///
+/// registry.register("X-Oma-Topics", FieldKind::Folded);
+/// assert_eq!(registry.kind("x-oma-topics"), FieldKind::Folded);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct FieldRegistry {
+    /// Custom registrations, keyed by lowercase field name.
+    custom: BTreeMap<String, FieldKind>,
+}
+
+impl FieldRegistry {
+    /// Create a registry that knows the standard fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the kind of a field, replacing any previous or standard kind.
+    pub fn register(&mut self, name: &str, kind: FieldKind) {
+        self.custom.insert(name.to_ascii_lowercase(), kind);
+    }
+
+    /// Return the registered kind of a field, or `None` if the field is unknown.
+    pub fn get(&self, name: &str) -> Option<FieldKind> {
+        self.custom
+            .get(&name.to_ascii_lowercase())
+            .copied()
+            .or_else(|| standard_kind(name))
+    }
+
+    /// Return the kind of a field, treating unknown fields as simple.
+    pub fn kind(&self, name: &str) -> FieldKind {
+        self.get(name).unwrap_or(FieldKind::Simple)
+    }
+}
+
+/// Join the lines of a value with single spaces, dropping empty lines.
+fn fold(value: &str) -> Cow<'_, str> {
+    if !value.contains('\n') {
+        return Cow::Borrowed(value.trim());
+    }
+    let mut folded = String::new();
+    for line in value
+        .split('\n')
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if !folded.is_empty() {
+            folded.push(' ');
+        }
+        folded.push_str(line);
+    }
+    Cow::Owned(folded)
+}
+
+/// Interpret a value according to the given kind.
+fn value_for_kind(value: &str, kind: FieldKind) -> Cow<'_, str> {
+    match kind {
+        FieldKind::Folded => fold(value),
+        FieldKind::Simple | FieldKind::Multiline => Cow::Borrowed(value),
+    }
+}
+
+macro_rules! field_kind_methods {
+    ($type: ty) => {
+        impl $type {
+            /// Return the kind of this field according to the standard fields.
+            ///
+            /// Use [`FieldRegistry`](struct.FieldRegistry.html) to classify custom fields.
+            pub fn kind(&self) -> FieldKind {
+                standard_kind(&self.name).unwrap_or(FieldKind::Simple)
+            }
+
+            /// Return the value with all lines joined by single spaces, as for folded fields.
+            pub fn folded_value(&self) -> Cow<'_, str> {
+                fold(&self.value)
+            }
+
+            /// Return the lines of the value.
+            ///
+            /// For multiline fields such as `Checksums-Sha256`, the first line is often empty.
+            pub fn lines(&self) -> core::str::Split<'_, char> {
+                self.value.split('\n')
+            }
+
+            /// Return the value interpreted according to the kind the registry assigns to this
+            /// field: folded fields are folded, while the lines of simple and multiline fields are
+            /// kept.
+            pub fn value_in(&self, registry: &FieldRegistry) -> Cow<'_, str> {
+                value_for_kind(&self.value, registry.kind(&self.name))
+            }
+        }
+    };
+}
+
+field_kind_methods!(Field<'_>);
+field_kind_methods!(OwnedField);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, tests::field};
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn should_classify_standard_fields() {
+        assert_eq!(field("Package", "foo").kind(), FieldKind::Simple);
+        assert_eq!(field("Depends", "a").kind(), FieldKind::Folded);
+        assert_eq!(field("description", "a").kind(), FieldKind::Multiline);
+        assert_eq!(field("Binary", "a, b").kind(), FieldKind::Folded);
+        assert_eq!(
+            field("Checksums-Sha512", "\nabc 1 a.dsc").kind(),
+            FieldKind::Multiline
+        );
+        assert_eq!(field("X-Custom", "a").kind(), FieldKind::Simple);
+    }
+
+    #[test]
+    fn should_fold_continuation_lines() {
+        let paragraphs =
+            parse_str("Uploaders:\n a <a@example.org>,\n  b <b@example.org>\n").unwrap();
+        let uploaders = &paragraphs[0].fields[0];
+        assert_eq!(uploaders.value, "\na <a@example.org>,\nb <b@example.org>");
+        assert_eq!(
+            uploaders.folded_value(),
+            "a <a@example.org>, b <b@example.org>"
+        );
+        assert_eq!(field("Depends", "single").folded_value(), "single");
+    }
+
+    #[test]
+    fn should_split_multiline_values_into_lines() {
+        let checksums = field("Checksums-Sha256", "\nabc 1 a.dsc\ndef 2 a.tar.xz");
+        assert_eq!(
+            checksums.lines().collect::<Vec<_>>(),
+            vec!["", "abc 1 a.dsc", "def 2 a.tar.xz"]
+        );
+    }
+
+    #[test]
+    fn should_interpret_value_by_registered_kind() {
+        let mut registry = FieldRegistry::new();
+        let depends = field("Depends", "a,\nb");
+        let description = field("Description", "synopsis\ntext");
+        let custom = field("X-Topics", "a\nb");
+        assert_eq!(depends.value_in(&registry), "a, b");
+        assert_eq!(description.value_in(&registry), "synopsis\ntext");
+        assert_eq!(custom.value_in(&registry), "a\nb");
+
+        registry.register("x-topics", FieldKind::Folded);
+        registry.register("Depends", FieldKind::Multiline);
+        assert_eq!(custom.value_in(&registry), "a b");
+        assert_eq!(depends.value_in(&registry), "a,\nb");
+        assert_eq!(registry.get("X-Unknown"), None);
+    }
+
+    #[test]
+    fn should_classify_owned_fields() {
+        let owned = OwnedField::from(field("Build-Depends", "a,\n b"));
+        assert_eq!(owned.kind(), FieldKind::Folded);
+        assert_eq!(owned.folded_value().to_string(), "a, b");
+    }
+}
@@ -6,8 +6,8 @@
 //! The [`parse_str`](fn.parse_str.html) function will parse a complete control file into a vec of
 //! [`Paragraph`](struct.Paragraph.html) values:
 //! ```
-//! # use debcontrol::{Paragraph, Field, parse_str};
-//! # fn main() -> Result<(), debcontrol::SyntaxError<'static>> {
+//! # use oma_debcontrol::{Paragraph, Field, parse_str};
+//! # fn main() -> Result<(), oma_debcontrol::SyntaxError<'static>> {
 //! let paragraphs = parse_str("
 //! a-field: with a value
 //! another-field: with a...
@@ -28,8 +28,8 @@
 //! The [`parse_streaming`](fn.parse_streaming.html) and [`parse_finish`](fn.parse_finish.html)
 //! functions can be used to parse a control file incrementally:
 //! ```
-//! # use debcontrol::{Paragraph, Field, Streaming, parse_streaming, parse_finish};
-//! # fn main() -> Result<(), debcontrol::SyntaxError<'static>> {
+//! # use oma_debcontrol::{Paragraph, Field, Streaming, parse_streaming, parse_finish};
+//! # fn main() -> Result<(), oma_debcontrol::SyntaxError<'static>> {
 //! let result = parse_streaming("field: value")?;
 //! assert_eq!(result, Streaming::Incomplete);
 //!
@@ -57,9 +57,38 @@ extern crate alloc;
 use alloc::{string::String, vec::Vec};
 use core::fmt;
 
+mod address;
+pub mod arch;
 mod buf_parse;
+mod diff;
+pub mod formats;
+mod installability;
+mod kind;
+mod list;
+pub mod lossless;
+mod order;
+mod owned;
 mod parser;
+mod rdepends;
+mod relation;
+mod satisfy;
+mod universe;
+mod version;
+mod write;
+pub use address::*;
 pub use buf_parse::*;
+pub use diff::*;
+pub use installability::*;
+pub use kind::*;
+pub use list::*;
+pub use order::*;
+pub use owned::*;
+pub use rdepends::*;
+pub use relation::*;
+pub use satisfy::*;
+pub use universe::*;
+pub use version::*;
+pub use write::*;
 #[cfg(test)]
 mod tests;
 
@@ -85,11 +114,53 @@ pub struct Paragraph<'a> {
     pub fields: Vec<Field<'a>>,
 }
 
-impl Paragraph<'_> {
+impl<'a> Paragraph<'a> {
     /// Create a new `Paragraph` from the given fields.
-    fn new(fields: Vec<Field>) -> Paragraph {
+    fn new(fields: Vec<Field<'a>>) -> Paragraph<'a> {
         Paragraph { fields }
     }
+
+    /// Return the first field with the given name.
+    ///
+    /// Field names are compared case-insensitively, as required by the Debian policy.
+    pub fn field(&self, name: &str) -> Option<&Field<'a>> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Return a mutable reference to the first field with the given name.
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut Field<'a>> {
+        self.fields
+            .iter_mut()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Return the value of the first field with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.field(name).map(|field| field.value.as_str())
+    }
+
+    /// Set the value of a field.
+    ///
+    /// If a field with this name already exists, its value is replaced in place so the field order
+    /// is kept. Otherwise, a new field is appended to the paragraph.
+    pub fn set(&mut self, name: &'a str, value: impl Into<String>) {
+        let value = value.into();
+        match self.field_mut(name) {
+            Some(field) => field.value = value,
+            None => self.fields.push(Field { name, value }),
+        }
+    }
+
+    /// Remove the first field with the given name and return it.
+    pub fn remove(&mut self, name: &str) -> Option<Field<'a>> {
+        let pos = self
+            .fields
+            .iter()
+            .position(|field| field.name.eq_ignore_ascii_case(name))?;
+        Some(self.fields.remove(pos))
+    }
 }
 
 #[cfg(not(feature = "verbose-errors"))]
@@ -152,7 +223,7 @@ pub enum Streaming<T> {
 /// * read more data from the source and try again or
 /// * if there's no more data in the source, call [`parse_finish`](fn.parse_finish.html) with all
 ///   remaining input.
-pub fn parse_streaming(input: &str) -> Result<Streaming<(&str, Paragraph)>, SyntaxError> {
+pub fn parse_streaming(input: &str) -> Result<Streaming<(&str, Paragraph<'_>)>, SyntaxError<'_>> {
     match parser::streaming::paragraph::<ErrorType>(input) {
         Ok((remaining, Some(item))) => Ok(Streaming::Item((remaining, item))),
         Ok((_, None)) => Ok(Streaming::Incomplete),
@@ -169,7 +240,7 @@ pub fn parse_streaming(input: &str) -> Result<Streaming<(&str, Paragraph)>, Synt
 /// [`Incomplete`](enum.Streaming.html#variant.Incomplete), call this function with any remaining
 /// input to parse the final remaining paragraph. If the remaining input is only whitespace and
 /// comments, `None` is returned.
-pub fn parse_finish(input: &str) -> Result<Option<Paragraph>, SyntaxError> {
+pub fn parse_finish(input: &str) -> Result<Option<Paragraph<'_>>, SyntaxError<'_>> {
     match parser::complete::paragraph::<ErrorType>(input) {
         Ok((_, item)) => Ok(item),
         Err(nom::Err::Error(underlying)) => Err(SyntaxError { input, underlying }),
@@ -182,7 +253,7 @@ pub fn parse_finish(input: &str) -> Result<Option<Paragraph>, SyntaxError> {
 ///
 /// This function does not work for partial input. The entire control file must be passed in at
 /// once.
-pub fn parse_str(input: &str) -> Result<Vec<Paragraph>, SyntaxError> {
+pub fn parse_str(input: &str) -> Result<Vec<Paragraph<'_>>, SyntaxError<'_>> {
     let mut paragraphs = Vec::new();
 
     let mut input = input;
@@ -0,0 +1,348 @@
+//! Helpers for fields whose values are lists, such as `Uploaders` or `Architecture`.
+
+use crate::{Field, OwnedField};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// How the items of a list field are separated.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ListSeparator {
+    /// Items are separated by commas, as in `Uploaders` or `Depends`. Commas within double quotes
+    /// don't separate items.
+    Comma,
+    /// Items are separated by whitespace, as in `Architecture`.
+    Whitespace,
+    /// Items are separated by commas, whitespace, or both, as in `Tests`.
+    CommaOrWhitespace,
+}
+
+/// List fields with a separator other than [`ListSeparator::Comma`](enum.ListSeparator.html).
+const NON_COMMA_LISTS: &[(&str, ListSeparator)] = &[
+    ("Architecture", ListSeparator::Whitespace),
+    // Comma-separated in `.dsc` files, but whitespace-separated in `.changes` files.
+    ("Binary", ListSeparator::CommaOrWhitespace),
+    ("Classes", ListSeparator::CommaOrWhitespace),
+    ("Features", ListSeparator::CommaOrWhitespace),
+    ("Restrictions", ListSeparator::CommaOrWhitespace),
+    ("Tests", ListSeparator::CommaOrWhitespace),
+];
+
+impl ListSeparator {
+    /// Return the separator used by the field with the given name.
+    ///
+    /// Fields that are not known to use another separator are assumed to be comma-separated.
+    pub fn for_field(name: &str) -> ListSeparator {
+        NON_COMMA_LISTS
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map_or(ListSeparator::Comma, |(_, separator)| *separator)
+    }
+
+    /// Split a value into its trimmed, non-empty items.
+    ///
+    /// Line breaks are treated like any other whitespace, so items may span continuation lines.
+    pub fn split(self, value: &str) -> Vec<&str> {
+        match self {
+            ListSeparator::Comma => split_commas(value),
+            ListSeparator::Whitespace => value.split_whitespace().collect(),
+            ListSeparator::CommaOrWhitespace => value
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|item| !item.is_empty())
+                .collect(),
+        }
+    }
+}
+
+/// Split a value at commas outside of double quotes.
+fn split_commas(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+macro_rules! list_methods {
+    ($type: ty) => {
+        impl $type {
+            /// Return the items of this list field, using the separator that
+            /// [`ListSeparator::for_field`](enum.ListSeparator.html#method.for_field) returns for
+            /// its name.
+            pub fn list(&self) -> Vec<&str> {
+                self.list_with(ListSeparator::for_field(&self.name))
+            }
+
+            /// Return the items of this list field, split by the given separator.
+            pub fn list_with(&self, separator: ListSeparator) -> Vec<&str> {
+                separator.split(&self.value)
+            }
+        }
+    };
+}
+
+list_methods!(Field<'_>);
+list_methods!(OwnedField);
+
+/// Options for formatting list fields, modeled after `wrap-and-sort` from devscripts.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct WrapStyle {
+    pub separator: ListSeparator,
+    /// Lists that don't fit into a single line of this length are written one item per line.
+    pub max_line_length: usize,
+    /// Always write one item per line, even if the list would fit into a single line.
+    pub wrap_always: bool,
+    /// Indent wrapped items by a single space, starting on the line after the field name
+    /// (`wrap-and-sort --short-indent`). Otherwise, the first item stays on the field definition
+    /// line and the other items are aligned with it.
+    pub short_indent: bool,
+    /// Add a comma after the last item of wrapped comma-separated lists.
+    pub trailing_comma: bool,
+    /// Sort the items and remove duplicates. Items that don't start with a lowercase letter or a
+    /// digit, such as substitution variables, are sorted after all other items.
+    pub sort: bool,
+}
+
+impl Default for WrapStyle {
+    fn default() -> Self {
+        WrapStyle {
+            separator: ListSeparator::Comma,
+            max_line_length: 79,
+            wrap_always: false,
+            short_indent: false,
+            trailing_comma: false,
+            sort: true,
+        }
+    }
+}
+
+/// Sort list items the way `wrap-and-sort` does and remove duplicates.
+fn sort_items(items: &mut Vec<&str>) {
+    items.sort_by_key(|item| {
+        let special = !item.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit());
+        (special, *item)
+    });
+    items.dedup();
+}
+
+/// Write a list field, including its name and final newline.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{write_list_field, WrapStyle};
+/// let mut output = String::new();
+/// let style = WrapStyle { max_line_length: 20, ..WrapStyle::default() };
+/// write_list_field(&mut output, "Depends", &["${misc:Depends}", "libc6", "bash"], &style).unwrap();
+/// assert_eq!(output, "\
+/// Depends: bash,
+///          libc6,
+///          ${misc:Depends}
+/// ");
+/// ```
+pub fn write_list_field(
+    f: &mut impl fmt::Write,
+    name: &str,
+    items: &[&str],
+    style: &WrapStyle,
+) -> fmt::Result {
+    let mut items = items.to_vec();
+    if style.sort {
+        sort_items(&mut items);
+    }
+    let (inline_separator, line_separator) = match style.separator {
+        ListSeparator::Comma => (", ", ","),
+        ListSeparator::Whitespace | ListSeparator::CommaOrWhitespace => (" ", ""),
+    };
+
+    let single_line_length = name.len()
+        + 1
+        + items
+            .iter()
+            .map(|item| 1 + item.chars().count())
+            .sum::<usize>()
+        + (inline_separator.len() - 1) * items.len().saturating_sub(1);
+    let wrap = !items.is_empty()
+        && (style.wrap_always || single_line_length > style.max_line_length)
+        && (items.len() != 1 || style.short_indent);
+
+    write!(f, "{}:", name)?;
+    if !wrap {
+        if !items.is_empty() {
+            write!(f, " {}", items.join(inline_separator))?;
+        }
+        return writeln!(f);
+    }
+
+    let indent = if style.short_indent {
+        1
+    } else {
+        name.len() + 2
+    };
+    for (i, item) in items.iter().enumerate() {
+        if i == 0 && !style.short_indent {
+            write!(f, " {}", item)?;
+        } else {
+            write!(f, "\n{:indent$}{}", "", item, indent = indent)?;
+        }
+        if i + 1 < items.len() || style.trailing_comma {
+            f.write_str(line_separator)?;
+        }
+    }
+    writeln!(f)
+}
+
+/// Format the value of a list field so that it is written one item per line if necessary.
+///
+/// Unlike [`write_list_field`](fn.write_list_field.html), this returns a field value that can be
+/// stored in a paragraph. Since continuation lines are always written with a single space of
+/// indentation, the result follows the `short_indent` style regardless of the option.
+pub fn wrap_list(name: &str, items: &[&str], style: &WrapStyle) -> String {
+    let style = WrapStyle {
+        short_indent: true,
+        ..*style
+    };
+    let mut text = String::new();
+    write_list_field(&mut text, name, items, &style).unwrap();
+    let value = text[name.len() + 1..].trim_end_matches('\n');
+    let mut lines = value.split('\n');
+    let mut result = String::from(lines.next().unwrap_or_default().trim_start());
+    for line in lines {
+        result.push('\n');
+        result.push_str(line.trim_start());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, tests::field};
+    use alloc::{string::ToString, vec};
+    use indoc::indoc;
+
+    #[test]
+    fn should_split_uploaders_across_continuation_lines() {
+        let paragraphs = parse_str(indoc!(
+            "
+            Uploaders: Jane Doe <jane@example.org>,
+             \"Roe, Richard\" <richard@example.org>,
+              John Smith <john@example.org>,
+            "
+        ))
+        .unwrap();
+        assert_eq!(
+            paragraphs[0].fields[0].list(),
+            vec![
+                "Jane Doe <jane@example.org>",
+                "\"Roe, Richard\" <richard@example.org>",
+                "John Smith <john@example.org>",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_split_by_field_separator() {
+        assert_eq!(
+            field("Architecture", "amd64  arm64\nriscv64").list(),
+            vec!["amd64", "arm64", "riscv64"]
+        );
+        assert_eq!(
+            field("Tests", "a, b c,\nd,").list(),
+            vec!["a", "b", "c", "d"]
+        );
+        assert_eq!(field("Provides", " a , ,b,").list(), vec!["a", "b"]);
+        assert_eq!(
+            field("Binary", "a b").list_with(ListSeparator::Comma),
+            vec!["a b"]
+        );
+        assert!(field("Depends", "").list().is_empty());
+    }
+
+    #[test]
+    fn should_keep_short_lists_on_one_line() {
+        let mut output = String::new();
+        write_list_field(&mut output, "Depends", &["b", "a"], &WrapStyle::default()).unwrap();
+        assert_eq!(output, "Depends: a, b\n");
+    }
+
+    #[test]
+    fn should_wrap_with_short_indent_and_trailing_comma() {
+        let style = WrapStyle {
+            wrap_always: true,
+            short_indent: true,
+            trailing_comma: true,
+            ..WrapStyle::default()
+        };
+        let mut output = String::new();
+        write_list_field(&mut output, "Build-Depends", &["b", "a", "b"], &style).unwrap();
+        assert_eq!(output, "Build-Depends:\n a,\n b,\n");
+    }
+
+    #[test]
+    fn should_sort_special_items_last() {
+        let style = WrapStyle {
+            wrap_always: true,
+            ..WrapStyle::default()
+        };
+        let mut output = String::new();
+        write_list_field(
+            &mut output,
+            "Depends",
+            &["${shlibs:Depends}", "zlib1g", "2ping", "Xorg"],
+            &style,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            indoc!(
+                "
+                Depends: 2ping,
+                         zlib1g,
+                         ${shlibs:Depends},
+                         Xorg
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn should_keep_order_without_sorting() {
+        let style = WrapStyle {
+            separator: ListSeparator::Whitespace,
+            sort: false,
+            ..WrapStyle::default()
+        };
+        let mut output = String::new();
+        write_list_field(&mut output, "Architecture", &["riscv64", "amd64"], &style).unwrap();
+        assert_eq!(output, "Architecture: riscv64 amd64\n");
+    }
+
+    #[test]
+    fn should_wrap_list_into_field_value() {
+        let style = WrapStyle {
+            max_line_length: 10,
+            trailing_comma: true,
+            ..WrapStyle::default()
+        };
+        let value = wrap_list("Depends", &["libfoo", "libbar"], &style);
+        assert_eq!(value, "\nlibbar,\nlibfoo,");
+        assert_eq!(
+            field("Depends", &value).to_string(),
+            "Depends:\n libbar,\n libfoo,\n"
+        );
+        assert_eq!(wrap_list("Depends", &["a"], &style), "a");
+    }
+}
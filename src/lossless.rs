@@ -0,0 +1,696 @@
+//! A lossless representation of control files.
+//!
+//! The regular parser only keeps field names and values, so comments and formatting details are
+//! lost. The types in this module keep the original text of every line instead. Writing a
+//! [`Document`](struct.Document.html) back out reproduces its input byte for byte, and only the
+//! fields and paragraphs that were modified are reformatted.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::lossless::Document;
+//! let mut doc = Document::parse("\
+//! ## the source package
+//! Source: foo
+//! Build-Depends: a,
+//! ## b is needed for tests
+//!                b
+//! ").unwrap();
+//!
+//! let source = doc.paragraphs_mut().next().unwrap();
+//! assert_eq!(source.get("Build-Depends"), Some("a,\nb"));
+//! source.set("Standards-Version", "4.6.2");
+//!
+//! assert_eq!(doc.to_string(), "\
+//! ## the source package
+//! Source: foo
+//! Build-Depends: a,
+//! ## b is needed for tests
+//!                b
+//! Standards-Version: 4.6.2
+//! ");
+//! ```
+
+use crate::{parse_str, OwnedField, OwnedParagraph, SyntaxError};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+mod merge;
+pub use merge::*;
+
+/// Strip a trailing line ending, if any.
+fn strip_line_ending(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .unwrap_or(line)
+}
+
+/// Make sure the text ends with a newline so that more lines can be appended after it.
+fn terminate_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+/// A field that remembers its original text.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Field {
+    name: String,
+    value: String,
+    text: String,
+}
+
+impl Field {
+    /// Create a new field with the given name and value.
+    ///
+    /// The field text is generated the same way [`OwnedField`](../struct.OwnedField.html) is
+    /// displayed.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Field {
+        let field = OwnedField {
+            name: name.into(),
+            value: value.into(),
+        };
+        Field {
+            text: field.to_string(),
+            name: field.name,
+            value: field.value,
+        }
+    }
+
+    /// Create a field from its original text.
+    ///
+    /// The value is extracted using the same rules as the regular parser: leading whitespace is
+    /// removed from every line and comment lines are skipped.
+    fn from_text(text: String) -> Field {
+        let mut lines = text.split_inclusive('\n');
+        let first = strip_line_ending(lines.next().unwrap_or_default());
+        let (name, first_value) = first.split_once(':').unwrap_or((first, ""));
+        let mut value = String::from(first_value.trim_start_matches([' ', '\t']));
+        for line in lines.filter(|line| !line.starts_with('#')) {
+            value.push('\n');
+            value.push_str(strip_line_ending(line).trim_start_matches([' ', '\t']));
+        }
+        Field {
+            name: String::from(name),
+            value,
+            text,
+        }
+    }
+
+    /// Return the field name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the field value, normalized the same way as [`Field`](../struct.Field.html) values.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Return the original text of the field, including continuation and comment lines.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Return the lines of the field value as they were written.
+    ///
+    /// The first line is the value on the field definition line, which is empty if the value
+    /// starts on a continuation line. Every further line is a continuation line including its
+    /// leading whitespace. Comment lines are skipped.
+    pub fn lines(&self) -> Vec<&str> {
+        let mut lines = self.text.split_inclusive('\n');
+        let first = strip_line_ending(lines.next().unwrap_or_default());
+        let first = first.split_once(':').map_or("", |(_, value)| value);
+        let mut result = vec![first.trim_start_matches([' ', '\t'])];
+        result.extend(
+            lines
+                .filter(|line| !line.starts_with('#'))
+                .map(strip_line_ending),
+        );
+        result
+    }
+
+    /// Return true if the value starts on a continuation line, as in `Uploaders:` followed by an
+    /// indented list.
+    pub fn is_first_line_empty(&self) -> bool {
+        self.lines()[0].is_empty()
+    }
+
+    /// Return the field value with the original indentation of its continuation lines.
+    ///
+    /// Comment lines are skipped and the value doesn't end with a newline. Some fields, such as
+    /// `Description`, give meaning to the indentation that [`value`](#method.value) removes.
+    pub fn raw_value(&self) -> String {
+        self.lines().join("\n")
+    }
+
+    /// Replace the value of this field.
+    ///
+    /// If the value actually changes, the field text is regenerated, dropping any comment lines
+    /// within the field.
+    pub fn set_value(&mut self, value: impl Into<String>) {
+        let value = value.into();
+        if value != self.value {
+            *self = Field::new(core::mem::take(&mut self.name), value);
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// A line or field within a paragraph.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum ParagraphItem {
+    /// A field definition.
+    Field(Field),
+    /// A comment line between fields, including its line ending.
+    Comment(String),
+}
+
+impl ParagraphItem {
+    fn text_mut(&mut self) -> &mut String {
+        match self {
+            ParagraphItem::Field(field) => &mut field.text,
+            ParagraphItem::Comment(text) => text,
+        }
+    }
+}
+
+/// A paragraph that remembers its original text.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct Paragraph {
+    pub items: Vec<ParagraphItem>,
+}
+
+impl Paragraph {
+    /// Return an iterator over all fields in this paragraph.
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.items.iter().filter_map(|item| match item {
+            ParagraphItem::Field(field) => Some(field),
+            ParagraphItem::Comment(_) => None,
+        })
+    }
+
+    /// Return the first field with the given name, compared case-insensitively.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Return a mutable reference to the first field with the given name.
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut Field> {
+        self.items.iter_mut().find_map(|item| match item {
+            ParagraphItem::Field(field) if field.name.eq_ignore_ascii_case(name) => Some(field),
+            _ => None,
+        })
+    }
+
+    /// Return the value of the first field with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.field(name).map(Field::value)
+    }
+
+    /// Set the value of a field.
+    ///
+    /// An existing field is updated in place. Otherwise, a new field is added after the last field
+    /// in the paragraph.
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        match self.field_mut(name) {
+            Some(field) => field.set_value(value),
+            None => self.push(Field::new(name, value)),
+        }
+    }
+
+    /// Add a field after the last field in the paragraph.
+    pub fn push(&mut self, field: Field) {
+        let pos = self
+            .items
+            .iter()
+            .rposition(|item| matches!(item, ParagraphItem::Field(_)))
+            .map_or(0, |pos| pos + 1);
+        if pos > 0 {
+            terminate_line(self.items[pos - 1].text_mut());
+        }
+        self.items.insert(pos, ParagraphItem::Field(field));
+    }
+
+    /// Remove the first field with the given name and return it.
+    pub fn remove(&mut self, name: &str) -> Option<Field> {
+        let pos = self.items.iter().position(
+            |item| matches!(item, ParagraphItem::Field(field) if field.name.eq_ignore_ascii_case(name)),
+        )?;
+        match self.items.remove(pos) {
+            ParagraphItem::Field(field) => Some(field),
+            ParagraphItem::Comment(_) => unreachable!(),
+        }
+    }
+
+    /// Convert this paragraph into a regular paragraph, dropping all formatting information.
+    pub fn to_owned_paragraph(&self) -> OwnedParagraph {
+        OwnedParagraph::new(
+            self.fields()
+                .map(|field| OwnedField {
+                    name: field.name.clone(),
+                    value: field.value.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    fn ends_with_newline(&self) -> bool {
+        match self.items.last() {
+            Some(ParagraphItem::Field(field)) => field.text.ends_with('\n'),
+            Some(ParagraphItem::Comment(text)) => text.ends_with('\n'),
+            None => true,
+        }
+    }
+}
+
+impl From<&OwnedParagraph> for Paragraph {
+    fn from(paragraph: &OwnedParagraph) -> Self {
+        Paragraph {
+            items: paragraph
+                .fields
+                .iter()
+                .map(|field| ParagraphItem::Field(Field::new(&*field.name, &*field.value)))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Paragraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                ParagraphItem::Field(field) => f.write_str(&field.text)?,
+                ParagraphItem::Comment(text) => f.write_str(text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A top-level item in a document.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Item {
+    /// A paragraph.
+    Paragraph(Paragraph),
+    /// Blank and comment lines between paragraphs, including their line endings.
+    Text(String),
+}
+
+/// A complete control file that remembers its original text.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct Document {
+    pub items: Vec<Item>,
+}
+
+impl Document {
+    /// Parse a complete control file.
+    ///
+    /// The input is validated with [`parse_str`](../fn.parse_str.html) first, so this accepts
+    /// exactly the same input as the regular parser.
+    pub fn parse(input: &str) -> Result<Document, SyntaxError<'_>> {
+        parse_str(input)?;
+
+        let mut builder = Builder::default();
+        for line in input.split_inclusive('\n') {
+            builder.line(line);
+        }
+        Ok(builder.finish())
+    }
+
+    /// Return an iterator over all paragraphs.
+    pub fn paragraphs(&self) -> impl Iterator<Item = &Paragraph> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Paragraph(paragraph) => Some(paragraph),
+            Item::Text(_) => None,
+        })
+    }
+
+    /// Return an iterator over mutable references to all paragraphs.
+    pub fn paragraphs_mut(&mut self) -> impl Iterator<Item = &mut Paragraph> {
+        self.items.iter_mut().filter_map(|item| match item {
+            Item::Paragraph(paragraph) => Some(paragraph),
+            Item::Text(_) => None,
+        })
+    }
+
+    /// Append a paragraph at the end of the document, separated by a blank line.
+    pub fn push_paragraph(&mut self, paragraph: Paragraph) {
+        let needs_separator = match self.items.last_mut() {
+            Some(Item::Paragraph(last)) => {
+                if !last.ends_with_newline() {
+                    if let Some(item) = last.items.last_mut() {
+                        item.text_mut().push('\n');
+                    }
+                }
+                true
+            }
+            Some(Item::Text(text)) => {
+                terminate_line(text);
+                false
+            }
+            None => false,
+        };
+        if needs_separator {
+            self.items.push(Item::Text(String::from("\n")));
+        }
+        self.items.push(Item::Paragraph(paragraph));
+    }
+
+    /// Remove the paragraph with the given index (counting only paragraphs) and return it.
+    ///
+    /// The blank line separating it from the previous paragraph is removed as well. Comment lines
+    /// around the paragraph are kept.
+    pub fn remove_paragraph(&mut self, index: usize) -> Option<Paragraph> {
+        let pos = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| matches!(item, Item::Paragraph(_)))
+            .nth(index)?
+            .0;
+        let paragraph = match self.items.remove(pos) {
+            Item::Paragraph(paragraph) => paragraph,
+            Item::Text(_) => unreachable!(),
+        };
+        if pos > 0 {
+            if let Item::Text(text) = &mut self.items[pos - 1] {
+                if let Some(stripped) = text.strip_suffix('\n') {
+                    if stripped.is_empty() || stripped.ends_with('\n') {
+                        text.truncate(stripped.len());
+                    }
+                }
+                if text.is_empty() {
+                    self.items.remove(pos - 1);
+                }
+            }
+        }
+        Some(paragraph)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                Item::Paragraph(paragraph) => write!(f, "{}", paragraph)?,
+                Item::Text(text) => f.write_str(text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Incrementally builds a document from input lines.
+///
+/// This relies on the input having been validated by the regular parser, and mirrors its rules:
+/// lines starting with whitespace continue the current field (even if they contain nothing else),
+/// and a paragraph ends with a line that is completely empty.
+#[derive(Default)]
+struct Builder {
+    items: Vec<Item>,
+    text: String,
+    paragraph: Option<Paragraph>,
+    comments: Vec<String>,
+}
+
+impl Builder {
+    fn line(&mut self, line: &str) {
+        let paragraph = match &mut self.paragraph {
+            Some(paragraph) => paragraph,
+            None => {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    self.text.push_str(line);
+                } else {
+                    if !self.text.is_empty() {
+                        self.items.push(Item::Text(core::mem::take(&mut self.text)));
+                    }
+                    self.paragraph = Some(Paragraph {
+                        items: vec![field_start(line)],
+                    });
+                }
+                return;
+            }
+        };
+
+        if line.starts_with('#') {
+            self.comments.push(line.to_string());
+        } else if line.starts_with([' ', '\t']) {
+            if let Some(ParagraphItem::Field(field)) = paragraph.items.last_mut() {
+                for comment in self.comments.drain(..) {
+                    field.text.push_str(&comment);
+                }
+                field.text.push_str(line);
+            }
+        } else {
+            paragraph
+                .items
+                .extend(self.comments.drain(..).map(ParagraphItem::Comment));
+            if strip_line_ending(line).is_empty() {
+                self.finish_paragraph();
+                self.text.push_str(line);
+            } else {
+                paragraph.items.push(field_start(line));
+            }
+        }
+    }
+
+    fn finish_paragraph(&mut self) {
+        if let Some(mut paragraph) = self.paragraph.take() {
+            paragraph
+                .items
+                .extend(self.comments.drain(..).map(ParagraphItem::Comment));
+            for item in &mut paragraph.items {
+                if let ParagraphItem::Field(field) = item {
+                    *field = Field::from_text(core::mem::take(&mut field.text));
+                }
+            }
+            self.items.push(Item::Paragraph(paragraph));
+        }
+    }
+
+    fn finish(mut self) -> Document {
+        self.finish_paragraph();
+        if !self.text.is_empty() {
+            self.items.push(Item::Text(self.text));
+        }
+        Document { items: self.items }
+    }
+}
+
+/// Start a new field from its definition line. The value is filled in once the field is complete.
+fn field_start(line: &str) -> ParagraphItem {
+    ParagraphItem::Field(Field {
+        name: String::new(),
+        value: String::new(),
+        text: line.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const INPUT: &str = indoc!(
+        "
+        # leading comment
+
+        Source: foo
+        # comment before field
+        Build-Depends: a,
+        # comment inside field
+         b,
+        \tc
+        Standards-Version: 4.6.2
+        # trailing comment
+
+        # comment before paragraph
+        Package: foo
+        Description: synopsis
+         .
+         text"
+    );
+
+    #[test]
+    fn should_round_trip_input() {
+        let doc = Document::parse(INPUT).unwrap();
+        assert_eq!(doc.to_string(), INPUT);
+    }
+
+    #[test]
+    fn should_round_trip_crlf_input() {
+        let input = "a: 1\r\n b\r\n\r\nc: 2\r\n";
+        let doc = Document::parse(input).unwrap();
+        assert_eq!(doc.to_string(), input);
+        assert_eq!(doc.paragraphs().next().unwrap().get("a"), Some("1\nb"));
+    }
+
+    #[test]
+    fn should_extract_same_values_as_parser() {
+        let doc = Document::parse(INPUT).unwrap();
+        let expected = parse_str(INPUT)
+            .unwrap()
+            .into_iter()
+            .map(OwnedParagraph::from)
+            .collect::<Vec<_>>();
+        let actual = doc
+            .paragraphs()
+            .map(Paragraph::to_owned_paragraph)
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_keep_indentation_in_raw_value() {
+        let doc = Document::parse(INPUT).unwrap();
+        let source = doc.paragraphs().next().unwrap();
+        assert_eq!(
+            source.field("Build-Depends").unwrap().raw_value(),
+            "a,\n b,\n\tc"
+        );
+        assert_eq!(source.field("Source").unwrap().raw_value(), "foo");
+    }
+
+    #[test]
+    fn should_keep_original_lines() {
+        let doc = Document::parse(indoc!(
+            "
+            Uploaders:
+              a <a@example.org>,
+            # comment inside field
+            \tb <b@example.org>
+            Description: synopsis
+             .
+               verbatim
+            "
+        ))
+        .unwrap();
+        let paragraph = doc.paragraphs().next().unwrap();
+        let uploaders = paragraph.field("Uploaders").unwrap();
+        assert_eq!(
+            uploaders.lines(),
+            vec!["", "  a <a@example.org>,", "\tb <b@example.org>"]
+        );
+        assert!(uploaders.is_first_line_empty());
+        assert_eq!(uploaders.value(), "\na <a@example.org>,\nb <b@example.org>");
+        let description = paragraph.field("Description").unwrap();
+        assert_eq!(description.lines(), vec!["synopsis", " .", "   verbatim"]);
+        assert!(!description.is_first_line_empty());
+    }
+
+    #[test]
+    fn should_keep_comments_as_separate_items() {
+        let doc = Document::parse(INPUT).unwrap();
+        let source = doc.paragraphs().next().unwrap();
+        assert_eq!(
+            source.items[0],
+            ParagraphItem::Field(Field::from_text("Source: foo\n".to_string()))
+        );
+        assert_eq!(
+            source.items[1],
+            ParagraphItem::Comment("# comment before field\n".to_string())
+        );
+        assert_eq!(
+            source.items[4],
+            ParagraphItem::Comment("# trailing comment\n".to_string())
+        );
+    }
+
+    #[test]
+    fn should_treat_whitespace_only_lines_as_continuations() {
+        let doc = Document::parse("a: 1\n \nb: 2\n").unwrap();
+        assert_eq!(doc.paragraphs().count(), 1);
+        assert_eq!(doc.to_string(), "a: 1\n \nb: 2\n");
+    }
+
+    #[test]
+    fn should_reject_invalid_input() {
+        assert!(Document::parse("a: 1\n-b: 2\n").is_err());
+    }
+
+    #[test]
+    fn should_only_reformat_modified_fields() {
+        let mut doc = Document::parse(INPUT).unwrap();
+        let source = doc.paragraphs_mut().next().unwrap();
+        source.set("Standards-Version", "4.7.0");
+        source.set("Build-Depends", "a,\nb,\nc");
+        source.set("Homepage", "https://example.org");
+        assert_eq!(
+            doc.to_string(),
+            indoc!(
+                "
+                # leading comment
+
+                Source: foo
+                # comment before field
+                Build-Depends: a,
+                # comment inside field
+                 b,
+                \tc
+                Standards-Version: 4.7.0
+                Homepage: https://example.org
+                # trailing comment
+
+                # comment before paragraph
+                Package: foo
+                Description: synopsis
+                 .
+                 text"
+            )
+        );
+    }
+
+    #[test]
+    fn should_remove_field() {
+        let mut doc = Document::parse("a: 1\nb: 2\n 3\nc: 4\n").unwrap();
+        let removed = doc.paragraphs_mut().next().unwrap().remove("B").unwrap();
+        assert_eq!(removed.value(), "2\n3");
+        assert_eq!(doc.to_string(), "a: 1\nc: 4\n");
+    }
+
+    #[test]
+    fn should_push_paragraph_after_unterminated_paragraph() {
+        let mut doc = Document::parse("a: 1").unwrap();
+        let mut paragraph = Paragraph::default();
+        paragraph.set("b", "2");
+        doc.push_paragraph(paragraph);
+        assert_eq!(doc.to_string(), "a: 1\n\nb: 2\n");
+    }
+
+    #[test]
+    fn should_push_field_after_unterminated_field() {
+        let mut doc = Document::parse("a: 1").unwrap();
+        doc.paragraphs_mut().next().unwrap().set("b", "2");
+        assert_eq!(doc.to_string(), "a: 1\nb: 2\n");
+    }
+
+    #[test]
+    fn should_remove_paragraph_with_separator() {
+        let mut doc = Document::parse("a: 1\n\nb: 2\n\n# c\nc: 3\n").unwrap();
+        let removed = doc.remove_paragraph(1).unwrap();
+        assert_eq!(removed.get("b"), Some("2"));
+        assert_eq!(doc.to_string(), "a: 1\n\n# c\nc: 3\n");
+        doc.remove_paragraph(1).unwrap();
+        assert_eq!(doc.to_string(), "a: 1\n\n# c\n");
+        assert_eq!(doc.remove_paragraph(1), None);
+    }
+
+    #[test]
+    fn should_convert_owned_paragraph() {
+        let paragraph = OwnedParagraph::new(vec![OwnedField {
+            name: "a".to_string(),
+            value: "1\n2".to_string(),
+        }]);
+        assert_eq!(Paragraph::from(&paragraph).to_string(), "a: 1\n 2\n");
+    }
+}
@@ -0,0 +1,632 @@
+//! Three-way merging of documents.
+
+use super::{terminate_line, Document, Paragraph, ParagraphItem};
+use crate::{FieldKind, FieldRegistry, ListSeparator};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+/// Fields whose items are package relationships. Their items are matched by package name when
+/// merging, so changing the version constraint of a package is a change of the item.
+const RELATIONSHIP_FIELDS: &[&str] = &[
+    "Breaks",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Built-Using",
+    "Conflicts",
+    "Depends",
+    "Enhances",
+    "Pre-Depends",
+    "Provides",
+    "Recommends",
+    "Replaces",
+    "Static-Built-Using",
+    "Suggests",
+];
+
+/// A change that couldn't be merged automatically.
+///
+/// The merged document keeps our version of the conflicting field or paragraph.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct MergeConflict {
+    /// The `Package` or `Source` of the paragraph, or an empty string if it has neither.
+    pub paragraph: String,
+    /// The conflicting field, or `None` if one side changed the paragraph and the other side
+    /// removed it.
+    pub field: Option<String>,
+    /// The field value or paragraph text in the common ancestor.
+    pub base: Option<String>,
+    /// Our field value or paragraph text, or `None` if we removed it.
+    pub ours: Option<String>,
+    /// Their field value or paragraph text, or `None` if they removed it.
+    pub theirs: Option<String>,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = match &self.field {
+            Some(field) => field,
+            None => {
+                let removed = if self.ours.is_none() { "us" } else { "them" };
+                return write!(
+                    f,
+                    "paragraph {} was removed by {}, but changed by the other side",
+                    self.paragraph, removed
+                );
+            }
+        };
+        let value = |value: &Option<String>| match value {
+            Some(value) => alloc::format!("'{}'", value),
+            None => String::from("removed"),
+        };
+        write!(
+            f,
+            "conflicting changes to {} in {}: ours {}, theirs {}",
+            field,
+            self.paragraph,
+            value(&self.ours),
+            value(&self.theirs)
+        )
+    }
+}
+
+/// The result of a three-way merge.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct MergeResult {
+    pub document: Document,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeResult {
+    /// Return true if all changes were merged without conflicts.
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Identifies a paragraph across the three versions of a document.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct ParagraphKey<'a> {
+    /// The field that names the paragraph, `Package` or `Source`, and its value. Binary and
+    /// source paragraphs with the same name are different paragraphs.
+    name: Option<(&'static str, &'a str)>,
+    /// The position among the paragraphs with the same name.
+    ordinal: usize,
+}
+
+impl<'a> ParagraphKey<'a> {
+    /// Return the name of the paragraph, or an empty string if it has none.
+    fn name(&self) -> &'a str {
+        self.name.map_or("", |(_, name)| name)
+    }
+}
+
+/// Identify every paragraph of a document by its `Package` field, or its `Source` field if it has
+/// no `Package` field, and its position among the paragraphs with the same name.
+fn paragraph_keys(document: &Document) -> Vec<ParagraphKey<'_>> {
+    let mut keys: Vec<ParagraphKey<'_>> = Vec::new();
+    for paragraph in document.paragraphs() {
+        let name = ["Package", "Source"]
+            .iter()
+            .find_map(|field| paragraph.get(field).map(|value| (*field, value)));
+        let ordinal = keys.iter().filter(|other| other.name == name).count();
+        keys.push(ParagraphKey { name, ordinal });
+    }
+    keys
+}
+
+/// Return true if both paragraphs have the same fields and values.
+fn same_fields(a: &Paragraph, b: &Paragraph) -> bool {
+    a.to_owned_paragraph() == b.to_owned_paragraph()
+}
+
+/// Return the key a list item is matched by.
+fn item_key<'i>(field: &str, item: &'i str) -> &'i str {
+    let relationship = RELATIONSHIP_FIELDS
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(field));
+    if !relationship {
+        return item;
+    }
+    item.split(|c: char| c.is_whitespace() || matches!(c, '(' | '[' | '<' | ':' | '|'))
+        .next()
+        .unwrap_or(item)
+}
+
+/// Merge the items of a comma-separated list field. Returns `None` if the changes conflict.
+fn merge_items<'v>(
+    field: &str,
+    base: &[&'v str],
+    ours: &[&'v str],
+    theirs: &[&'v str],
+) -> Option<Vec<&'v str>> {
+    let has_duplicate_keys = |items: &[&str]| {
+        items.iter().enumerate().any(|(i, item)| {
+            items[..i]
+                .iter()
+                .any(|other| item_key(field, other) == item_key(field, item))
+        })
+    };
+    let by_name =
+        !(has_duplicate_keys(base) || has_duplicate_keys(ours) || has_duplicate_keys(theirs));
+    let key = |item: &'v str| if by_name { item_key(field, item) } else { item };
+    let find =
+        |items: &[&'v str], wanted: &str| items.iter().copied().find(|item| key(item) == wanted);
+
+    let mut merged = Vec::new();
+    for item in ours.iter().copied() {
+        let (base_item, their_item) = (find(base, key(item)), find(theirs, key(item)));
+        if their_item == Some(item) || base_item == their_item {
+            merged.push(item);
+        } else if base_item == Some(item) {
+            merged.extend(their_item);
+        } else {
+            return None;
+        }
+    }
+    for (i, item) in theirs.iter().copied().enumerate() {
+        if find(ours, key(item)).is_some() {
+            continue;
+        }
+        match find(base, key(item)) {
+            // We removed the item and they didn't change it.
+            Some(base_item) if base_item == item => {}
+            Some(_) => return None,
+            None => {
+                // Keep the item next to the one it follows in their list.
+                let pos = match i.checked_sub(1) {
+                    Some(previous) => merged
+                        .iter()
+                        .position(|other| key(other) == key(theirs[previous]))
+                        .map_or(merged.len(), |pos| pos + 1),
+                    None => 0,
+                };
+                merged.insert(pos, item);
+            }
+        }
+    }
+    Some(merged)
+}
+
+/// Merge the values of a list field that both sides changed. Returns `None` if the changes
+/// conflict.
+///
+/// The merged value keeps the layout of our value: either all items on one line, or one item per
+/// line, optionally starting on the line after the field name and with a trailing comma.
+fn merge_list(field: &str, base: Option<&str>, ours: &str, theirs: &str) -> Option<String> {
+    if FieldRegistry::new().kind(field) != FieldKind::Folded
+        || ListSeparator::for_field(field) != ListSeparator::Comma
+    {
+        return None;
+    }
+    let split = |value| ListSeparator::Comma.split(value);
+    let base = base.map(split).unwrap_or_default();
+    let items = merge_items(field, &base, &split(ours), &split(theirs))?;
+    let mut value = String::new();
+    if ours.contains('\n') {
+        if ours.starts_with('\n') {
+            value.push('\n');
+        }
+        value.push_str(&items.join(",\n"));
+        if ours.ends_with(',') {
+            value.push(',');
+        }
+    } else {
+        value.push_str(&items.join(", "));
+    }
+    Some(value)
+}
+
+/// Insert items into a paragraph, keeping every line terminated.
+fn insert_items(paragraph: &mut Paragraph, pos: usize, mut items: Vec<ParagraphItem>) {
+    if pos > 0 {
+        terminate_line(paragraph.items[pos - 1].text_mut());
+    }
+    if let Some(last) = items.last_mut() {
+        terminate_line(last.text_mut());
+    }
+    paragraph.items.splice(pos..pos, items);
+}
+
+/// Take their version of a field, including its comments.
+fn take_field(result: &mut Paragraph, theirs: &Paragraph, name: &str) {
+    let pos = match theirs.items.iter().position(
+        |item| matches!(item, ParagraphItem::Field(field) if field.name.eq_ignore_ascii_case(name)),
+    ) {
+        Some(pos) => pos,
+        None => return,
+    };
+    let field = match &theirs.items[pos] {
+        ParagraphItem::Field(field) => field,
+        ParagraphItem::Comment(_) => unreachable!(),
+    };
+    if let Some(existing) = result.field_mut(name) {
+        let terminated = existing.text.ends_with('\n');
+        *existing = field.clone();
+        if terminated {
+            terminate_line(&mut existing.text);
+        }
+        return;
+    }
+
+    // Add the field after the field it follows in their paragraph, along with the comments
+    // directly before it.
+    let start = theirs.items[..pos]
+        .iter()
+        .rposition(|item| matches!(item, ParagraphItem::Field(_)))
+        .map_or(0, |previous| previous + 1);
+    let insert_at = match &theirs.items[..start].last() {
+        Some(ParagraphItem::Field(previous)) => result
+            .items
+            .iter()
+            .position(|item| matches!(item, ParagraphItem::Field(field) if field.name.eq_ignore_ascii_case(&previous.name))),
+        _ => None,
+    };
+    let insert_at = match insert_at {
+        Some(previous) => previous + 1,
+        None if start == 0 => 0,
+        None => result
+            .items
+            .iter()
+            .rposition(|item| matches!(item, ParagraphItem::Field(_)))
+            .map_or(0, |last| last + 1),
+    };
+    insert_items(result, insert_at, theirs.items[start..=pos].to_vec());
+}
+
+/// Merge the fields of a paragraph that exists on both sides into `result`, which starts out as
+/// our version.
+fn merge_paragraph(
+    result: &mut Paragraph,
+    base: Option<&Paragraph>,
+    theirs: &Paragraph,
+    name: &str,
+    conflicts: &mut Vec<MergeConflict>,
+) {
+    let empty = Paragraph::default();
+    let base = base.unwrap_or(&empty);
+    let ours = result.clone();
+    let mut names: Vec<&str> = Vec::new();
+    for field in ours.fields().chain(theirs.fields()) {
+        if !names
+            .iter()
+            .any(|other| other.eq_ignore_ascii_case(&field.name))
+        {
+            names.push(&field.name);
+        }
+    }
+
+    for field in names {
+        let (base_value, our_value, their_value) =
+            (base.get(field), ours.get(field), theirs.get(field));
+        if our_value == their_value || base_value == their_value {
+            continue;
+        }
+        if base_value == our_value {
+            match their_value {
+                Some(_) => take_field(result, theirs, field),
+                None => {
+                    result.remove(field);
+                }
+            }
+            continue;
+        }
+        let merged = match (our_value, their_value) {
+            (Some(ours), Some(theirs)) => merge_list(field, base_value, ours, theirs),
+            _ => None,
+        };
+        match (merged, result.field_mut(field)) {
+            (Some(value), Some(existing)) => existing.set_value(value),
+            _ => conflicts.push(MergeConflict {
+                paragraph: String::from(name),
+                field: Some(String::from(field)),
+                base: base_value.map(String::from),
+                ours: our_value.map(String::from),
+                theirs: their_value.map(String::from),
+            }),
+        }
+    }
+}
+
+/// Merge two modified versions of a document with their common ancestor.
+///
+/// The merge starts from our document, so its formatting and comments are kept, and applies
+/// their changes at field granularity:
+///
+/// * Paragraphs are matched by their `Package` or `Source` field. Paragraphs they added are
+///   appended, and paragraphs removed on one side are removed if the other side didn't change
+///   them.
+/// * Fields changed only on one side take that side's version, including its comments.
+/// * If both sides changed a comma-separated list field such as `Build-Depends`, the items are
+///   merged individually. Relationship items are matched by package name. The merged field is
+///   reformatted, so comments within it are lost.
+/// * All other changes on both sides are conflicts. The merged document keeps our version for
+///   them.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::lossless::{merge, Document};
+/// let base = Document::parse("\
+/// Source: foo
+/// ## keep sorted
+/// Build-Depends: debhelper-compat (= 13), libfoo-dev
+/// Standards-Version: 4.6.2
+/// ").unwrap();
+/// let ours = Document::parse("\
+/// Source: foo
+/// ## keep sorted
+/// Build-Depends: debhelper-compat (= 13), libbar-dev, libfoo-dev
+/// Standards-Version: 4.6.2
+/// ").unwrap();
+/// let theirs = Document::parse("\
+/// Source: foo
+/// Build-Depends: debhelper-compat (= 13), libfoo-dev (>= 2)
+/// Standards-Version: 4.7.0
+/// ").unwrap();
+///
+/// let result = merge(&base, &ours, &theirs);
+/// assert!(result.is_clean());
+/// assert_eq!(result.document.to_string(), "\
+/// Source: foo
+/// ## keep sorted
+/// Build-Depends: debhelper-compat (= 13), libbar-dev, libfoo-dev (>= 2)
+/// Standards-Version: 4.7.0
+/// ");
+/// ```
+pub fn merge(base: &Document, ours: &Document, theirs: &Document) -> MergeResult {
+    let base_paragraphs = base.paragraphs().collect::<Vec<_>>();
+    let base_keys = paragraph_keys(base);
+    let their_paragraphs = theirs.paragraphs().collect::<Vec<_>>();
+    let their_keys = paragraph_keys(theirs);
+    let our_keys = paragraph_keys(ours);
+    let find = |keys: &[ParagraphKey<'_>], key| keys.iter().position(|other| *other == key);
+
+    let mut document = ours.clone();
+    let mut conflicts = Vec::new();
+    let mut removed = Vec::new();
+    for (index, (result, key)) in document.paragraphs_mut().zip(&our_keys).enumerate() {
+        let name = key.name();
+        let base = find(&base_keys, *key).map(|index| base_paragraphs[index]);
+        match (base, find(&their_keys, *key)) {
+            (base, Some(their_index)) => merge_paragraph(
+                result,
+                base,
+                their_paragraphs[their_index],
+                name,
+                &mut conflicts,
+            ),
+            (Some(base), None) if same_fields(base, result) => removed.push(index),
+            (Some(base), None) => conflicts.push(MergeConflict {
+                paragraph: String::from(name),
+                field: None,
+                base: Some(base.to_string()),
+                ours: Some(result.to_string()),
+                theirs: None,
+            }),
+            (None, None) => {}
+        }
+    }
+
+    for (theirs, key) in their_paragraphs.iter().zip(&their_keys) {
+        if find(&our_keys, *key).is_some() {
+            continue;
+        }
+        match find(&base_keys, *key).map(|index| base_paragraphs[index]) {
+            Some(base) if same_fields(base, theirs) => {}
+            Some(base) => conflicts.push(MergeConflict {
+                paragraph: String::from(key.name()),
+                field: None,
+                base: Some(base.to_string()),
+                ours: None,
+                theirs: Some(theirs.to_string()),
+            }),
+            None => document.push_paragraph((*theirs).clone()),
+        }
+    }
+    for index in removed.into_iter().rev() {
+        document.remove_paragraph(index);
+    }
+
+    MergeResult {
+        document,
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, vec};
+    use indoc::indoc;
+
+    const BASE: &str = indoc!(
+        "
+        # Maintained in git
+        Source: foo
+        Maintainer: Jane Doe <jane@example.org>
+        Build-Depends:
+         debhelper-compat (= 13),
+         libbar-dev,
+         libfoo-dev (>= 1),
+        Standards-Version: 4.6.2
+
+        Package: foo
+        Architecture: any
+        Depends: ${misc:Depends}, ${shlibs:Depends}
+        Description: foo tool
+
+        Package: foo-doc
+        Architecture: all
+        Description: foo documentation
+        "
+    );
+
+    fn merge_str(ours: &str, theirs: &str) -> MergeResult {
+        merge(
+            &Document::parse(BASE).unwrap(),
+            &Document::parse(ours).unwrap(),
+            &Document::parse(theirs).unwrap(),
+        )
+    }
+
+    #[test]
+    fn should_merge_changes_to_different_fields() {
+        let ours = BASE
+            .replace("4.6.2", "4.7.0")
+            .replace("Source: foo\n", "Source: foo\n# contact the team first\n");
+        let theirs = BASE
+            .replace("Description: foo tool\n", "")
+            .replace(
+                "Architecture: any\n",
+                "Architecture: any\n# needed for plugins\nRecommends: foo-plugins\n",
+            )
+            .replace("Maintainer: Jane Doe <jane@example.org>\n", "");
+        let result = merge_str(&ours, &theirs);
+        assert_eq!(result.conflicts, vec![]);
+        assert_eq!(
+            result.document.to_string(),
+            indoc!(
+                "
+                # Maintained in git
+                Source: foo
+                # contact the team first
+                Build-Depends:
+                 debhelper-compat (= 13),
+                 libbar-dev,
+                 libfoo-dev (>= 1),
+                Standards-Version: 4.7.0
+
+                Package: foo
+                Architecture: any
+                # needed for plugins
+                Recommends: foo-plugins
+                Depends: ${misc:Depends}, ${shlibs:Depends}
+
+                Package: foo-doc
+                Architecture: all
+                Description: foo documentation
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn should_merge_list_items() {
+        let ours = BASE.replace(
+            " libfoo-dev (>= 1),\n",
+            " libfoo-dev (>= 2),\n libqux-dev,\n",
+        );
+        let theirs = BASE
+            .replace(" libbar-dev,\n", "")
+            .replace(
+                " debhelper-compat (= 13),\n",
+                " debhelper-compat (= 13),\n dh-python,\n",
+            )
+            .replace("${misc:Depends}, ", "");
+        let result = merge_str(&ours, &theirs);
+        assert!(result.is_clean());
+        let paragraph = result.document.paragraphs().next().unwrap();
+        assert_eq!(
+            paragraph.get("Build-Depends"),
+            Some("\ndebhelper-compat (= 13),\ndh-python,\nlibfoo-dev (>= 2),\nlibqux-dev,")
+        );
+        let paragraph = result.document.paragraphs().nth(1).unwrap();
+        assert_eq!(paragraph.get("Depends"), Some("${shlibs:Depends}"));
+    }
+
+    #[test]
+    fn should_report_conflicts() {
+        let ours = BASE
+            .replace("4.6.2", "4.7.0")
+            .replace("libfoo-dev (>= 1)", "libfoo-dev (>= 2)");
+        let theirs = BASE
+            .replace("4.6.2", "4.6.3")
+            .replace("libfoo-dev (>= 1)", "libfoo-dev (>= 3)");
+        let result = merge_str(&ours, &theirs);
+        assert_eq!(
+            result.conflicts,
+            vec![
+                MergeConflict {
+                    paragraph: "foo".to_string(),
+                    field: Some("Build-Depends".to_string()),
+                    base: Some(
+                        "\ndebhelper-compat (= 13),\nlibbar-dev,\nlibfoo-dev (>= 1),".to_string()
+                    ),
+                    ours: Some(
+                        "\ndebhelper-compat (= 13),\nlibbar-dev,\nlibfoo-dev (>= 2),".to_string()
+                    ),
+                    theirs: Some(
+                        "\ndebhelper-compat (= 13),\nlibbar-dev,\nlibfoo-dev (>= 3),".to_string()
+                    ),
+                },
+                MergeConflict {
+                    paragraph: "foo".to_string(),
+                    field: Some("Standards-Version".to_string()),
+                    base: Some("4.6.2".to_string()),
+                    ours: Some("4.7.0".to_string()),
+                    theirs: Some("4.6.3".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            result.conflicts[1].to_string(),
+            "conflicting changes to Standards-Version in foo: ours '4.7.0', theirs '4.6.3'"
+        );
+        assert_eq!(result.document.to_string(), ours);
+    }
+
+    #[test]
+    fn should_match_binary_paragraphs_after_source_rename() {
+        let ours = BASE.replace("Source: foo\n", "Source: foo2\n");
+        let theirs = BASE.replace("Architecture: any", "Architecture: linux-any");
+        let result = merge_str(&ours, &theirs);
+        assert_eq!(result.conflicts, vec![]);
+        assert_eq!(
+            result.document.to_string(),
+            ours.replace("Architecture: any", "Architecture: linux-any")
+        );
+    }
+
+    #[test]
+    fn should_merge_added_and_removed_paragraphs() {
+        let ours = BASE.replace(
+            "\nPackage: foo-doc\nArchitecture: all\nDescription: foo documentation\n",
+            "",
+        );
+        let theirs = format!("{}\nPackage: foo-dbg\nArchitecture: any\n", BASE);
+        let result = merge_str(&ours, &theirs);
+        assert!(result.is_clean());
+        assert_eq!(
+            result
+                .document
+                .paragraphs()
+                .map(|paragraph| paragraph.get("Package").unwrap_or_default())
+                .collect::<Vec<_>>(),
+            vec!["", "foo", "foo-dbg"]
+        );
+
+        // They changed the paragraph we removed.
+        let theirs = BASE.replace("foo documentation", "documentation for foo");
+        let result = merge_str(&ours, &theirs);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].field, None);
+        assert_eq!(
+            result.conflicts[0].to_string(),
+            "paragraph foo-doc was removed by us, but changed by the other side"
+        );
+        assert_eq!(result.document.to_string(), ours);
+
+        // We changed the paragraph they removed.
+        let result = merge_str(&theirs, &ours);
+        assert_eq!(
+            result.conflicts[0].to_string(),
+            "paragraph foo-doc was removed by them, but changed by the other side"
+        );
+    }
+}
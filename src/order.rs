@@ -0,0 +1,355 @@
+//! Canonical field ordering, as used by dpkg and APT when writing control files.
+
+use crate::{OwnedParagraph, Paragraph};
+
+/// The order of fields in binary package indexes, from `TFRewritePackageOrder` in APT 2.6.
+const BINARY_INDEX_ORDER: &[&str] = &[
+    "Package",
+    "Package-Type",
+    "Architecture",
+    "Subarchitecture",
+    "Version",
+    "Kernel-Version",
+    "Built-Using",
+    "Static-Built-Using",
+    "Built-For-Profiles",
+    "Auto-Built-Package",
+    "Multi-Arch",
+    "Status",
+    "Priority",
+    "Build-Essential",
+    "Protected",
+    "Important",
+    "Essential",
+    "Installer-Menu-Item",
+    "Section",
+    "Source",
+    "Origin",
+    "Phased-Update-Percentage",
+    "Maintainer",
+    "Original-Maintainer",
+    "Bugs",
+    "Config-Version",
+    "Conffiles",
+    "Triggers-Awaited",
+    "Triggers-Pending",
+    "Installed-Size",
+    "Provides",
+    "Pre-Depends",
+    "Depends",
+    "Recommends",
+    "Suggests",
+    "Conflicts",
+    "Breaks",
+    "Replaces",
+    "Enhances",
+    "Filename",
+    "MSDOS-Filename",
+    "Size",
+    "MD5sum",
+    "SHA1",
+    "SHA256",
+    "SHA512",
+    "Homepage",
+    "Description",
+    "Description-md5",
+    "Tag",
+    "Task",
+];
+
+/// The order of fields in source package indexes, from `TFRewriteSourceOrder` in APT 2.6.
+const SOURCE_INDEX_ORDER: &[&str] = &[
+    "Package",
+    "Source",
+    "Format",
+    "Binary",
+    "Architecture",
+    "Version",
+    "Priority",
+    "Section",
+    "Origin",
+    "Maintainer",
+    "Original-Maintainer",
+    "Uploaders",
+    "Dm-Upload-Allowed",
+    "Standards-Version",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+    "Testsuite",
+    "Testsuite-Triggers",
+    "Homepage",
+    "Description",
+    "Vcs-Browser",
+    "Vcs-Browse",
+    "Vcs-Arch",
+    "Vcs-Bzr",
+    "Vcs-Cvs",
+    "Vcs-Darcs",
+    "Vcs-Git",
+    "Vcs-Hg",
+    "Vcs-Mtn",
+    "Vcs-Svn",
+    "Directory",
+    "Package-List",
+    "Files",
+    "Checksums-Md5",
+    "Checksums-Sha1",
+    "Checksums-Sha256",
+    "Checksums-Sha512",
+];
+
+/// The order of fields in the dpkg status database, from `fieldinfos` in dpkg 1.21.
+const STATUS_ORDER: &[&str] = &[
+    "Package",
+    "Essential",
+    "Protected",
+    "Status",
+    "Priority",
+    "Section",
+    "Installed-Size",
+    "Origin",
+    "Maintainer",
+    "Bugs",
+    "Architecture",
+    "Multi-Arch",
+    "Source",
+    "Version",
+    "Config-Version",
+    "Replaces",
+    "Provides",
+    "Depends",
+    "Pre-Depends",
+    "Recommends",
+    "Suggests",
+    "Breaks",
+    "Conflicts",
+    "Enhances",
+    "Conffiles",
+    "Filename",
+    "Size",
+    "MD5sum",
+    "MSDOS-Filename",
+    "Description",
+    "Triggers-Pending",
+    "Triggers-Awaited",
+];
+
+/// The order of fields in `DEBIAN/control`, from `field_ordered_list(CTRL_PKG_DEB)` in
+/// `Dpkg::Control::FieldsCore` of dpkg 1.21.
+const CONTROL_ORDER: &[&str] = &[
+    "Package",
+    "Package-Type",
+    "Source",
+    "Version",
+    "Kernel-Version",
+    "Built-For-Profiles",
+    "Auto-Built-Package",
+    "Architecture",
+    "Subarchitecture",
+    "Installer-Menu-Item",
+    "Build-Essential",
+    "Essential",
+    "Protected",
+    "Origin",
+    "Bugs",
+    "Maintainer",
+    "Installed-Size",
+    "Pre-Depends",
+    "Depends",
+    "Recommends",
+    "Suggests",
+    "Enhances",
+    "Conflicts",
+    "Breaks",
+    "Replaces",
+    "Provides",
+    "Built-Using",
+    "Static-Built-Using",
+    "Section",
+    "Priority",
+    "Multi-Arch",
+    "Homepage",
+    "Description",
+    "Tag",
+    "Task",
+];
+
+/// A kind of control file with a canonical field order.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FieldOrder {
+    /// A `Packages` index, in the order written by `apt-ftparchive`.
+    BinaryIndex,
+    /// A `Sources` index, in the order written by `apt-ftparchive`.
+    SourceIndex,
+    /// The dpkg status database (`/var/lib/dpkg/status`), in the order written by dpkg.
+    Status,
+    /// The control file of a binary package (`DEBIAN/control`), in the order written by
+    /// `dpkg-gencontrol`.
+    Control,
+}
+
+impl FieldOrder {
+    /// Return the known fields in their canonical order.
+    pub fn fields(self) -> &'static [&'static str] {
+        match self {
+            FieldOrder::BinaryIndex => BINARY_INDEX_ORDER,
+            FieldOrder::SourceIndex => SOURCE_INDEX_ORDER,
+            FieldOrder::Status => STATUS_ORDER,
+            FieldOrder::Control => CONTROL_ORDER,
+        }
+    }
+
+    /// Return the position of a field in the canonical order, or `None` if the field is unknown.
+    ///
+    /// Field names are compared case-insensitively.
+    pub fn position(self, name: &str) -> Option<usize> {
+        self.fields()
+            .iter()
+            .position(|field| field.eq_ignore_ascii_case(name))
+    }
+
+    /// Return the key to sort a field by. Unknown fields sort after all known fields.
+    fn sort_key(self, name: &str) -> usize {
+        self.position(name).unwrap_or(usize::MAX)
+    }
+}
+
+impl Paragraph<'_> {
+    /// Reorder the fields into the canonical order for the given kind of file.
+    ///
+    /// Known fields are sorted by their position in the ordering table of dpkg or APT. Unknown
+    /// fields are moved to the end, but keep their order relative to each other, as do repeated
+    /// fields.
+    ///
+    /// # Example
+    /// ```
+    /// # use oma_debcontrol::{parse_str, FieldOrder};
+    /// let mut paragraphs = parse_str("\
+    /// Description: a tool
+    /// X-Custom: 1
+    /// Version: 1.0
+    /// Package: foo
+    /// Architecture: amd64
+    /// ").unwrap();
+    /// paragraphs[0].canonicalize(FieldOrder::BinaryIndex);
+    ///
+    /// let names = paragraphs[0].fields.iter().map(|field| field.name).collect::<Vec<_>>();
+    /// assert_eq!(names, ["Package", "Architecture", "Version", "Description", "X-Custom"]);
+    /// ```
+    pub fn canonicalize(&mut self, order: FieldOrder) {
+        self.fields.sort_by_key(|field| order.sort_key(field.name));
+    }
+}
+
+impl OwnedParagraph {
+    /// Reorder the fields into the canonical order for the given kind of file.
+    ///
+    /// See [`Paragraph::canonicalize`](struct.Paragraph.html#method.canonicalize).
+    pub fn canonicalize(&mut self, order: FieldOrder) {
+        self.fields.sort_by_key(|field| order.sort_key(&field.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::vec::Vec;
+    use indoc::indoc;
+
+    const PARAGRAPH: &str = indoc!(
+        "
+        Description: a tool
+        x-b: 1
+        Depends: libc6
+        Maintainer: Jane Doe <jane@example.org>
+        X-A: 2
+        version: 1.0
+        Status: install ok installed
+        Installed-Size: 12
+        Package: foo
+        "
+    );
+
+    fn canonical_names(order: FieldOrder) -> Vec<&'static str> {
+        let mut paragraph = parse_str(PARAGRAPH).unwrap().remove(0);
+        paragraph.canonicalize(order);
+        paragraph.fields.iter().map(|field| field.name).collect()
+    }
+
+    #[test]
+    fn should_order_fields_by_file_kind() {
+        assert_eq!(
+            canonical_names(FieldOrder::BinaryIndex),
+            [
+                "Package",
+                "version",
+                "Status",
+                "Maintainer",
+                "Installed-Size",
+                "Depends",
+                "Description",
+                "x-b",
+                "X-A",
+            ]
+        );
+        assert_eq!(
+            canonical_names(FieldOrder::Status),
+            [
+                "Package",
+                "Status",
+                "Installed-Size",
+                "Maintainer",
+                "version",
+                "Depends",
+                "Description",
+                "x-b",
+                "X-A",
+            ]
+        );
+        assert_eq!(
+            canonical_names(FieldOrder::Control),
+            [
+                "Package",
+                "version",
+                "Maintainer",
+                "Installed-Size",
+                "Depends",
+                "Description",
+                "x-b",
+                "X-A",
+                "Status",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_keep_unknown_and_repeated_fields_stable() {
+        let mut paragraph = OwnedParagraph::from(
+            parse_str("B: 1\nSource: foo\nA: 2\nBinary: foo\nB: 3\nPackage: foo\n")
+                .unwrap()
+                .remove(0),
+        );
+        paragraph.canonicalize(FieldOrder::SourceIndex);
+        let fields = paragraph
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("Package", "foo"),
+                ("Source", "foo"),
+                ("Binary", "foo"),
+                ("B", "1"),
+                ("A", "2"),
+                ("B", "3"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{Field, Paragraph};
+use alloc::{string::String, vec::Vec};
+
+/// A single field in a control file that owns its name.
+///
+/// [`Field`](struct.Field.html) borrows its name from the parser input. This type is useful when
+/// paragraphs need to outlive their input, e.g. when reading from a
+/// [`BufParse`](struct.BufParse.html) that reuses its buffer.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct OwnedField {
+    pub name: String,
+    pub value: String,
+}
+
+/// A paragraph in a control file that owns all of its data.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct OwnedParagraph {
+    pub fields: Vec<OwnedField>,
+}
+
+impl From<Field<'_>> for OwnedField {
+    fn from(field: Field<'_>) -> Self {
+        OwnedField {
+            name: String::from(field.name),
+            value: field.value,
+        }
+    }
+}
+
+impl From<Paragraph<'_>> for OwnedParagraph {
+    fn from(paragraph: Paragraph<'_>) -> Self {
+        OwnedParagraph {
+            fields: paragraph.fields.into_iter().map(OwnedField::from).collect(),
+        }
+    }
+}
+
+impl OwnedField {
+    /// Return a borrowed [`Field`](struct.Field.html) with the same name and value.
+    pub fn as_field(&self) -> Field<'_> {
+        Field {
+            name: &self.name,
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl OwnedParagraph {
+    /// Create a new `OwnedParagraph` from the given fields.
+    pub fn new(fields: Vec<OwnedField>) -> Self {
+        OwnedParagraph { fields }
+    }
+
+    /// Return a borrowed [`Paragraph`](struct.Paragraph.html) with the same fields.
+    pub fn as_paragraph(&self) -> Paragraph<'_> {
+        Paragraph::new(self.fields.iter().map(OwnedField::as_field).collect())
+    }
+
+    /// Return the first field with the given name.
+    ///
+    /// Field names are compared case-insensitively, as required by the Debian policy.
+    pub fn field(&self, name: &str) -> Option<&OwnedField> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Return a mutable reference to the first field with the given name.
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut OwnedField> {
+        self.fields
+            .iter_mut()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Return the value of the first field with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.field(name).map(|field| field.value.as_str())
+    }
+
+    /// Set the value of a field.
+    ///
+    /// If a field with this name already exists, its value is replaced in place so the field order
+    /// is kept. Otherwise, a new field is appended to the paragraph.
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.field_mut(name) {
+            Some(field) => field.value = value,
+            None => self.fields.push(OwnedField {
+                name: String::from(name),
+                value,
+            }),
+        }
+    }
+
+    /// Remove the first field with the given name and return it.
+    pub fn remove(&mut self, name: &str) -> Option<OwnedField> {
+        let pos = self
+            .fields
+            .iter()
+            .position(|field| field.name.eq_ignore_ascii_case(name))?;
+        Some(self.fields.remove(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::field;
+    use alloc::vec;
+
+    fn owned(name: &str, value: &str) -> OwnedField {
+        OwnedField {
+            name: String::from(name),
+            value: String::from(value),
+        }
+    }
+
+    #[test]
+    fn should_convert_paragraph_into_owned_paragraph() {
+        let paragraph = Paragraph::new(vec![field("a", "1"), field("b", "2\n3")]);
+        let result = OwnedParagraph::from(paragraph);
+        assert_eq!(result.fields, vec![owned("a", "1"), owned("b", "2\n3")]);
+    }
+
+    #[test]
+    fn should_borrow_owned_paragraph_as_paragraph() {
+        let paragraph = OwnedParagraph::new(vec![owned("a", "1")]);
+        assert_eq!(
+            paragraph.as_paragraph(),
+            Paragraph::new(vec![field("a", "1")])
+        );
+    }
+
+    #[test]
+    fn should_look_up_fields_case_insensitively() {
+        let paragraph = OwnedParagraph::new(vec![owned("Package", "foo")]);
+        assert_eq!(paragraph.get("package"), Some("foo"));
+        assert_eq!(paragraph.get("PACKAGE"), Some("foo"));
+        assert_eq!(paragraph.get("Version"), None);
+    }
+
+    #[test]
+    fn should_replace_existing_field_in_place() {
+        let mut paragraph = OwnedParagraph::new(vec![owned("a", "1"), owned("b", "2")]);
+        paragraph.set("A", "3");
+        paragraph.set("c", "4");
+        assert_eq!(
+            paragraph.fields,
+            vec![owned("a", "3"), owned("b", "2"), owned("c", "4")]
+        );
+    }
+
+    #[test]
+    fn should_remove_field() {
+        let mut paragraph = OwnedParagraph::new(vec![owned("a", "1"), owned("b", "2")]);
+        assert_eq!(paragraph.remove("a"), Some(owned("a", "1")));
+        assert_eq!(paragraph.remove("a"), None);
+        assert_eq!(paragraph.fields, vec![owned("b", "2")]);
+    }
+}
@@ -216,7 +216,9 @@ macro_rules! parsers {
             /// This parser first consumes any leading comment and blank lines, followed by one or
             /// more field definitions, followed by either a blank line or (for complete parsers)
             /// EOF.
-            pub(crate) fn paragraph<'a, E>(input: &'a str) -> IResult<&'a str, Option<Paragraph>, E>
+            pub(crate) fn paragraph<'a, E>(
+                input: &'a str,
+            ) -> IResult<&'a str, Option<Paragraph<'a>>, E>
             where
                 E: ParseError<&'a str> + nom::error::ContextError<&'a str>,
             {
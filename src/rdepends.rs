@@ -0,0 +1,385 @@
+//! An index of reverse dependencies for impact analysis.
+
+use crate::{parse_relations, OwnedParagraph, Paragraph, RelationError, VersionOperator};
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+/// A relationship field that creates edges in a
+/// [`ReverseDependencies`](struct.ReverseDependencies.html) index.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum DependencyField {
+    PreDepends,
+    Depends,
+    Recommends,
+    BuildDepends,
+    BuildDependsArch,
+    BuildDependsIndep,
+}
+
+impl DependencyField {
+    /// All fields, in the order they are indexed.
+    pub const ALL: &'static [DependencyField] = &[
+        DependencyField::PreDepends,
+        DependencyField::Depends,
+        DependencyField::Recommends,
+        DependencyField::BuildDepends,
+        DependencyField::BuildDependsArch,
+        DependencyField::BuildDependsIndep,
+    ];
+
+    /// Return the field name.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DependencyField::PreDepends => "Pre-Depends",
+            DependencyField::Depends => "Depends",
+            DependencyField::Recommends => "Recommends",
+            DependencyField::BuildDepends => "Build-Depends",
+            DependencyField::BuildDependsArch => "Build-Depends-Arch",
+            DependencyField::BuildDependsIndep => "Build-Depends-Indep",
+        }
+    }
+
+    /// Return true if this is a build dependency field of a source package.
+    pub fn is_build_dependency(self) -> bool {
+        matches!(
+            self,
+            DependencyField::BuildDepends
+                | DependencyField::BuildDependsArch
+                | DependencyField::BuildDependsIndep
+        )
+    }
+}
+
+impl fmt::Display for DependencyField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A dependency of one package on another.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ReverseEdge {
+    /// The depending package. For build dependencies, this is the source package.
+    pub package: String,
+    pub field: DependencyField,
+    /// The package name the relation refers to, which may be a virtual package.
+    pub target: String,
+    /// The relation that created the edge, as written in the field.
+    pub relation: String,
+    /// The version constraint of the relation.
+    pub version: Option<(VersionOperator, String)>,
+    /// True if the relation is one of several alternatives, so the depending package may not
+    /// actually need the target.
+    pub alternative: bool,
+}
+
+impl fmt::Display for ReverseEdge {
+    /// Write the edge as `package Field: relation`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.package, self.field, self.relation)?;
+        if self.alternative {
+            f.write_str(" (alternative)")?;
+        }
+        Ok(())
+    }
+}
+
+/// An index from packages to the packages that depend on them.
+///
+/// The index is built incrementally from binary and source paragraphs, such as those of `Packages`
+/// and `Sources` files. `Pre-Depends`, `Depends` and `Recommends` of binary packages and the
+/// `Build-Depends*` fields of source packages create edges. Querying a package also returns the
+/// edges on the virtual packages it `Provides`.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{parse_str, DependencyField, ReverseDependencies};
+/// let paragraphs = parse_str("\
+/// Package: libfoo1
+/// Provides: libfoo-abi-1
+///
+/// Package: foo
+/// Depends: libfoo1 (>= 1.2)
+///
+/// Package: bar
+/// Recommends: foo | baz, libfoo-abi-1
+///
+/// Package: foo
+/// Build-Depends: bar
+/// ").unwrap();
+/// let mut index = ReverseDependencies::new();
+/// for paragraph in &paragraphs {
+///     index.insert(paragraph).unwrap();
+/// }
+///
+/// let direct = index.direct("libfoo1");
+/// assert_eq!(direct.len(), 2);
+/// assert_eq!(direct[0].to_string(), "foo Depends: libfoo1 (>= 1.2)");
+/// assert_eq!(direct[1].to_string(), "bar Recommends: libfoo-abi-1");
+///
+/// let transitive = index.transitive("libfoo1", DependencyField::ALL);
+/// let names = transitive.iter().map(|(depth, edge)| (*depth, edge.package.as_str()));
+/// assert_eq!(names.collect::<Vec<_>>(), vec![(1, "foo"), (1, "bar"), (2, "foo")]);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ReverseDependencies {
+    /// Edges by target name.
+    edges: BTreeMap<String, Vec<ReverseEdge>>,
+    /// The names each binary package provides.
+    provides: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ReverseDependencies {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the relationships of a paragraph to the index.
+    ///
+    /// The depending package is named by the `Package` field, or by the `Source` field for
+    /// paragraphs without one, such as the source paragraph of `debian/control`. Paragraphs with
+    /// neither are ignored.
+    pub fn insert(&mut self, paragraph: &Paragraph<'_>) -> Result<(), RelationError> {
+        self.insert_fields(|name| paragraph.get(name))
+    }
+
+    /// Add the relationships of an owned paragraph to the index, as read by
+    /// [`BufParse::next_owned`](struct.BufParse.html#method.next_owned).
+    pub fn insert_owned(&mut self, paragraph: &OwnedParagraph) -> Result<(), RelationError> {
+        self.insert_fields(|name| paragraph.get(name))
+    }
+
+    fn insert_fields<'a>(
+        &mut self,
+        get: impl Fn(&str) -> Option<&'a str>,
+    ) -> Result<(), RelationError> {
+        let package = match get("Package").or_else(|| get("Source")) {
+            Some(package) => package.trim(),
+            None => return Ok(()),
+        };
+        // Parse everything first, so that an error leaves the index unchanged.
+        let provides = get("Provides").map(parse_relations).transpose()?;
+        let mut fields = Vec::new();
+        for field in DependencyField::ALL {
+            if let Some(value) = get(field.as_str()) {
+                fields.push((*field, parse_relations(value)?));
+            }
+        }
+
+        if let Some(provides) = provides {
+            let names = self.provides.entry(package.to_string()).or_default();
+            for relation in provides.iter().flatten() {
+                names.insert(relation.name.to_string());
+            }
+        }
+        for (field, relations) in fields {
+            for alternatives in relations {
+                for relation in &alternatives {
+                    self.edges
+                        .entry(relation.name.to_string())
+                        .or_default()
+                        .push(ReverseEdge {
+                            package: package.to_string(),
+                            field,
+                            target: relation.name.to_string(),
+                            relation: relation.to_string(),
+                            version: relation
+                                .version
+                                .map(|version| (version.operator, version.version.to_string())),
+                            alternative: alternatives.len() > 1,
+                        });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the edges of packages that depend on the given package, directly or through a
+    /// virtual package it provides.
+    pub fn direct(&self, package: &str) -> Vec<&ReverseEdge> {
+        let provided = self.provides.get(package).into_iter().flatten();
+        core::iter::once(package)
+            .chain(provided.map(String::as_str))
+            .filter_map(|name| self.edges.get(name))
+            .flatten()
+            .collect()
+    }
+
+    /// Return the packages that depend on the given package directly or indirectly through the
+    /// given fields, with their distance from the package.
+    ///
+    /// Packages are listed in breadth-first order, each with the first edge found for it, which
+    /// lies on a shortest dependency path. Build dependencies end a path, since source packages
+    /// can't be depended on.
+    pub fn transitive(
+        &self,
+        package: &str,
+        fields: &[DependencyField],
+    ) -> Vec<(usize, &ReverseEdge)> {
+        let mut result = Vec::new();
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((package, 0));
+        while let Some((current, depth)) = queue.pop_front() {
+            for edge in self.direct(current) {
+                if !fields.contains(&edge.field) {
+                    continue;
+                }
+                let build = edge.field.is_build_dependency();
+                if !seen.insert((edge.package.as_str(), build)) {
+                    continue;
+                }
+                result.push((depth + 1, edge));
+                if !build {
+                    queue.push_back((&edge.package, depth + 1));
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::vec;
+    use indoc::indoc;
+
+    const PARAGRAPHS: &str = indoc!(
+        "
+        Package: libc6
+        Version: 2.36-9
+
+        Package: libfoo1
+        Provides: libfoo-abi-1 (= 1.2)
+        Pre-Depends: libc6 (>= 2.34)
+
+        Package: foo
+        Depends: libfoo1 (>= 1.2), libc6
+        Recommends: foo-data
+
+        Package: foo-data
+        Depends: foo
+
+        Source: foo
+        Build-Depends: libc6-dev | libc-dev, debhelper-compat (= 13)
+
+        Package: libc6
+        Build-Depends-Arch: gcc-12 [amd64]
+        "
+    );
+
+    fn index() -> ReverseDependencies {
+        let mut index = ReverseDependencies::new();
+        for paragraph in parse_str(PARAGRAPHS).unwrap() {
+            index.insert(&paragraph).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn should_record_relation_of_each_edge() {
+        let index = index();
+        assert_eq!(
+            index.direct("libc6"),
+            vec![
+                &ReverseEdge {
+                    package: "libfoo1".to_string(),
+                    field: DependencyField::PreDepends,
+                    target: "libc6".to_string(),
+                    relation: "libc6 (>= 2.34)".to_string(),
+                    version: Some((VersionOperator::LaterOrEqual, "2.34".to_string())),
+                    alternative: false,
+                },
+                &ReverseEdge {
+                    package: "foo".to_string(),
+                    field: DependencyField::Depends,
+                    target: "libc6".to_string(),
+                    relation: "libc6".to_string(),
+                    version: None,
+                    alternative: false,
+                },
+            ]
+        );
+        let build = index.direct("libc-dev");
+        assert_eq!(build.len(), 1);
+        assert_eq!(
+            build[0].to_string(),
+            "foo Build-Depends: libc-dev (alternative)"
+        );
+        assert_eq!(
+            index.direct("gcc-12")[0].to_string(),
+            "libc6 Build-Depends-Arch: gcc-12 [amd64]"
+        );
+        assert!(index.direct("unknown").is_empty());
+    }
+
+    #[test]
+    fn should_include_virtual_packages() {
+        let mut index = index();
+        let paragraphs = parse_str("Package: bar\nDepends: libfoo-abi-1\n").unwrap();
+        index.insert(&paragraphs[0]).unwrap();
+        let edges = index.direct("libfoo1");
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[1].target, "libfoo-abi-1");
+        assert_eq!(edges[1].package, "bar");
+    }
+
+    #[test]
+    fn should_follow_dependencies_transitively() {
+        let index = index();
+        let transitive = |fields| {
+            index
+                .transitive("libc6", fields)
+                .into_iter()
+                .map(|(depth, edge)| (depth, edge.package.as_str(), edge.field))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            transitive(DependencyField::ALL),
+            vec![
+                (1, "libfoo1", DependencyField::PreDepends),
+                (1, "foo", DependencyField::Depends),
+                (2, "foo-data", DependencyField::Depends),
+            ]
+        );
+        assert_eq!(
+            transitive(&[DependencyField::PreDepends]),
+            vec![(1, "libfoo1", DependencyField::PreDepends)]
+        );
+        assert_eq!(
+            index
+                .transitive("debhelper-compat", DependencyField::ALL)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn should_index_owned_paragraphs() {
+        let mut index = ReverseDependencies::new();
+        let paragraph =
+            OwnedParagraph::from(parse_str("Package: a\nDepends: b\n").unwrap().remove(0));
+        index.insert_owned(&paragraph).unwrap();
+        assert_eq!(index.direct("b")[0].package, "a");
+        let invalid =
+            OwnedParagraph::from(parse_str("Package: a\nDepends: (b\n").unwrap().remove(0));
+        assert!(index.insert_owned(&invalid).is_err());
+    }
+
+    #[test]
+    fn should_leave_index_unchanged_on_error() {
+        let mut index = index();
+        let before = index.clone();
+        let paragraphs =
+            parse_str("Package: bar\nProvides: baz\nDepends: libc6\nRecommends: (qux\n").unwrap();
+        assert!(index.insert(&paragraphs[0]).is_err());
+        assert_eq!(index, before);
+    }
+}
@@ -0,0 +1,626 @@
+//! Parsing of package relationship fields such as `Depends` and `Build-Depends`.
+//!
+//! A relationship field is a comma-separated list of relationships, each of which is a list of
+//! alternatives separated by `|`. Every alternative names a package, optionally followed by an
+//! architecture qualifier, a version constraint, an architecture restriction list and build
+//! profile restriction formulas:
+//!
+//! ```text
+//! libfoo-dev:native (>= 1.2) [linux-any] <!nocheck> <!nodoc cross>
+//! ```
+//!
+//! See [Debian Policy 7.1](https://www.debian.org/doc/debian-policy/ch-relationships.html) and the
+//! [BuildProfileSpec](https://wiki.debian.org/BuildProfileSpec).
+
+use crate::{arch, Field, OwnedField};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till, take_till1, take_until, take_while1},
+    character::complete::{char, multispace0},
+    combinator::{all_consuming, map, opt, recognize},
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// A relationship that couldn't be parsed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RelationError {
+    /// The offending relationship, i.e. the comma-separated item that contains the error.
+    pub relation: String,
+}
+
+impl fmt::Display for RelationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid relationship '{}'", self.relation)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RelationError {}
+
+/// The operator of a version constraint.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum VersionOperator {
+    /// `<<`
+    Earlier,
+    /// `<=`, or the obsolete `<`
+    EarlierOrEqual,
+    /// `=`
+    Equal,
+    /// `>=`, or the obsolete `>`
+    LaterOrEqual,
+    /// `>>`
+    Later,
+}
+
+impl VersionOperator {
+    /// Return the operator as written in relationship fields.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VersionOperator::Earlier => "<<",
+            VersionOperator::EarlierOrEqual => "<=",
+            VersionOperator::Equal => "=",
+            VersionOperator::LaterOrEqual => ">=",
+            VersionOperator::Later => ">>",
+        }
+    }
+}
+
+impl fmt::Display for VersionOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A version constraint, as in `(>= 1.2)`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct VersionConstraint<'a> {
+    pub operator: VersionOperator,
+    pub version: &'a str,
+}
+
+impl fmt::Display for VersionConstraint<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.operator, self.version)
+    }
+}
+
+/// A term of a build profile restriction formula, such as `nocheck` or `!nodoc`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct ProfileTerm<'a> {
+    pub name: &'a str,
+    pub negated: bool,
+}
+
+impl ProfileTerm<'_> {
+    /// Return true if the term holds for the given active build profiles.
+    pub fn evaluate(&self, profiles: &[&str]) -> bool {
+        profiles.contains(&self.name) != self.negated
+    }
+}
+
+impl fmt::Display for ProfileTerm<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            f.write_str("!")?;
+        }
+        f.write_str(self.name)
+    }
+}
+
+/// A single alternative of a relationship.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct Relation<'a> {
+    /// The package name. Substitution variables such as `${misc:Depends}` are kept as names.
+    pub name: &'a str,
+    /// The architecture qualifier, as in `python3:any` or `gcc:native`.
+    pub arch_qualifier: Option<&'a str>,
+    pub version: Option<VersionConstraint<'a>>,
+    /// The architecture restriction list, as in `[linux-any !hurd-i386]`, including the `!` of
+    /// negated entries. An empty list doesn't restrict the relation.
+    pub architectures: Vec<&'a str>,
+    /// The build profile restriction formula: the relation is active if all terms of any of the
+    /// lists hold. An empty formula doesn't restrict the relation.
+    pub profiles: Vec<Vec<ProfileTerm<'a>>>,
+}
+
+impl Relation<'_> {
+    /// Return true if the architecture restriction list allows the given host architecture.
+    pub fn matches_arch(&self, arch: &str) -> bool {
+        let negated = self
+            .architectures
+            .first()
+            .is_some_and(|entry| entry.starts_with('!'));
+        self.architectures.is_empty()
+            || self
+                .architectures
+                .iter()
+                .any(|entry| arch::matches(entry.trim_start_matches('!'), arch))
+                != negated
+    }
+
+    /// Return true if the build profile restriction formula holds for the given active build
+    /// profiles.
+    pub fn matches_profiles(&self, profiles: &[&str]) -> bool {
+        self.profiles.is_empty()
+            || self
+                .profiles
+                .iter()
+                .any(|terms| terms.iter().all(|term| term.evaluate(profiles)))
+    }
+
+    /// Return true if the relation applies in the given build environment.
+    pub fn is_active(&self, context: &BuildContext<'_>) -> bool {
+        self.matches_arch(context.host_arch) && self.matches_profiles(&context.profiles)
+    }
+}
+
+impl fmt::Display for Relation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        if let Some(qualifier) = self.arch_qualifier {
+            write!(f, ":{}", qualifier)?;
+        }
+        if let Some(version) = &self.version {
+            write!(f, " ({})", version)?;
+        }
+        if !self.architectures.is_empty() {
+            write!(f, " [{}]", self.architectures.join(" "))?;
+        }
+        for terms in &self.profiles {
+            f.write_str(" <")?;
+            for (i, term) in terms.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                write!(f, "{}", term)?;
+            }
+            f.write_str(">")?;
+        }
+        Ok(())
+    }
+}
+
+/// The alternatives of a relationship, such as `grub-pc | extlinux`.
+pub type Alternatives<'a> = Vec<Relation<'a>>;
+
+/// Write relationships the way they appear in a field value, separated by `, ` and ` | `.
+pub fn write_relations(f: &mut impl fmt::Write, relations: &[Alternatives<'_>]) -> fmt::Result {
+    for (i, alternatives) in relations.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        for (j, relation) in alternatives.iter().enumerate() {
+            if j > 0 {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{}", relation)?;
+        }
+    }
+    Ok(())
+}
+
+/// Return true if this character is valid in a package name.
+///
+/// Policy only allows lowercase letters, digits, `+`, `-` and `.`, but dpkg is more lenient.
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "+-._".contains(c)
+}
+
+/// Parse a package name or a substitution variable.
+fn package_name(input: &str) -> IResult<&str, &str> {
+    alt((
+        recognize(tuple((tag("${"), take_until("}"), char('}')))),
+        take_while1(is_name_char),
+    ))(input)
+}
+
+/// Parse the operator of a version constraint.
+fn version_operator(input: &str) -> IResult<&str, VersionOperator> {
+    alt((
+        map(tag("<<"), |_| VersionOperator::Earlier),
+        map(tag("<="), |_| VersionOperator::EarlierOrEqual),
+        map(tag(">>"), |_| VersionOperator::Later),
+        map(tag(">="), |_| VersionOperator::LaterOrEqual),
+        map(tag("="), |_| VersionOperator::Equal),
+        map(tag("<"), |_| VersionOperator::EarlierOrEqual),
+        map(tag(">"), |_| VersionOperator::LaterOrEqual),
+    ))(input)
+}
+
+/// Parse a parenthesized version constraint.
+fn version_constraint(input: &str) -> IResult<&str, VersionConstraint<'_>> {
+    map(
+        delimited(
+            pair(char('('), multispace0),
+            pair(
+                version_operator,
+                preceded(
+                    multispace0,
+                    take_till1(|c: char| c == ')' || c.is_whitespace()),
+                ),
+            ),
+            pair(multispace0, char(')')),
+        ),
+        |(operator, version)| VersionConstraint { operator, version },
+    )(input)
+}
+
+/// Parse the contents of a bracketed list, split into its whitespace-separated entries.
+fn bracketed(open: char, close: char) -> impl Fn(&str) -> IResult<&str, Vec<&str>> {
+    move |input| {
+        map(
+            delimited(char(open), take_till(|c| c == close), char(close)),
+            |list: &str| list.split_whitespace().collect(),
+        )(input)
+    }
+}
+
+/// Parse a single alternative.
+fn relation(input: &str) -> IResult<&str, Relation<'_>> {
+    map(
+        tuple((
+            package_name,
+            opt(preceded(char(':'), take_while1(is_name_char))),
+            opt(preceded(multispace0, version_constraint)),
+            opt(preceded(multispace0, bracketed('[', ']'))),
+            many0(preceded(multispace0, bracketed('<', '>'))),
+        )),
+        |(name, arch_qualifier, version, architectures, profiles)| Relation {
+            name,
+            arch_qualifier,
+            version,
+            architectures: architectures.unwrap_or_default(),
+            profiles: profiles
+                .into_iter()
+                .map(|terms| {
+                    terms
+                        .into_iter()
+                        .map(|term| match term.strip_prefix('!') {
+                            Some(name) => ProfileTerm {
+                                name,
+                                negated: true,
+                            },
+                            None => ProfileTerm {
+                                name: term,
+                                negated: false,
+                            },
+                        })
+                        .collect()
+                })
+                .collect(),
+        },
+    )(input)
+}
+
+/// Parse a relationship with all of its alternatives.
+fn alternatives(input: &str) -> IResult<&str, Alternatives<'_>> {
+    let (input, first) = preceded(multispace0, relation)(input)?;
+    let (input, mut rest) = terminated(
+        many0(preceded(
+            delimited(multispace0, char('|'), multispace0),
+            relation,
+        )),
+        multispace0,
+    )(input)?;
+    rest.insert(0, first);
+    Ok((input, rest))
+}
+
+/// Return true if the relation is well-formed beyond what the grammar checks.
+fn is_valid(relation: &Relation<'_>) -> bool {
+    let negated = |entry: &&str| entry.starts_with('!');
+    let architectures = &relation.architectures;
+    let consistent_architectures =
+        architectures.iter().all(negated) || !architectures.iter().any(negated);
+    let valid_profiles = relation
+        .profiles
+        .iter()
+        .all(|terms| !terms.is_empty() && terms.iter().all(|term| !term.name.is_empty()));
+    consistent_architectures && valid_profiles
+}
+
+/// Parse the value of a relationship field.
+///
+/// Empty items, for example after a trailing comma, are skipped.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{parse_relations, VersionOperator};
+/// let relations = parse_relations("libc6 (>= 2.34), grub-pc | extlinux [amd64 i386],").unwrap();
+/// assert_eq!(relations.len(), 2);
+/// assert_eq!(relations[0][0].name, "libc6");
+/// assert_eq!(relations[0][0].version.unwrap().operator, VersionOperator::LaterOrEqual);
+/// assert_eq!(relations[1][1].architectures, vec!["amd64", "i386"]);
+/// ```
+pub fn parse_relations(value: &str) -> Result<Vec<Alternatives<'_>>, RelationError> {
+    value
+        .split(',')
+        .filter(|item| !item.trim().is_empty())
+        .map(|item| match all_consuming(alternatives)(item) {
+            Ok((_, alternatives)) if alternatives.iter().all(is_valid) => Ok(alternatives),
+            _ => Err(RelationError {
+                relation: String::from(item.trim()),
+            }),
+        })
+        .collect()
+}
+
+/// The environment in which build dependencies are evaluated.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BuildContext<'a> {
+    /// The architecture the package is built for, which architecture restriction lists are
+    /// evaluated against.
+    pub host_arch: &'a str,
+    /// The architecture the package is built on, which the `:native` qualifier refers to.
+    pub build_arch: &'a str,
+    /// The active build profiles, as in `DEB_BUILD_PROFILES`.
+    pub profiles: Vec<&'a str>,
+}
+
+impl<'a> BuildContext<'a> {
+    /// Create a context for a native build with no active build profiles.
+    pub fn native(arch: &'a str) -> Self {
+        BuildContext {
+            host_arch: arch,
+            build_arch: arch,
+            profiles: Vec::new(),
+        }
+    }
+}
+
+/// Reduce relationships to those that apply in the given build environment, the way
+/// `dpkg-checkbuilddeps` does.
+///
+/// Alternatives whose architecture restriction list or build profile formula doesn't hold are
+/// removed, and relationships without any remaining alternatives are dropped. The restrictions of
+/// the remaining alternatives are removed, since they have been evaluated.
+///
+/// The `:native` qualifier is resolved as well: it is removed in native builds, where unqualified
+/// build dependencies already refer to the build architecture, and replaced by the build
+/// architecture in cross builds.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{parse_relations, reduce_relations, write_relations, BuildContext};
+/// let relations = parse_relations("gnupg <!nocheck>, libselinux1-dev [linux-any], bison").unwrap();
+/// let context = BuildContext {
+///     profiles: vec!["nocheck"],
+///     ..BuildContext::native("hurd-i386")
+/// };
+/// let mut output = String::new();
+/// write_relations(&mut output, &reduce_relations(&relations, &context)).unwrap();
+/// assert_eq!(output, "bison");
+/// ```
+pub fn reduce_relations<'a>(
+    relations: &[Alternatives<'a>],
+    context: &BuildContext<'a>,
+) -> Vec<Alternatives<'a>> {
+    relations
+        .iter()
+        .map(|alternatives| {
+            alternatives
+                .iter()
+                .filter(|relation| relation.is_active(context))
+                .map(|relation| Relation {
+                    arch_qualifier: match relation.arch_qualifier {
+                        Some("native") if context.build_arch == context.host_arch => None,
+                        Some("native") => Some(context.build_arch),
+                        qualifier => qualifier,
+                    },
+                    architectures: Vec::new(),
+                    profiles: Vec::new(),
+                    ..relation.clone()
+                })
+                .collect::<Alternatives<'_>>()
+        })
+        .filter(|alternatives| !alternatives.is_empty())
+        .collect()
+}
+
+macro_rules! relation_methods {
+    ($type: ty) => {
+        impl $type {
+            /// Parse the value as a relationship field, as in `Depends` or `Build-Depends`.
+            pub fn relations(&self) -> Result<Vec<Alternatives<'_>>, RelationError> {
+                parse_relations(&self.value)
+            }
+        }
+    };
+}
+
+relation_methods!(Field<'_>);
+relation_methods!(OwnedField);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::field;
+    use alloc::{string::ToString, vec};
+
+    fn relation(name: &str) -> Relation<'_> {
+        Relation {
+            name,
+            ..Relation::default()
+        }
+    }
+
+    fn to_string(relations: &[Alternatives<'_>]) -> String {
+        let mut output = String::new();
+        write_relations(&mut output, relations).unwrap();
+        output
+    }
+
+    #[test]
+    fn should_parse_full_relation() {
+        let relations = parse_relations(
+            "libfoo-dev:native ( >= 1:1.2-3~ ) [linux-any] <!nocheck> <!nodoc cross>",
+        )
+        .unwrap();
+        assert_eq!(
+            relations,
+            vec![vec![Relation {
+                name: "libfoo-dev",
+                arch_qualifier: Some("native"),
+                version: Some(VersionConstraint {
+                    operator: VersionOperator::LaterOrEqual,
+                    version: "1:1.2-3~",
+                }),
+                architectures: vec!["linux-any"],
+                profiles: vec![
+                    vec![ProfileTerm {
+                        name: "nocheck",
+                        negated: true
+                    }],
+                    vec![
+                        ProfileTerm {
+                            name: "nodoc",
+                            negated: true
+                        },
+                        ProfileTerm {
+                            name: "cross",
+                            negated: false
+                        }
+                    ],
+                ],
+            }]]
+        );
+        assert_eq!(
+            to_string(&relations),
+            "libfoo-dev:native (>= 1:1.2-3~) [linux-any] <!nocheck> <!nodoc cross>"
+        );
+    }
+
+    #[test]
+    fn should_parse_alternatives_across_lines() {
+        let relations =
+            parse_relations("\ndracut,\ngrub-efi-amd64 | grub-pc|\n extlinux,\n${misc:Depends},")
+                .unwrap();
+        assert_eq!(
+            relations,
+            vec![
+                vec![relation("dracut")],
+                vec![
+                    relation("grub-efi-amd64"),
+                    relation("grub-pc"),
+                    relation("extlinux")
+                ],
+                vec![relation("${misc:Depends}")],
+            ]
+        );
+    }
+
+    #[test]
+    fn should_parse_operators() {
+        let operators = [
+            ("<<", VersionOperator::Earlier),
+            ("<=", VersionOperator::EarlierOrEqual),
+            ("<", VersionOperator::EarlierOrEqual),
+            ("=", VersionOperator::Equal),
+            (">=", VersionOperator::LaterOrEqual),
+            (">", VersionOperator::LaterOrEqual),
+            (">>", VersionOperator::Later),
+        ];
+        for (text, operator) in operators.iter() {
+            let value = ["a (", text, " ${binary:Version})"].concat();
+            let relations = parse_relations(&value).unwrap();
+            assert_eq!(
+                relations[0][0].version,
+                Some(VersionConstraint {
+                    operator: *operator,
+                    version: "${binary:Version}"
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_relations() {
+        for value in [
+            "a (>= 1",
+            "a (1.0)",
+            "a (>=)",
+            "a [amd64",
+            "a [amd64 !i386]",
+            "a <>",
+            "a b",
+            "a |",
+            "| a",
+            "a:",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                parse_relations(value),
+                Err(RelationError {
+                    relation: value.to_string()
+                }),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn should_evaluate_profile_formulas() {
+        let relations = parse_relations("a <!nocheck> <!nodoc cross>").unwrap();
+        let a = &relations[0][0];
+        assert!(a.matches_profiles(&[]));
+        assert!(a.matches_profiles(&["nodoc"]));
+        assert!(!a.matches_profiles(&["nocheck", "nodoc"]));
+        assert!(a.matches_profiles(&["nocheck", "cross"]));
+        assert!(!a.matches_profiles(&["nocheck", "cross", "nodoc"]));
+        assert!(relation("b").matches_profiles(&["nocheck"]));
+    }
+
+    #[test]
+    fn should_evaluate_architecture_lists() {
+        let relations = parse_relations("a [linux-any], b [!s390x !sparc64]").unwrap();
+        assert!(relations[0][0].matches_arch("loongarch64"));
+        assert!(!relations[0][0].matches_arch("hurd-amd64"));
+        assert!(relations[1][0].matches_arch("amd64"));
+        assert!(!relations[1][0].matches_arch("s390x"));
+    }
+
+    #[test]
+    fn should_reduce_alternatives() {
+        let relations =
+            parse_relations("a [amd64] | b <cross>, c [i386] | d <!cross>, e [i386] <cross>")
+                .unwrap();
+        let mut context = BuildContext::native("amd64");
+        assert_eq!(to_string(&reduce_relations(&relations, &context)), "a, d");
+        context.profiles.push("cross");
+        context.host_arch = "i386";
+        assert_eq!(
+            to_string(&reduce_relations(&relations, &context)),
+            "b, c, e"
+        );
+    }
+
+    #[test]
+    fn should_resolve_native_qualifier() {
+        let relations = parse_relations("gcc:native, python3:any, libc6-dev").unwrap();
+        let mut context = BuildContext::native("amd64");
+        assert_eq!(
+            to_string(&reduce_relations(&relations, &context)),
+            "gcc, python3:any, libc6-dev"
+        );
+        context.host_arch = "arm64";
+        assert_eq!(
+            to_string(&reduce_relations(&relations, &context)),
+            "gcc:amd64, python3:any, libc6-dev"
+        );
+    }
+
+    #[test]
+    fn should_parse_field_relations() {
+        let depends = field("Depends", "a (= 1) | b");
+        let relations = depends.relations().unwrap();
+        assert_eq!(relations[0].len(), 2);
+        let owned = OwnedField::from(depends.clone());
+        assert_eq!(owned.relations().unwrap(), relations);
+    }
+}
@@ -0,0 +1,773 @@
+//! Checking relationships against a set of available or installed packages.
+//!
+//! A relationship such as `python3:any (>= 3.11) | python3-minimal` is satisfied if any of its
+//! alternatives is satisfied by a package in the set, either directly or through a `Provides`
+//! field. Architectures are taken into account following the `Multi-Arch` rules dpkg applies:
+//!
+//! * a relation without architecture qualifier is satisfied by packages of the architecture of the
+//!   depending package, by `Architecture: all` packages (which count as native), and by
+//!   `Multi-Arch: foreign` packages of any architecture;
+//! * `:any` is satisfied by `Multi-Arch: allowed` packages of any architecture;
+//! * `:native` is satisfied by packages of the native architecture;
+//! * any other qualifier, such as `:i386`, is satisfied by packages of that architecture.
+//!
+//! Conflicts and breaks without architecture qualifier apply to packages of every architecture,
+//! like `:any`.
+//!
+//! See the [MultiarchSpec](https://wiki.ubuntu.com/MultiarchSpec) for details.
+//!
+//! # Example
+//! ```
+//! # use oma_debcontrol::{parse_relations, parse_str, OwnedParagraph, PackageSet};
+//! let paragraphs = parse_str("\
+//! Package: mawk
+//! Version: 1.3.4-1
+//! Architecture: amd64
+//! Multi-Arch: foreign
+//! Provides: awk
+//! ").unwrap().into_iter().map(OwnedParagraph::from).collect::<Vec<_>>();
+//! let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+//!
+//! let relations = parse_relations("gawk | awk").unwrap();
+//! let satisfaction = set.satisfies(&relations[0], "i386").unwrap();
+//! assert!(satisfaction.is_satisfied());
+//! assert_eq!(satisfaction.satisfier().unwrap().package.name, "mawk");
+//! assert_eq!(satisfaction.to_string(), "\
+//! gawk: no such package
+//! awk: satisfied by mawk 1.3.4-1 (amd64) via awk
+//! ");
+//! ```
+
+use crate::{
+    formats::status::{Database, State, StatusError},
+    parse_relations, Alternatives, OwnedParagraph, Relation, RelationError, Version, VersionError,
+    VersionOperator,
+};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+
+/// An error in a package paragraph that prevents adding it to a [`PackageSet`](struct.PackageSet.html).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PackageError {
+    /// The paragraph lacks a required field.
+    MissingField(&'static str),
+    /// The `Version` field is invalid.
+    InvalidVersion(VersionError),
+    /// The `Multi-Arch` field has an unknown value.
+    InvalidMultiArch(String),
+    /// A relationship field such as `Depends` or `Provides` is invalid.
+    InvalidRelation {
+        field: &'static str,
+        error: RelationError,
+    },
+    /// The `Status` field of a status database entry is invalid.
+    InvalidStatus(StatusError),
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageError::MissingField(field) => write!(f, "missing field '{}'", field),
+            PackageError::InvalidVersion(error) => write!(f, "{}", error),
+            PackageError::InvalidMultiArch(value) => {
+                write!(f, "invalid Multi-Arch value '{}'", value)
+            }
+            PackageError::InvalidRelation { field, error } => {
+                write!(f, "invalid {}: {}", field, error)
+            }
+            PackageError::InvalidStatus(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PackageError {}
+
+/// The value of a `Multi-Arch` field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum MultiArch {
+    /// The package is not multi-arch aware. This is the default.
+    #[default]
+    No,
+    /// The package can be co-installed with itself for other architectures.
+    Same,
+    /// The package can satisfy dependencies of packages of other architectures.
+    Foreign,
+    /// The package can satisfy `:any` dependencies of packages of other architectures.
+    Allowed,
+}
+
+impl MultiArch {
+    /// Parse the value of a `Multi-Arch` field, ignoring case.
+    pub fn parse(value: &str) -> Option<MultiArch> {
+        [
+            MultiArch::No,
+            MultiArch::Same,
+            MultiArch::Foreign,
+            MultiArch::Allowed,
+        ]
+        .iter()
+        .copied()
+        .find(|multi_arch| multi_arch.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+
+    /// Return the value as written in `Multi-Arch` fields.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MultiArch::No => "no",
+            MultiArch::Same => "same",
+            MultiArch::Foreign => "foreign",
+            MultiArch::Allowed => "allowed",
+        }
+    }
+}
+
+impl fmt::Display for MultiArch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The fields of a `Packages` or status entry that relationships are checked against.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PackageInfo<'a> {
+    pub name: &'a str,
+    pub version: Version<'a>,
+    pub architecture: &'a str,
+    pub multi_arch: MultiArch,
+    /// The virtual packages this package provides.
+    pub provides: Vec<Relation<'a>>,
+    pub pre_depends: Vec<Alternatives<'a>>,
+    pub depends: Vec<Alternatives<'a>>,
+    pub conflicts: Vec<Alternatives<'a>>,
+    pub breaks: Vec<Alternatives<'a>>,
+}
+
+impl<'a> PackageInfo<'a> {
+    /// Read the package information from a paragraph.
+    ///
+    /// `Package`, `Version` and `Architecture` are required. Of the relationship fields, only
+    /// `Provides`, `Pre-Depends`, `Depends`, `Conflicts` and `Breaks` are read.
+    pub fn from_paragraph(paragraph: &'a OwnedParagraph) -> Result<Self, PackageError> {
+        let required = |field| {
+            paragraph
+                .get(field)
+                .ok_or(PackageError::MissingField(field))
+        };
+        let multi_arch = match paragraph.get("Multi-Arch") {
+            Some(value) => MultiArch::parse(value)
+                .ok_or_else(|| PackageError::InvalidMultiArch(String::from(value)))?,
+            None => MultiArch::No,
+        };
+        let relations = |field| {
+            parse_relations(paragraph.get(field).unwrap_or_default())
+                .map_err(|error| PackageError::InvalidRelation { field, error })
+        };
+        Ok(PackageInfo {
+            name: required("Package")?,
+            version: Version::parse(required("Version")?).map_err(PackageError::InvalidVersion)?,
+            architecture: required("Architecture")?,
+            multi_arch,
+            provides: relations("Provides")?.into_iter().flatten().collect(),
+            pre_depends: relations("Pre-Depends")?,
+            depends: relations("Depends")?,
+            conflicts: relations("Conflicts")?,
+            breaks: relations("Breaks")?,
+        })
+    }
+}
+
+impl fmt::Display for PackageInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ({})", self.name, self.version, self.architecture)
+    }
+}
+
+/// Why a package doesn't satisfy a relation it was considered for.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Mismatch {
+    /// The package doesn't have the version the relation requires.
+    Version,
+    /// The package's architecture or `Multi-Arch` value doesn't fit the relation.
+    Architecture,
+    /// The relation requires a version, but the package provides the virtual package without one.
+    UnversionedProvides,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mismatch::Version => "wrong version",
+            Mismatch::Architecture => "wrong architecture",
+            Mismatch::UnversionedProvides => "unversioned provides",
+        })
+    }
+}
+
+/// A package that was considered for a relation because its name or one of the names it
+/// provides matches.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Candidate<'p> {
+    pub package: &'p PackageInfo<'p>,
+    /// The `Provides` entry through which the package was considered, if it was not considered
+    /// by its own name.
+    pub provides: Option<&'p Relation<'p>>,
+    /// Why the package doesn't satisfy the relation, or `None` if it does.
+    pub mismatch: Option<Mismatch>,
+}
+
+impl Candidate<'_> {
+    /// Return true if the package satisfies the relation.
+    pub fn is_match(&self) -> bool {
+        self.mismatch.is_none()
+    }
+}
+
+impl fmt::Display for Candidate<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.package)?;
+        if let Some(provides) = self.provides {
+            write!(f, " via {}", provides)?;
+        }
+        if let Some(mismatch) = self.mismatch {
+            write!(f, " has {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of checking a single alternative of a relationship.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AlternativeCheck<'r, 'p> {
+    pub relation: &'r Relation<'r>,
+    /// All packages that were considered for the relation. If there are none, no package of that
+    /// name exists in the set.
+    pub candidates: Vec<Candidate<'p>>,
+}
+
+impl<'p> AlternativeCheck<'_, 'p> {
+    /// Return true if any package satisfies the relation.
+    pub fn is_satisfied(&self) -> bool {
+        self.candidates.iter().any(Candidate::is_match)
+    }
+
+    /// Return the packages that satisfy the relation.
+    pub fn satisfied_by(&self) -> impl Iterator<Item = &Candidate<'p>> {
+        self.candidates
+            .iter()
+            .filter(|candidate| candidate.is_match())
+    }
+}
+
+impl fmt::Display for AlternativeCheck<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.relation)?;
+        if self.candidates.is_empty() {
+            return f.write_str("no such package");
+        }
+        if self.is_satisfied() {
+            f.write_str("satisfied by ")?;
+            return join(f, self.satisfied_by());
+        }
+        f.write_str("not satisfied, ")?;
+        join(f, self.candidates.iter())
+    }
+}
+
+/// Write candidates separated by commas.
+fn join<'a, 'p: 'a>(
+    f: &mut fmt::Formatter<'_>,
+    candidates: impl Iterator<Item = &'a Candidate<'p>>,
+) -> fmt::Result {
+    for (i, candidate) in candidates.enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", candidate)?;
+    }
+    Ok(())
+}
+
+/// The result of checking a relationship with all of its alternatives.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Satisfaction<'r, 'p> {
+    /// The results for the alternatives, in the order they are listed in the relationship.
+    pub alternatives: Vec<AlternativeCheck<'r, 'p>>,
+}
+
+impl<'p> Satisfaction<'_, 'p> {
+    /// Return true if any of the alternatives is satisfied.
+    pub fn is_satisfied(&self) -> bool {
+        self.alternatives.iter().any(AlternativeCheck::is_satisfied)
+    }
+
+    /// Return the first package that satisfies the first satisfied alternative.
+    pub fn satisfier(&self) -> Option<&Candidate<'p>> {
+        self.alternatives
+            .iter()
+            .flat_map(AlternativeCheck::satisfied_by)
+            .next()
+    }
+}
+
+impl fmt::Display for Satisfaction<'_, '_> {
+    /// Write one line per alternative, explaining why it is or isn't satisfied.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for alternative in &self.alternatives {
+            writeln!(f, "{}", alternative)?;
+        }
+        Ok(())
+    }
+}
+
+/// The index of a candidate package, the index of the `Provides` entry through which it was
+/// considered, and why it doesn't match.
+pub(crate) type CandidateIndex = (usize, Option<usize>, Option<Mismatch>);
+
+/// A set of packages that relationships can be checked against, such as the packages of a
+/// `Packages` file or the installed packages of a dpkg status database.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PackageSet<'a> {
+    /// The native architecture, which `:native` and `Architecture: all` refer to.
+    pub native_arch: &'a str,
+    packages: Vec<PackageInfo<'a>>,
+    /// Indices of packages by name.
+    names: BTreeMap<&'a str, Vec<usize>>,
+    /// Indices of packages and their `Provides` entries by provided name.
+    provided: BTreeMap<&'a str, Vec<(usize, usize)>>,
+}
+
+impl<'a> PackageSet<'a> {
+    /// Create an empty set for the given native architecture.
+    pub fn new(native_arch: &'a str) -> Self {
+        PackageSet {
+            native_arch,
+            ..PackageSet::default()
+        }
+    }
+
+    /// Create a set from the paragraphs of a `Packages` file.
+    pub fn from_paragraphs(
+        native_arch: &'a str,
+        paragraphs: impl IntoIterator<Item = &'a OwnedParagraph>,
+    ) -> Result<Self, PackageError> {
+        let mut set = PackageSet::new(native_arch);
+        for paragraph in paragraphs {
+            set.insert(PackageInfo::from_paragraph(paragraph)?);
+        }
+        Ok(set)
+    }
+
+    /// Create a set from the packages of a status database that satisfy dependencies, i.e. those
+    /// that are installed or only await or have pending triggers.
+    pub fn from_status(native_arch: &'a str, database: &'a Database) -> Result<Self, PackageError> {
+        let mut set = PackageSet::new(native_arch);
+        for entry in &database.entries {
+            let status = entry.status().map_err(PackageError::InvalidStatus)?;
+            if let Some(status) = status {
+                if matches!(
+                    status.state,
+                    State::Installed | State::TriggersAwaited | State::TriggersPending
+                ) {
+                    set.insert(PackageInfo::from_paragraph(&entry.paragraph)?);
+                }
+            }
+        }
+        Ok(set)
+    }
+
+    /// Add a package to the set.
+    pub fn insert(&mut self, package: PackageInfo<'a>) {
+        let index = self.packages.len();
+        self.names.entry(package.name).or_default().push(index);
+        for (i, provides) in package.provides.iter().enumerate() {
+            self.provided
+                .entry(provides.name)
+                .or_default()
+                .push((index, i));
+        }
+        self.packages.push(package);
+    }
+
+    /// Return all packages in the order they were added.
+    pub fn packages(&self) -> &[PackageInfo<'a>] {
+        &self.packages
+    }
+
+    /// Return all packages with the given name.
+    pub fn get(&self, name: &str) -> impl Iterator<Item = &PackageInfo<'a>> {
+        self.names
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(move |index| &self.packages[*index])
+    }
+
+    /// Return the indices of all packages with the given name.
+    pub(crate) fn indices(&self, name: &str) -> &[usize] {
+        self.names.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Return the number of packages in the set.
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Return true if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Return true if the package's architecture fits the relation, given the architecture of the
+    /// depending package.
+    ///
+    /// For conflicts, relations without qualifier and `:any` match packages of every architecture,
+    /// as in dpkg.
+    fn arch_matches(
+        &self,
+        package: &PackageInfo<'_>,
+        relation: &Relation<'_>,
+        arch: &str,
+        conflict: bool,
+    ) -> bool {
+        let native = |arch| {
+            if arch == "all" {
+                self.native_arch
+            } else {
+                arch
+            }
+        };
+        let package_arch = native(package.architecture);
+        match relation.arch_qualifier {
+            None | Some("any") if conflict => true,
+            None => package_arch == native(arch) || package.multi_arch == MultiArch::Foreign,
+            Some("any") => package.multi_arch == MultiArch::Allowed,
+            Some("native") => package_arch == self.native_arch,
+            Some(qualifier) => package_arch == qualifier,
+        }
+    }
+
+    /// Return all packages considered for a relation, as indices of the package and of the
+    /// `Provides` entry through which it was considered, along with any mismatch.
+    pub(crate) fn candidates(
+        &self,
+        relation: &Relation<'_>,
+        arch: &str,
+        conflict: bool,
+    ) -> Result<Vec<CandidateIndex>, VersionError> {
+        let required = match relation.version {
+            Some(constraint) => Some((constraint.operator, Version::parse(constraint.version)?)),
+            None => None,
+        };
+        let accepts = |version: &Version<'_>| match &required {
+            Some((operator, required)) => operator.accepts(version.cmp(required)),
+            None => true,
+        };
+
+        let mut candidates = Vec::new();
+        for index in self.names.get(relation.name).into_iter().flatten() {
+            let package = &self.packages[*index];
+            let mismatch = if !self.arch_matches(package, relation, arch, conflict) {
+                Some(Mismatch::Architecture)
+            } else if !accepts(&package.version) {
+                Some(Mismatch::Version)
+            } else {
+                None
+            };
+            candidates.push((*index, None, mismatch));
+        }
+        for (index, i) in self.provided.get(relation.name).into_iter().flatten() {
+            let package = &self.packages[*index];
+            let provides = &package.provides[*i];
+            let mismatch = if !self.arch_matches(package, relation, arch, conflict) {
+                Some(Mismatch::Architecture)
+            } else {
+                match (required, provides.version) {
+                    (None, _) => None,
+                    (Some(_), None) => Some(Mismatch::UnversionedProvides),
+                    (Some(_), Some(provided)) => {
+                        let version = Version::parse(provided.version)?;
+                        if provided.operator == VersionOperator::Equal && accepts(&version) {
+                            None
+                        } else {
+                            Some(Mismatch::Version)
+                        }
+                    }
+                }
+            };
+            candidates.push((*index, Some(*i), mismatch));
+        }
+        Ok(candidates)
+    }
+
+    /// Check a single alternative.
+    fn check<'r>(
+        &self,
+        relation: &'r Relation<'r>,
+        arch: &str,
+    ) -> Result<AlternativeCheck<'r, '_>, VersionError> {
+        let candidates = self
+            .candidates(relation, arch, false)?
+            .into_iter()
+            .map(|(index, provides, mismatch)| {
+                let package = &self.packages[index];
+                Candidate {
+                    package,
+                    provides: provides.map(|i| &package.provides[i]),
+                    mismatch,
+                }
+            })
+            .collect();
+        Ok(AlternativeCheck {
+            relation,
+            candidates,
+        })
+    }
+
+    /// Check which alternatives of a relationship are satisfied, and by which packages.
+    ///
+    /// `arch` is the architecture of the package that declares the relationship; `all` stands for
+    /// the native architecture. For build dependencies, this is the host architecture. Fails if
+    /// a version in the relationship or in a versioned `Provides` is invalid.
+    pub fn satisfies<'r>(
+        &self,
+        alternatives: &'r [Relation<'r>],
+        arch: &str,
+    ) -> Result<Satisfaction<'r, '_>, VersionError> {
+        Ok(Satisfaction {
+            alternatives: alternatives
+                .iter()
+                .map(|relation| self.check(relation, arch))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Check which alternatives of a relationship are satisfied by a package set.
+///
+/// This is a shorthand for [`PackageSet::satisfies`](struct.PackageSet.html#method.satisfies).
+pub fn satisfies<'r, 'p>(
+    alternatives: &'r [Relation<'r>],
+    set: &'p PackageSet<'_>,
+    arch: &str,
+) -> Result<Satisfaction<'r, 'p>, VersionError> {
+    set.satisfies(alternatives, arch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use alloc::{string::ToString, vec};
+    use indoc::indoc;
+
+    const PACKAGES: &str = indoc!(
+        "
+        Package: libc6
+        Version: 2.36-9
+        Architecture: amd64
+        Multi-Arch: same
+
+        Package: libc6
+        Version: 2.36-9
+        Architecture: i386
+        Multi-Arch: same
+
+        Package: python3
+        Version: 3.11.2-1
+        Architecture: amd64
+        Multi-Arch: allowed
+
+        Package: make
+        Version: 4.3-4.1
+        Architecture: amd64
+        Multi-Arch: foreign
+
+        Package: mawk
+        Version: 1.3.4-1
+        Architecture: amd64
+        Provides: awk
+
+        Package: libfoo1
+        Version: 1.0-1
+        Architecture: amd64
+        Provides: libfoo-abi (= 2), libfoo-abi-any (= 2)
+
+        Package: tzdata
+        Version: 2024a-1
+        Architecture: all
+        "
+    );
+
+    fn paragraphs(input: &str) -> Vec<OwnedParagraph> {
+        parse_str(input)
+            .unwrap()
+            .into_iter()
+            .map(OwnedParagraph::from)
+            .collect()
+    }
+
+    fn relation(value: &str) -> Alternatives<'_> {
+        parse_relations(value).unwrap().remove(0)
+    }
+
+    fn mismatches(satisfaction: &Satisfaction<'_, '_>) -> Vec<Option<Mismatch>> {
+        satisfaction
+            .alternatives
+            .iter()
+            .flat_map(|alternative| &alternative.candidates)
+            .map(|candidate| candidate.mismatch)
+            .collect()
+    }
+
+    #[test]
+    fn should_match_architecture_of_depending_package() {
+        let paragraphs = paragraphs(PACKAGES);
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let libc = relation("libc6 (>= 2.36)");
+
+        let amd64 = set.satisfies(&libc, "amd64").unwrap();
+        assert_eq!(amd64.satisfier().unwrap().package.architecture, "amd64");
+        assert_eq!(mismatches(&amd64), vec![None, Some(Mismatch::Architecture)]);
+        let i386 = set.satisfies(&libc, "i386").unwrap();
+        assert_eq!(i386.satisfier().unwrap().package.architecture, "i386");
+        let all = set.satisfies(&libc, "all").unwrap();
+        assert_eq!(all.satisfier().unwrap().package.architecture, "amd64");
+
+        assert!(set
+            .satisfies(&relation("tzdata"), "amd64")
+            .unwrap()
+            .is_satisfied());
+        assert!(!set
+            .satisfies(&relation("tzdata"), "i386")
+            .unwrap()
+            .is_satisfied());
+    }
+
+    #[test]
+    fn should_apply_multi_arch_rules() {
+        let paragraphs = paragraphs(PACKAGES);
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let cases = [
+            ("make", "i386", true),
+            ("python3", "i386", false),
+            ("python3:any", "i386", true),
+            ("make:any", "amd64", false),
+            ("libc6:any", "amd64", false),
+            ("python3:native", "i386", true),
+            ("libc6:i386", "amd64", true),
+            ("make:i386", "i386", false),
+            ("awk", "i386", false),
+        ];
+        for (value, arch, expected) in cases.iter() {
+            let relation = relation(value);
+            let satisfaction = set.satisfies(&relation, arch).unwrap();
+            assert_eq!(satisfaction.is_satisfied(), *expected, "{} {}", value, arch);
+        }
+    }
+
+    #[test]
+    fn should_check_versions() {
+        let paragraphs = paragraphs(PACKAGES);
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let check = |value| {
+            let relation = relation(value);
+            let satisfaction = set.satisfies(&relation, "amd64").unwrap();
+            (satisfaction.is_satisfied(), mismatches(&satisfaction))
+        };
+        assert_eq!(
+            check("make (<< 4.3)"),
+            (false, vec![Some(Mismatch::Version)])
+        );
+        assert_eq!(check("make (= 4.3-4.1)"), (true, vec![None]));
+        assert_eq!(check("libfoo-abi (>= 2)"), (true, vec![None]));
+        assert_eq!(
+            check("libfoo-abi (>> 2)"),
+            (false, vec![Some(Mismatch::Version)])
+        );
+        assert_eq!(check("awk"), (true, vec![None]));
+        assert_eq!(
+            check("awk (>= 1)"),
+            (false, vec![Some(Mismatch::UnversionedProvides)])
+        );
+
+        let relation = relation("make (= ${binary:Version})");
+        assert!(set.satisfies(&relation, "amd64").is_err());
+    }
+
+    #[test]
+    fn should_explain_alternatives() {
+        let paragraphs = paragraphs(PACKAGES);
+        let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+        let relation = relation("gawk | awk (>= 1) | libc6 (>= 2.37) | python3 | mawk");
+        let satisfaction = satisfies(&relation, &set, "amd64").unwrap();
+        assert_eq!(satisfaction.satisfier().unwrap().package.name, "python3");
+        assert_eq!(
+            satisfaction.to_string(),
+            indoc!(
+                "
+                gawk: no such package
+                awk (>= 1): not satisfied, mawk 1.3.4-1 (amd64) via awk has unversioned provides
+                libc6 (>= 2.37): not satisfied, libc6 2.36-9 (amd64) has wrong version, libc6 2.36-9 (i386) has wrong architecture
+                python3: satisfied by python3 3.11.2-1 (amd64)
+                mawk: satisfied by mawk 1.3.4-1 (amd64)
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn should_only_use_installed_status_entries() {
+        let database = Database::parse(indoc!(
+            "
+            Package: a
+            Status: install ok installed
+            Version: 1
+            Architecture: amd64
+
+            Package: b
+            Status: deinstall ok config-files
+            Version: 1
+            Architecture: amd64
+
+            Package: c
+            Status: install ok triggers-pending
+            Version: 1
+            Architecture: amd64
+            "
+        ))
+        .unwrap();
+        let set = PackageSet::from_status("amd64", &database).unwrap();
+        assert_eq!(
+            set.packages().iter().map(|p| p.name).collect::<Vec<_>>(),
+            vec!["a", "c"]
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_packages() {
+        let invalid = |input| {
+            let paragraphs = paragraphs(input);
+            PackageSet::from_paragraphs("amd64", &paragraphs).unwrap_err()
+        };
+        assert_eq!(
+            invalid("Package: a\nVersion: 1\n"),
+            PackageError::MissingField("Architecture")
+        );
+        assert_eq!(
+            invalid("Package: a\nVersion: 1\nArchitecture: all\nMulti-Arch: maybe\n"),
+            PackageError::InvalidMultiArch("maybe".to_string())
+        );
+        assert!(matches!(
+            invalid("Package: a\nVersion: x\nArchitecture: all\n"),
+            PackageError::InvalidVersion(_)
+        ));
+        assert!(matches!(
+            invalid("Package: a\nVersion: 1\nArchitecture: all\nDepends: b (>= 1\n"),
+            PackageError::InvalidRelation {
+                field: "Depends",
+                ..
+            }
+        ));
+    }
+}
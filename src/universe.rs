@@ -0,0 +1,425 @@
+//! All versions of packages available from several repositories.
+
+use crate::{
+    formats::preferences::{Preferences, ReleaseInfo},
+    OwnedParagraph, PackageError, Version,
+};
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+/// A package version in a [`PackageUniverse`](struct.PackageUniverse.html) and the releases it is
+/// available from.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PackageVersion<'a> {
+    pub paragraph: OwnedParagraph,
+    /// The releases this version is available from, in the order they were added.
+    pub releases: Vec<ReleaseInfo<'a>>,
+}
+
+impl<'a> PackageVersion<'a> {
+    /// Return the package name.
+    pub fn name(&self) -> &str {
+        self.paragraph.get("Package").unwrap_or_default()
+    }
+
+    /// Return the version string.
+    pub fn version(&self) -> &str {
+        self.paragraph.get("Version").unwrap_or_default()
+    }
+
+    /// Return the package architecture.
+    pub fn architecture(&self) -> &str {
+        self.paragraph.get("Architecture").unwrap_or_default()
+    }
+
+    /// Return the SHA256 checksum of the package file, if known.
+    pub fn sha256(&self) -> Option<&str> {
+        self.paragraph.get("SHA256")
+    }
+
+    /// Compute the pin priority of this version, which is the highest priority of any release it
+    /// is available from.
+    ///
+    /// Without preferences, this is the highest
+    /// [default priority](formats/preferences/struct.ReleaseInfo.html#method.default_priority) of
+    /// the releases.
+    pub fn priority(&self, preferences: Option<&Preferences<'_>>) -> i32 {
+        self.releases
+            .iter()
+            .map(|release| match preferences {
+                Some(preferences) => preferences.priority(self.name(), self.version(), release),
+                None => release.default_priority(),
+            })
+            .max()
+            .unwrap_or(i32::MIN)
+    }
+
+    /// Return true if both paragraphs describe the same package file.
+    ///
+    /// Paragraphs are compared by their `SHA256` field, or entirely if either lacks one.
+    fn is_same_file(&self, paragraph: &OwnedParagraph) -> bool {
+        match (self.sha256(), paragraph.get("SHA256")) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.paragraph == *paragraph,
+        }
+    }
+}
+
+/// All versions of all packages from a set of package indices, such as the `Packages` files of
+/// several repositories.
+///
+/// Versions are grouped by package name and architecture and ordered from the highest to the
+/// lowest version. When the same package file is available from several releases, which is
+/// detected by its `SHA256` checksum, it is kept only once and its
+/// [`releases`](struct.PackageVersion.html#structfield.releases) are merged. Different files with
+/// the same version are kept separately, as in APT.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{parse_str, OwnedParagraph, PackageUniverse};
+/// # use oma_debcontrol::formats::preferences::{Preferences, ReleaseInfo};
+/// let stable = ReleaseInfo { archive: Some("stable"), ..ReleaseInfo::default() };
+/// let testing = ReleaseInfo { archive: Some("testing"), ..ReleaseInfo::default() };
+/// let packages = |input| parse_str(input).unwrap().into_iter().map(OwnedParagraph::from);
+///
+/// let mut universe = PackageUniverse::new();
+/// for paragraph in packages("Package: oma\nVersion: 1.3.0\nArchitecture: amd64\nSHA256: aa\n") {
+///     universe.insert(paragraph, &stable).unwrap();
+/// }
+/// for paragraph in packages("Package: oma\nVersion: 1.14.0\nArchitecture: amd64\nSHA256: bb\n") {
+///     universe.insert(paragraph, &testing).unwrap();
+/// }
+///
+/// let candidate = universe.candidate("oma", "amd64", None).unwrap();
+/// assert_eq!(candidate.version(), "1.14.0");
+///
+/// let input = "Package: *\nPin: release a=testing\nPin-Priority: 100\n";
+/// let paragraphs = parse_str(input).unwrap();
+/// let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+/// let candidate = universe.candidate("oma", "amd64", Some(&preferences)).unwrap();
+/// assert_eq!(candidate.version(), "1.3.0");
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PackageUniverse<'a> {
+    /// Versions by package name and architecture, from highest to lowest.
+    packages: BTreeMap<(String, String), Vec<PackageVersion<'a>>>,
+}
+
+impl<'a> PackageUniverse<'a> {
+    /// Create an empty universe.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a package paragraph from the given release.
+    ///
+    /// Returns true if the paragraph adds a new version, or false if the same package file was
+    /// already added, possibly from another release.
+    pub fn insert(
+        &mut self,
+        paragraph: OwnedParagraph,
+        release: &ReleaseInfo<'a>,
+    ) -> Result<bool, PackageError> {
+        let required = |field| {
+            paragraph
+                .get(field)
+                .map(str::trim)
+                .ok_or(PackageError::MissingField(field))
+        };
+        let name = String::from(required("Package")?);
+        let architecture = String::from(required("Architecture")?);
+        let version = Version::parse(required("Version")?).map_err(PackageError::InvalidVersion)?;
+
+        let versions = self.packages.entry((name, architecture)).or_default();
+        if let Some(existing) = versions
+            .iter_mut()
+            .find(|existing| existing.is_same_file(&paragraph))
+        {
+            if !existing.releases.contains(release) {
+                existing.releases.push(release.clone());
+            }
+            return Ok(false);
+        }
+        // Insert after all versions that are higher or equal, so equal versions keep their order.
+        let position = versions.partition_point(|existing| {
+            Version::parse(existing.version()).is_ok_and(|existing| existing >= version)
+        });
+        versions.insert(
+            position,
+            PackageVersion {
+                paragraph,
+                releases: vec![release.clone()],
+            },
+        );
+        Ok(true)
+    }
+
+    /// Return all versions of the given package for the given architecture, from highest to
+    /// lowest.
+    pub fn versions(&self, name: &str, architecture: &str) -> &[PackageVersion<'a>] {
+        self.packages
+            .get(&(String::from(name), String::from(architecture)))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Return the package names and architectures in the universe with their versions.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &[PackageVersion<'a>])> {
+        self.packages
+            .iter()
+            .map(|((name, arch), versions)| (name.as_str(), arch.as_str(), versions.as_slice()))
+    }
+
+    /// Select the candidate version of the given package for the given architecture.
+    ///
+    /// As in APT, this is the version with the highest pin priority, and the highest version
+    /// among those with the same priority. Versions with a negative priority are never selected.
+    pub fn candidate(
+        &self,
+        name: &str,
+        architecture: &str,
+        preferences: Option<&Preferences<'_>>,
+    ) -> Option<&PackageVersion<'a>> {
+        let mut candidate = None;
+        let mut highest = -1;
+        for version in self.versions(name, architecture) {
+            let priority = version.priority(preferences);
+            if priority > highest {
+                highest = priority;
+                candidate = Some(version);
+            }
+        }
+        candidate
+    }
+
+    /// Return the candidate versions of all packages, ordered by name and architecture.
+    ///
+    /// Their paragraphs can be used to build a [`PackageSet`](struct.PackageSet.html).
+    pub fn candidates<'s>(
+        &'s self,
+        preferences: Option<&'s Preferences<'_>>,
+    ) -> impl Iterator<Item = &'s PackageVersion<'a>> {
+        self.packages
+            .keys()
+            .filter_map(move |(name, arch)| self.candidate(name, arch, preferences))
+    }
+
+    /// Return the number of versions in the universe.
+    pub fn len(&self) -> usize {
+        self.packages.values().map(Vec::len).sum()
+    }
+
+    /// Return true if the universe is empty.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, VersionError};
+    use indoc::indoc;
+
+    const STABLE: &str = indoc!(
+        "
+        Package: oma
+        Version: 1.3.0
+        Architecture: amd64
+        SHA256: 01
+
+        Package: oma
+        Version: 1.3.0
+        Architecture: arm64
+        SHA256: 02
+
+        Package: apt
+        Version: 2.7.0
+        Architecture: amd64
+        SHA256: 03
+        "
+    );
+
+    const TESTING: &str = indoc!(
+        "
+        Package: oma
+        Version: 1.14.0~rc1
+        Architecture: amd64
+        SHA256: 04
+
+        Package: oma
+        Version: 1.3.0
+        Architecture: amd64
+        SHA256: 01
+
+        Package: apt
+        Version: 1:2.5
+        Architecture: amd64
+        SHA256: 05
+
+        Package: apt
+        Version: 2.7.0
+        Architecture: amd64
+        SHA256: 06
+        "
+    );
+
+    fn release(archive: &'static str) -> ReleaseInfo<'static> {
+        ReleaseInfo {
+            archive: Some(archive),
+            ..ReleaseInfo::default()
+        }
+    }
+
+    fn universe() -> PackageUniverse<'static> {
+        let mut universe = PackageUniverse::new();
+        for (input, archive) in [(STABLE, "stable"), (TESTING, "testing")].iter() {
+            for paragraph in parse_str(input).unwrap() {
+                universe
+                    .insert(OwnedParagraph::from(paragraph), &release(archive))
+                    .unwrap();
+            }
+        }
+        universe
+    }
+
+    fn versions<'u>(universe: &'u PackageUniverse<'_>, name: &str) -> Vec<(&'u str, usize)> {
+        universe
+            .versions(name, "amd64")
+            .iter()
+            .map(|version| (version.version(), version.releases.len()))
+            .collect()
+    }
+
+    #[test]
+    fn should_order_versions_and_merge_identical_files() {
+        let universe = universe();
+        assert_eq!(universe.len(), 6);
+        assert_eq!(
+            versions(&universe, "oma"),
+            vec![("1.14.0~rc1", 1), ("1.3.0", 2)]
+        );
+        assert_eq!(
+            universe.versions("oma", "amd64")[1].releases,
+            vec![release("stable"), release("testing")]
+        );
+        assert_eq!(
+            versions(&universe, "apt"),
+            vec![("1:2.5", 1), ("2.7.0", 1), ("2.7.0", 1)]
+        );
+        assert_eq!(universe.versions("apt", "amd64")[1].sha256(), Some("03"));
+        assert_eq!(universe.versions("oma", "arm64").len(), 1);
+        assert!(universe.versions("oma", "i386").is_empty());
+    }
+
+    #[test]
+    fn should_merge_paragraphs_without_checksums_only_if_identical() {
+        let mut universe = PackageUniverse::new();
+        let paragraphs = parse_str(indoc!(
+            "
+            Package: a
+            Version: 1
+            Architecture: all
+
+            Package: a
+            Version: 1
+            Architecture: all
+
+            Package: a
+            Version: 1
+            Architecture: all
+            Size: 10
+            "
+        ))
+        .unwrap();
+        let inserted = paragraphs
+            .into_iter()
+            .map(|paragraph| {
+                universe
+                    .insert(OwnedParagraph::from(paragraph), &release("stable"))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(inserted, vec![true, false, true]);
+        assert_eq!(universe.versions("a", "all")[0].releases.len(), 1);
+    }
+
+    #[test]
+    fn should_reject_invalid_paragraphs() {
+        let mut universe = PackageUniverse::new();
+        let mut insert = |input| {
+            let paragraph = OwnedParagraph::from(parse_str(input).unwrap().remove(0));
+            universe.insert(paragraph, &ReleaseInfo::default())
+        };
+        assert_eq!(
+            insert("Version: 1\nArchitecture: all\n"),
+            Err(PackageError::MissingField("Package"))
+        );
+        assert_eq!(
+            insert("Package: a\nVersion: 1\n"),
+            Err(PackageError::MissingField("Architecture"))
+        );
+        assert_eq!(
+            insert("Package: a\nVersion: a1\nArchitecture: all\n"),
+            Err(PackageError::InvalidVersion(VersionError {
+                version: String::from("a1"),
+                reason: "version number does not start with digit",
+            }))
+        );
+        assert!(universe.is_empty());
+    }
+
+    #[test]
+    fn should_select_candidate_by_priority_and_version() {
+        let universe = universe();
+        let candidate = |name, preferences| {
+            universe
+                .candidate(name, "amd64", preferences)
+                .map(PackageVersion::version)
+        };
+        assert_eq!(candidate("oma", None), Some("1.14.0~rc1"));
+        assert_eq!(candidate("apt", None), Some("1:2.5"));
+        assert_eq!(candidate("dpkg", None), None);
+
+        let paragraphs = parse_str(indoc!(
+            "
+            Package: *
+            Pin: release a=testing
+            Pin-Priority: 100
+
+            Package: apt
+            Pin: version 1:*
+            Pin-Priority: -1
+            "
+        ))
+        .unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        assert_eq!(candidate("oma", Some(&preferences)), Some("1.3.0"));
+        assert_eq!(candidate("apt", Some(&preferences)), Some("2.7.0"));
+        assert_eq!(
+            universe
+                .candidate("apt", "amd64", Some(&preferences))
+                .and_then(PackageVersion::sha256),
+            Some("03")
+        );
+
+        let candidates = universe
+            .candidates(Some(&preferences))
+            .map(|version| (version.name(), version.architecture(), version.version()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            candidates,
+            vec![
+                ("apt", "amd64", "2.7.0"),
+                ("oma", "amd64", "1.3.0"),
+                ("oma", "arm64", "1.3.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_skip_versions_with_negative_priority() {
+        let universe = universe();
+        let paragraphs = parse_str("Package: oma\nPin: version *\nPin-Priority: -10\n").unwrap();
+        let preferences = Preferences::from_paragraphs(&paragraphs).unwrap();
+        assert_eq!(universe.candidate("oma", "amd64", Some(&preferences)), None);
+    }
+}
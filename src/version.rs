@@ -0,0 +1,380 @@
+//! Parsing and comparison of Debian package versions.
+
+use crate::{VersionConstraint, VersionOperator};
+use alloc::string::String;
+use core::{cmp::Ordering, fmt, str::FromStr};
+
+/// A version that couldn't be parsed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VersionError {
+    /// The offending version.
+    pub version: String,
+    /// What is wrong with the version.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid version '{}': {}", self.version, self.reason)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VersionError {}
+
+/// A Debian package version, consisting of an epoch, an upstream version and a revision.
+///
+/// Versions are compared the way dpkg compares them, so `1.0` and `0:1.0-0` are equal even though
+/// they are written differently. See
+/// [Debian Policy 5.6.12](https://www.debian.org/doc/debian-policy/ch-controlfields.html#version).
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::Version;
+/// let version = Version::parse("1:2.36.1-8+deb11u1").unwrap();
+/// assert_eq!(version.epoch, 1);
+/// assert_eq!(version.upstream, "2.36.1");
+/// assert_eq!(version.revision, Some("8+deb11u1"));
+///
+/// assert!(Version::parse("1.0~rc1").unwrap() < Version::parse("1.0").unwrap());
+/// assert!(Version::parse("1.0").unwrap() < Version::parse("1.0+b1").unwrap());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Version<'a> {
+    pub epoch: u32,
+    pub upstream: &'a str,
+    /// The Debian revision. A missing revision compares equal to `0`.
+    pub revision: Option<&'a str>,
+}
+
+impl<'a> Version<'a> {
+    /// Parse a version the way dpkg does.
+    ///
+    /// Leading and trailing whitespace is ignored. The upstream version must start with a digit
+    /// and only contain alphanumerics and `.+-~:`; the revision must only contain alphanumerics
+    /// and `.+~`.
+    pub fn parse(version: &'a str) -> Result<Version<'a>, VersionError> {
+        let invalid = |reason| VersionError {
+            version: String::from(version),
+            reason,
+        };
+        let trimmed = version.trim();
+        if trimmed.is_empty() {
+            return Err(invalid("version string is empty"));
+        }
+        if trimmed.contains(char::is_whitespace) {
+            return Err(invalid("version string has embedded spaces"));
+        }
+
+        let (epoch, rest) = match trimmed.split_once(':') {
+            Some((epoch, rest)) => {
+                if epoch.is_empty() {
+                    return Err(invalid("epoch in version is empty"));
+                }
+                if !epoch.bytes().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid("epoch in version is not number"));
+                }
+                let epoch = epoch
+                    .parse()
+                    .map_err(|_| invalid("epoch in version is too big"))?;
+                if rest.is_empty() {
+                    return Err(invalid("nothing after colon in version number"));
+                }
+                (epoch, rest)
+            }
+            None => (0, trimmed),
+        };
+        let (upstream, revision) = match rest.rsplit_once('-') {
+            Some((_, "")) => return Err(invalid("revision number is empty")),
+            Some((upstream, revision)) => (upstream, Some(revision)),
+            None => (rest, None),
+        };
+
+        if upstream.is_empty() {
+            return Err(invalid("version number is empty"));
+        }
+        if !upstream.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(invalid("version number does not start with digit"));
+        }
+        if !upstream
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".-+~:".contains(c))
+        {
+            return Err(invalid("invalid character in version number"));
+        }
+        if !revision
+            .unwrap_or_default()
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".+~".contains(c))
+        {
+            return Err(invalid("invalid character in revision number"));
+        }
+        Ok(Version {
+            epoch,
+            upstream,
+            revision,
+        })
+    }
+}
+
+/// Return the sort weight of a non-digit character, as in dpkg's `order`.
+fn order(c: Option<u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+        Some(b'~') => -1,
+        Some(c) => i32::from(c) + 256,
+    }
+}
+
+/// Compare two upstream versions or revisions, as in dpkg's `verrevcmp`.
+///
+/// Non-digit parts are compared character by character, with letters sorting before other
+/// characters and `~` sorting before everything, even the end of the string. Digit parts are
+/// compared numerically.
+fn compare_part(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    let is_digit = |s: &[u8], i: usize| s.get(i).is_some_and(u8::is_ascii_digit);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !is_digit(a, i)) || (j < b.len() && !is_digit(b, j)) {
+            let (ac, bc) = (order(a.get(i).copied()), order(b.get(j).copied()));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while is_digit(a, i) && is_digit(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if is_digit(a, i) {
+            return Ordering::Greater;
+        }
+        if is_digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+impl Ord for Version<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| compare_part(self.upstream, other.upstream))
+            .then_with(|| {
+                compare_part(
+                    self.revision.unwrap_or_default(),
+                    other.revision.unwrap_or_default(),
+                )
+            })
+    }
+}
+
+impl PartialOrd for Version<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version<'_> {}
+
+impl fmt::Display for Version<'_> {
+    /// Write the version, omitting a zero epoch.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.epoch > 0 {
+            write!(f, "{}:", self.epoch)?;
+        }
+        f.write_str(self.upstream)?;
+        if let Some(revision) = self.revision {
+            write!(f, "-{}", revision)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two versions, like `dpkg --compare-versions`.
+pub fn compare_versions(a: &str, b: &str) -> Result<Ordering, VersionError> {
+    Ok(Version::parse(a)?.cmp(&Version::parse(b)?))
+}
+
+impl VersionOperator {
+    /// Return true if a version that compares to the required version with the given ordering
+    /// satisfies this operator.
+    pub fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            VersionOperator::Earlier => ordering == Ordering::Less,
+            VersionOperator::EarlierOrEqual => ordering != Ordering::Greater,
+            VersionOperator::Equal => ordering == Ordering::Equal,
+            VersionOperator::LaterOrEqual => ordering != Ordering::Less,
+            VersionOperator::Later => ordering == Ordering::Greater,
+        }
+    }
+
+    /// Parse an operator as written in relationship fields or `dpkg --compare-versions`.
+    ///
+    /// The obsolete `<` and `>` are accepted as `<=` and `>=`.
+    fn parse(operator: &str) -> Option<Self> {
+        match operator {
+            "<<" | "lt" => Some(VersionOperator::Earlier),
+            "<=" | "<" | "le" => Some(VersionOperator::EarlierOrEqual),
+            "=" | "eq" => Some(VersionOperator::Equal),
+            ">=" | ">" | "ge" => Some(VersionOperator::LaterOrEqual),
+            ">>" | "gt" => Some(VersionOperator::Later),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for VersionOperator {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VersionOperator::parse(s).ok_or(())
+    }
+}
+
+impl VersionConstraint<'_> {
+    /// Return true if the version satisfies this constraint.
+    ///
+    /// Fails if the version in the constraint is invalid, for example because it is still a
+    /// substitution variable such as `${binary:Version}`.
+    pub fn matches(&self, version: &Version<'_>) -> Result<bool, VersionError> {
+        let required = Version::parse(self.version)?;
+        Ok(self.operator.accepts(version.cmp(&required)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn version(version: &str) -> Version<'_> {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn should_parse_version_parts() {
+        let v = version(" 2:1.2-3-4ubuntu1 ");
+        assert_eq!(v.epoch, 2);
+        assert_eq!(v.upstream, "1.2-3");
+        assert_eq!(v.revision, Some("4ubuntu1"));
+        let v = version("1.0");
+        assert_eq!((v.epoch, v.upstream, v.revision), (0, "1.0", None));
+        assert_eq!(version("0:1.0-1").to_string(), "1.0-1");
+        assert_eq!(version("1:1.0").to_string(), "1:1.0");
+    }
+
+    #[test]
+    fn should_reject_invalid_versions() {
+        let cases = [
+            ("", "version string is empty"),
+            ("1 0", "version string has embedded spaces"),
+            (":1.0", "epoch in version is empty"),
+            ("a:1.0", "epoch in version is not number"),
+            ("99999999999:1.0", "epoch in version is too big"),
+            ("1:", "nothing after colon in version number"),
+            ("1.0-", "revision number is empty"),
+            ("-1", "version number is empty"),
+            ("a1.0", "version number does not start with digit"),
+            ("1.0_1", "invalid character in version number"),
+            ("1.0-1_2", "invalid character in revision number"),
+            ("${binary:Version}", "epoch in version is not number"),
+        ];
+        for (value, reason) in cases.iter() {
+            assert_eq!(
+                Version::parse(value),
+                Err(VersionError {
+                    version: value.to_string(),
+                    reason
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn should_compare_like_dpkg() {
+        // Taken from the dpkg test suite (t-version.c and Dpkg_Version.t).
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "1.0-0", Ordering::Equal),
+            ("0:1.0", "1.0", Ordering::Equal),
+            ("1.0", "1.00", Ordering::Equal),
+            ("1.0", "1.1", Ordering::Less),
+            ("1:1.0", "2.0", Ordering::Greater),
+            ("1.0-1", "1.0-2", Ordering::Less),
+            ("1.0~rc1", "1.0", Ordering::Less),
+            ("1.0~~", "1.0~~a", Ordering::Less),
+            ("1.0~~a", "1.0~", Ordering::Less),
+            ("1.0~", "1.0", Ordering::Less),
+            ("1.0", "1.0a", Ordering::Less),
+            ("1.0a", "1.0+", Ordering::Less),
+            ("1.0+", "1.0.", Ordering::Less),
+            ("1.0", "1.0+b1", Ordering::Less),
+            ("1.2.3", "1.2.10", Ordering::Less),
+            (
+                "2.7.4+reloaded2-13ubuntu1",
+                "2.7.4+reloaded2-13+deb9u1",
+                Ordering::Less,
+            ),
+            ("0.9", "0.10", Ordering::Less),
+            ("1:1.18.36:5.4", "1:1.18.36:5.5", Ordering::Less),
+            ("9:1.18.36:5.4-20", "8:1.18.36:5.5-1", Ordering::Greater),
+            ("1.0-1ubuntu1", "1.0-1", Ordering::Greater),
+        ];
+        for (a, b, expected) in cases.iter() {
+            assert_eq!(compare_versions(a, b), Ok(*expected), "{} {}", a, b);
+            assert_eq!(
+                compare_versions(b, a),
+                Ok(expected.reverse()),
+                "{} {}",
+                b,
+                a
+            );
+        }
+    }
+
+    #[test]
+    fn should_check_constraints() {
+        let cases = [
+            ("<<", "1.0", "1.1", true),
+            ("<<", "1.1", "1.1", false),
+            ("<=", "1.1", "1.1", true),
+            ("=", "1.1", "1.1-0", true),
+            (">=", "1.0", "1.1", false),
+            (">>", "1.2", "1.1", true),
+        ];
+        for (operator, actual, required, expected) in cases.iter() {
+            let constraint = VersionConstraint {
+                operator: operator.parse().unwrap(),
+                version: required,
+            };
+            assert_eq!(constraint.matches(&version(actual)), Ok(*expected));
+        }
+        assert_eq!("lt".parse(), Ok(VersionOperator::Earlier));
+        assert_eq!("=>".parse::<VersionOperator>(), Err(()));
+    }
+}
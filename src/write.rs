@@ -0,0 +1,156 @@
+//! Serialization of paragraphs back into control file syntax.
+
+use crate::{Field, OwnedField, OwnedParagraph, Paragraph};
+use core::fmt;
+
+/// Write a single field definition, including its continuation lines.
+///
+/// The first line of the value is written on the field definition line. Every further line is
+/// written as a continuation line indented by a single space. Empty lines can't be represented in
+/// control files, so they are written as a single `.`, which is the conventional placeholder for a
+/// blank line in multiline fields.
+fn write_field(f: &mut impl fmt::Write, name: &str, value: &str) -> fmt::Result {
+    let mut lines = value.split('\n');
+    f.write_str(name)?;
+    f.write_char(':')?;
+    if let Some(first) = lines.next() {
+        if !first.is_empty() {
+            f.write_char(' ')?;
+            f.write_str(first)?;
+        }
+    }
+    f.write_char('\n')?;
+    for line in lines {
+        if line.trim().is_empty() {
+            f.write_str(" .\n")?;
+        } else {
+            f.write_char(' ')?;
+            f.write_str(line)?;
+            f.write_char('\n')?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Field<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_field(f, self.name, &self.value)
+    }
+}
+
+impl fmt::Display for OwnedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_field(f, &self.name, &self.value)
+    }
+}
+
+impl fmt::Display for Paragraph<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.fields {
+            write_field(f, field.name, &field.value)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OwnedParagraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.fields {
+            write_field(f, &field.name, &field.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the given paragraphs as a control file.
+///
+/// Every paragraph is written using its `Display` implementation, and paragraphs are separated by a
+/// single blank line. Parsing the output with [`parse_str`](fn.parse_str.html) produces the same
+/// paragraphs again, except for empty value lines which are written as `.`.
+///
+/// # Example
+/// ```
+/// # use oma_debcontrol::{parse_str, write_paragraphs};
+/// let paragraphs = parse_str("a: 1\n\nb: 2\n 3\n").unwrap();
+/// let mut output = String::new();
+/// write_paragraphs(&mut output, &paragraphs).unwrap();
+/// assert_eq!(output, "a: 1\n\nb: 2\n 3\n");
+/// ```
+pub fn write_paragraphs<'p, W, P>(
+    f: &mut W,
+    paragraphs: impl IntoIterator<Item = &'p P>,
+) -> fmt::Result
+where
+    W: fmt::Write,
+    P: fmt::Display + 'p,
+{
+    for (i, paragraph) in paragraphs.into_iter().enumerate() {
+        if i > 0 {
+            f.write_char('\n')?;
+        }
+        write!(f, "{}", paragraph)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, tests::field};
+    use alloc::{string::String, string::ToString, vec};
+    use indoc::indoc;
+
+    #[test]
+    fn should_write_single_line_field() {
+        assert_eq!(field("Package", "foo").to_string(), "Package: foo\n");
+    }
+
+    #[test]
+    fn should_write_continuation_lines() {
+        let result = field("Description", "synopsis\nline 1\nline 2").to_string();
+        assert_eq!(result, "Description: synopsis\n line 1\n line 2\n");
+    }
+
+    #[test]
+    fn should_write_empty_first_line_without_trailing_space() {
+        let result = field("Uploaders", "\na <a@example.org>,\nb <b@example.org>").to_string();
+        assert_eq!(
+            result,
+            "Uploaders:\n a <a@example.org>,\n b <b@example.org>\n"
+        );
+    }
+
+    #[test]
+    fn should_write_empty_lines_as_dot() {
+        let result = field("Description", "synopsis\npara 1\n\npara 2").to_string();
+        assert_eq!(result, "Description: synopsis\n para 1\n .\n para 2\n");
+    }
+
+    #[test]
+    fn should_round_trip_paragraphs() {
+        let input = indoc!(
+            "
+            Source: foo
+            Build-Depends:
+             a,
+             b
+
+            Package: foo
+            Description: synopsis
+             text
+            "
+        )
+        .trim_start();
+        let paragraphs = parse_str(input).unwrap();
+        let mut output = String::new();
+        write_paragraphs(&mut output, &paragraphs).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn should_write_nothing_for_no_paragraphs() {
+        let mut output = String::new();
+        write_paragraphs::<_, Paragraph>(&mut output, &vec![]).unwrap();
+        assert_eq!(output, "");
+    }
+}
@@ -0,0 +1,45 @@
+use oma_debcontrol::{parse_str, FieldOrder};
+use std::{fs::read_to_string, path::PathBuf};
+
+fn data_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("order")
+        .join(name)
+}
+
+fn canonicalize(name: &str, order: FieldOrder) -> String {
+    let input = read_to_string(data_file(name)).unwrap();
+    let mut paragraph = parse_str(&input).unwrap().remove(0);
+    paragraph.canonicalize(order);
+    paragraph.to_string()
+}
+
+#[test]
+fn should_order_packages_stanza_like_apt() {
+    let expected = read_to_string(data_file("Packages.apt")).unwrap();
+    assert_eq!(canonicalize("Packages", FieldOrder::BinaryIndex), expected);
+    assert_eq!(
+        canonicalize("Packages.apt", FieldOrder::BinaryIndex),
+        expected
+    );
+}
+
+#[test]
+fn should_order_sources_stanza_like_apt() {
+    let expected = read_to_string(data_file("Sources.apt")).unwrap();
+    assert_eq!(canonicalize("Sources", FieldOrder::SourceIndex), expected);
+    assert_eq!(
+        canonicalize("Sources.apt", FieldOrder::SourceIndex),
+        expected
+    );
+}
+
+#[test]
+fn should_keep_dpkg_status_stanza_order() {
+    let input = read_to_string(data_file("status")).unwrap();
+    let original = parse_str(&input).unwrap().remove(0);
+    let mut paragraph = original.clone();
+    paragraph.canonicalize(FieldOrder::Status);
+    assert_eq!(paragraph, original);
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "std")]
+
+use oma_debcontrol::{BufParse, InstallabilityChecker, PackageSet};
+use std::{fs::File, path::PathBuf};
+
+fn data_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("packages")
+}
+
+#[test]
+fn should_check_installability_of_packages_file() {
+    let mut parse = BufParse::new(File::open(data_file()).unwrap(), 256);
+    let mut paragraphs = Vec::new();
+    while let Some(paragraph) = parse.next_owned().unwrap() {
+        paragraphs.push(paragraph);
+    }
+
+    let set = PackageSet::from_paragraphs("amd64", &paragraphs).unwrap();
+    let checker = InstallabilityChecker::new(&set).unwrap();
+    let broken = checker
+        .check_all()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        broken,
+        vec![
+            "\
+gawk 1:5.2.1-2 (amd64) is not installable:
+  nothing satisfies libmpfr6 (>= 3.1.3) of gawk 1:5.2.1-2 (amd64)
+",
+            "\
+nscd 2.35-1 (amd64) is not installable:
+  nscd 2.35-1 (amd64) depends on libc6 (>= 2.35)
+  libc6 2.36-9+deb12u4 (amd64) conflicts with nscd 2.35-1 (amd64) through nscd (<< 2.36)
+",
+            "\
+sendfile 2.1b.20080616-5.1 (amd64) is not installable:
+  sendfile 2.1b.20080616-5.1 (amd64) depends on base-files
+  base-files 12.4+deb12u5 (amd64) conflicts with sendfile 2.1b.20080616-5.1 (amd64) through sendfile (<< 2.1b.20080616-5.2~)
+",
+        ]
+    );
+
+    let bash = checker
+        .installation_set(0)
+        .unwrap()
+        .into_iter()
+        .map(|package| package.name)
+        .collect::<Vec<_>>();
+    assert!(bash.contains(&"mawk"));
+    assert!(!bash.contains(&"gawk"));
+}
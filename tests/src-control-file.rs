@@ -1,4 +1,7 @@
-use oma_debcontrol::{parse_str, BufParse, BufParseInput, Streaming};
+use oma_debcontrol::{
+    formats::debian_control::ControlFile, parse_str, write_relations, BufParse, BufParseInput,
+    BuildContext, Streaming,
+};
 use std::{
     fs::{read_to_string, File},
     io::Read as IoRead,
@@ -84,3 +87,81 @@ fn should_parse_control_file_streaming() {
         ]
     );
 }
+
+#[test]
+fn should_parse_control_file_losslessly() {
+    let input = read_to_string(data_file()).unwrap();
+    let control = ControlFile::parse(&input).unwrap();
+
+    assert_eq!(control.to_string(), input);
+    assert_eq!(control.validate(), vec![]);
+    let source = control.source().unwrap();
+    assert_eq!(source.name(), Some("ostree"));
+    assert_eq!(source.standards_version(), Some("4.4.1"));
+    assert_eq!(source.build_depends_indep(), Some("\nlibglib2.0-doc,"));
+    assert_eq!(control.binaries().count(), 7);
+}
+
+#[test]
+fn should_split_uploaders_list() {
+    let input = read_to_string(data_file()).unwrap();
+    let paragraphs = parse_str(&input).unwrap();
+    let uploaders = paragraphs[0].field("Uploaders").unwrap();
+    assert_eq!(
+        uploaders.list(),
+        vec![
+            "Matthias Klumpp <mak@debian.org>",
+            "Simon McVittie <smcv@debian.org>",
+            "Sjoerd Simons <sjoerd@debian.org>",
+        ]
+    );
+}
+
+#[test]
+fn should_reduce_build_depends() {
+    let input = read_to_string(data_file()).unwrap();
+    let control = ControlFile::parse(&input).unwrap();
+    let source = control.source().unwrap();
+    let names = |context: &BuildContext<'static>| {
+        source
+            .build_dependencies(context)
+            .unwrap()
+            .into_iter()
+            .map(|alternatives| alternatives[0].name)
+            .collect::<Vec<_>>()
+    };
+
+    let all = names(&BuildContext::native("amd64"));
+    assert_eq!(all.len(), 37);
+    assert_eq!(all.last(), Some(&"libglib2.0-doc"));
+
+    let context = BuildContext {
+        profiles: vec!["nocheck", "nodoc"],
+        ..BuildContext::native("amd64")
+    };
+    let reduced = names(&context);
+    assert_eq!(reduced.len(), 30);
+    for name in [
+        "docbook-xml",
+        "docbook-xsl",
+        "gnupg",
+        "gtk-doc-tools",
+        "python3",
+        "python3-yaml",
+        "xsltproc",
+    ]
+    .iter()
+    {
+        assert!(all.contains(name));
+        assert!(!reduced.contains(name));
+    }
+
+    let relations = source.build_dependencies(&context).unwrap();
+    let mut output = String::new();
+    write_relations(&mut output, &relations[6..8]).unwrap();
+    assert_eq!(output, "debhelper-compat (= 12), dh-exec (>= 0.23~)");
+    assert!(relations
+        .iter()
+        .flatten()
+        .all(|relation| relation.profiles.is_empty()));
+}
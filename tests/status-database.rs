@@ -0,0 +1,56 @@
+#![cfg(feature = "std")]
+
+use oma_debcontrol::{
+    formats::status::{Database, Flag, State, Status, Want},
+    BufParse,
+};
+use std::{
+    env,
+    fs::{self, File},
+    path::PathBuf,
+    process,
+};
+
+const STATUS: &str = "\
+Package: foo
+Status: install ok unpacked
+Architecture: amd64
+Version: 1.0-1
+X-Unknown: value
+
+Package: bar
+Status: install ok installed
+Architecture: all
+Version: 2.0
+";
+
+fn temp_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("oma-debcontrol-{}-{}", process::id(), name))
+}
+
+#[test]
+fn should_read_and_atomically_write_status_database() {
+    let path = temp_file("status");
+    fs::write(&path, STATUS).unwrap();
+
+    let mut parse = BufParse::new(File::open(&path).unwrap(), 16);
+    let mut db = Database::read(&mut parse).unwrap();
+    db.get_mut("foo", Some("amd64"))
+        .unwrap()
+        .set_status(Status {
+            want: Want::Install,
+            flag: Flag::Ok,
+            state: State::Installed,
+        });
+    db.write_atomic(&path).unwrap();
+
+    let written = fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        written,
+        STATUS.replacen("install ok unpacked", "install ok installed", 1)
+    );
+    let mut tmp = path.clone().into_os_string();
+    tmp.push("-new");
+    assert!(!PathBuf::from(tmp).exists());
+    fs::remove_file(&path).unwrap();
+}